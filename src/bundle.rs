@@ -0,0 +1,117 @@
+use std::fmt;
+use std::io::{self, ErrorKind, Read};
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::{Game, Location, Platform, Save, Snapshot};
+use crate::error::Error;
+use crate::filesystem::FileFilter;
+
+/// The extension of bundle files.
+pub const BUNDLE_EXTENSION: &str = "sshbundle";
+
+/// The name of the file inside a bundle that describes its contents. It is always the first file.
+pub const BUNDLE_FILE_NAME: &str = "bundle.json";
+
+/// The folder inside a bundle holding a folder with the files of every snapshot.
+pub const BUNDLE_SNAPSHOTS_FOLDER: &str = "snapshots";
+
+/// The folder inside a bundle holding the objects of its deduplicated snapshots.
+pub const BUNDLE_OBJECTS_FOLDER: &str = "objects";
+
+/// The version of the bundle layout written by this version of the app.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// A game with its saves and their snapshots, exported to a single file to import into another database.
+///
+/// The rows keep the IDs they had in the database they were exported from. Importing inserts them with
+/// new IDs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameBundle {
+    pub version: u32,
+    pub game: Game,
+    pub saves: Vec<BundledSave>,
+    /// Whether the bundle contains the key file of the backup folder its encrypted snapshots came from.
+    #[serde(default)]
+    pub has_key_file: bool,
+    /// The number of files in the bundle besides this description.
+    pub total_files: usize,
+    /// The size of all files in the bundle besides this description.
+    pub total_bytes: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundledSave {
+    pub save: Save,
+    pub platform: Platform,
+    pub location: Location,
+    /// The snapshots of the save, oldest first.
+    pub snapshots: Vec<BundledSnapshot>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundledSnapshot {
+    /// The snapshot row. Its backup path is the folder holding its files inside the bundle.
+    pub snapshot: Snapshot,
+    /// The name of the snapshot folder in the backup folder it was exported from.
+    pub folder_name: String,
+}
+
+impl GameBundle {
+    /// Reads the description at the start of a bundle.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it cannot be parsed, was written by a newer version of the
+    /// app or has a save file location with file patterns that are not valid.
+    pub fn read(reader: impl Read) -> Result<Self, Error> {
+        let bundle: GameBundle = serde_json::from_reader(reader).map_err(|e| {
+            if e.is_io() {
+                Error::Io(e.into())
+            } else {
+                Error::Validation(format!("the bundle is not valid: {}", e))
+            }
+        })?;
+
+        if bundle.version > BUNDLE_VERSION {
+            return Err(Error::Validation(format!(
+                "the bundle was made by a newer version (layout {})",
+                bundle.version
+            )));
+        }
+        for save in &bundle.saves {
+            FileFilter::for_location(&save.location)?;
+        }
+        Ok(bundle)
+    }
+
+    /// Returns the description as JSON.
+    pub fn to_json(&self) -> Result<Vec<u8>, io::Error> {
+        serde_json::to_vec_pretty(self).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+/// What importing a bundle added to the database.
+#[derive(Clone, Debug, Default)]
+pub struct ImportReport {
+    /// The ID the imported game got.
+    pub game_id: i32,
+    pub title: String,
+    pub snapshots_imported: usize,
+    /// Encrypted snapshots that were left out because the backup folder uses a different passphrase.
+    pub snapshots_skipped: usize,
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "imported '{}' with {} snapshots", self.title, self.snapshots_imported)?;
+        if self.snapshots_skipped > 0 {
+            write!(
+                f,
+                ", skipped {} encrypted snapshots because this backup folder uses a different passphrase",
+                self.snapshots_skipped
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,444 @@
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+/// The name of the file in the backup folder that holds what is needed to check a passphrase and derive
+/// the key from it.
+pub const KEY_FILE_NAME: &str = "encryption.json";
+
+/// The first bytes of every encrypted file.
+const MAGIC: &[u8; 8] = b"SSHENC01";
+
+const SALT_LEN: usize = 16;
+
+/// The length of the nonce prefix of the STREAM construction, the nonce minus the 5 bytes of the counter.
+const NONCE_PREFIX_LEN: usize = 19;
+
+/// How many bytes of plain text are encrypted and authenticated at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const TAG_LEN: usize = 16;
+
+/// The text encrypted into the key file to check whether a passphrase is the right one.
+const VERIFIER_TEXT: &[u8] = b"sharks_save_haven backup key";
+
+/// The keys unlocked with their passphrase since the process started. They are never written anywhere.
+static UNLOCKED_KEYS: Mutex<Vec<BackupKey>> = Mutex::new(Vec::new());
+
+/// A key derived from the backup passphrase.
+#[derive(Clone)]
+pub struct BackupKey {
+    /// The salt the key was derived with, which also tells encrypted files which key they need.
+    salt: [u8; SALT_LEN],
+    key: Key,
+}
+
+/// The key file in the backup folder. It holds the key derivation parameters and a text encrypted with
+/// the key, but not the key itself.
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyFile {
+    salt: String,
+    memory_cost: u32,
+    time_cost: u32,
+    parallelism: u32,
+    verifier_nonce: String,
+    verifier: String,
+}
+
+impl KeyFile {
+    fn load(backup_root: &Path) -> Result<Self, io::Error> {
+        let contents = fs::read_to_string(backup_root.join(KEY_FILE_NAME))?;
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+
+    fn salt(&self) -> Result<[u8; SALT_LEN], io::Error> {
+        from_hex(&self.salt)?
+            .try_into()
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "the key file has an invalid salt"))
+    }
+
+    /// Derives the key from a passphrase and checks it against the verifier.
+    fn derive_key(&self, passphrase: &str) -> Result<BackupKey, io::Error> {
+        let salt = self.salt()?;
+        let params = Params::new(self.memory_cost, self.time_cost, self.parallelism, Some(32))
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))?;
+
+        let mut key = Key::default();
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+
+        let nonce = from_hex(&self.verifier_nonce)?;
+        if nonce.len() != 24 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "the key file has an invalid nonce"));
+        }
+        let verified = XChaCha20Poly1305::new(&key)
+            .decrypt(XNonce::from_slice(&nonce), from_hex(&self.verifier)?.as_slice())
+            .is_ok_and(|text| text == VERIFIER_TEXT);
+        if !verified {
+            return Err(io::Error::new(ErrorKind::PermissionDenied, "wrong passphrase"));
+        }
+
+        Ok(BackupKey { salt, key })
+    }
+}
+
+/// Returns whether a passphrase was set for the backups in a backup folder.
+pub fn has_key_file(backup_root: &Path) -> bool {
+    backup_root.join(KEY_FILE_NAME).is_file()
+}
+
+/// Sets the passphrase for the backups in a backup folder and unlocks the key derived from it.
+///
+/// # Arguments
+///
+/// * `backup_root` - The folder the backups are stored in.
+/// * `passphrase` - The passphrase to derive the key from.
+///
+/// # Errors
+///
+/// This function will return an error if a passphrase was already set or the key file cannot be written.
+pub fn create_key_file(backup_root: &Path, passphrase: &str) -> Result<(), io::Error> {
+    if has_key_file(backup_root) {
+        return Err(io::Error::new(
+            ErrorKind::AlreadyExists,
+            "a passphrase was already set for this backup folder",
+        ));
+    }
+
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let params = Params::default();
+
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+
+    let mut nonce = [0; 24];
+    OsRng.fill_bytes(&mut nonce);
+    let verifier = XChaCha20Poly1305::new(&key)
+        .encrypt(XNonce::from_slice(&nonce), VERIFIER_TEXT)
+        .map_err(|_| encryption_error())?;
+
+    let key_file = KeyFile {
+        salt: to_hex(&salt),
+        memory_cost: params.m_cost(),
+        time_cost: params.t_cost(),
+        parallelism: params.p_cost(),
+        verifier_nonce: to_hex(&nonce),
+        verifier: to_hex(&verifier),
+    };
+    let contents = serde_json::to_string_pretty(&key_file)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+    fs::create_dir_all(backup_root)?;
+    fs::write(backup_root.join(KEY_FILE_NAME), contents)?;
+
+    remember(BackupKey { salt, key });
+    Ok(())
+}
+
+/// Checks the passphrase of the backups in a backup folder and keeps the key derived from it in memory
+/// until the process ends.
+///
+/// # Arguments
+///
+/// * `backup_root` - The folder the backups are stored in.
+/// * `passphrase` - The passphrase to check.
+///
+/// # Errors
+///
+/// This function will return an error if no passphrase was set, the key file cannot be read or the
+/// passphrase is wrong.
+pub fn unlock(backup_root: &Path, passphrase: &str) -> Result<(), io::Error> {
+    let key = KeyFile::load(backup_root)?.derive_key(passphrase)?;
+    remember(key);
+    Ok(())
+}
+
+/// Returns the key of a backup folder if its passphrase was entered.
+pub fn unlocked_key(backup_root: &Path) -> Option<BackupKey> {
+    let salt = KeyFile::load(backup_root).ok()?.salt().ok()?;
+    find_key(&salt)
+}
+
+/// Returns whether the passphrase of a backup folder was entered.
+pub fn is_unlocked(backup_root: &Path) -> bool {
+    unlocked_key(backup_root).is_some()
+}
+
+/// Returns whether data starts like a file written by [`EncryptWriter`].
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// The error returned when encrypted backups are read or written before the passphrase was entered.
+pub fn locked_error() -> io::Error {
+    io::Error::new(ErrorKind::PermissionDenied, "the backup passphrase has not been entered")
+}
+
+fn remember(key: BackupKey) {
+    let mut keys = UNLOCKED_KEYS.lock().unwrap();
+    if !keys.iter().any(|known| known.salt == key.salt) {
+        keys.push(key);
+    }
+}
+
+fn find_key(salt: &[u8]) -> Option<BackupKey> {
+    UNLOCKED_KEYS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|key| key.salt == salt)
+        .cloned()
+}
+
+/// Encrypts everything written to it in chunks that are each authenticated, so a damaged, reordered or
+/// truncated file fails to decrypt.
+///
+/// [`EncryptWriter::finish`] has to be called after the last write, otherwise the file is incomplete.
+pub struct EncryptWriter<W: Write> {
+    inner: W,
+    encryptor: Option<EncryptorBE32<XChaCha20Poly1305>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    /// Writes the header of an encrypted file and returns a writer that encrypts with the given key.
+    pub fn new(mut inner: W, key: &BackupKey) -> Result<Self, io::Error> {
+        let mut nonce_prefix = [0; NONCE_PREFIX_LEN];
+        OsRng.fill_bytes(&mut nonce_prefix);
+
+        inner.write_all(MAGIC)?;
+        inner.write_all(&key.salt)?;
+        inner.write_all(&nonce_prefix)?;
+
+        let cipher = XChaCha20Poly1305::new(&key.key);
+        Ok(Self {
+            inner,
+            encryptor: Some(EncryptorBE32::from_aead(cipher, GenericArray::from_slice(&nonce_prefix))),
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        })
+    }
+
+    /// Encrypts the last chunk and returns the inner writer.
+    pub fn finish(mut self) -> Result<W, io::Error> {
+        let encryptor = self.encryptor.take().ok_or_else(encryption_error)?;
+        let chunk = encryptor
+            .encrypt_last(self.buffer.as_slice())
+            .map_err(|_| encryption_error())?;
+        self.inner.write_all(&chunk)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // A full chunk is only encrypted once more data follows, the last chunk is marked as such
+        if self.buffer.len() == CHUNK_SIZE && !data.is_empty() {
+            let encryptor = self.encryptor.as_mut().ok_or_else(encryption_error)?;
+            let chunk = encryptor
+                .encrypt_next(self.buffer.as_slice())
+                .map_err(|_| encryption_error())?;
+            self.inner.write_all(&chunk)?;
+            self.buffer.clear();
+        }
+
+        let len = data.len().min(CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts a file written by [`EncryptWriter`] while it is read.
+pub struct DecryptReader<R: Read> {
+    inner: R,
+    decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+    /// Encrypted bytes read ahead to tell whether a chunk is the last one.
+    pending: Vec<u8>,
+    plain: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> DecryptReader<R> {
+    /// Reads the header of an encrypted file and returns a reader that decrypts it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file isn't encrypted or the passphrase of the key it was
+    /// encrypted with has not been entered.
+    pub fn new(mut inner: R) -> Result<Self, io::Error> {
+        let mut header = [0; MAGIC.len() + SALT_LEN + NONCE_PREFIX_LEN];
+        inner.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(io::Error::new(ErrorKind::InvalidData, "the file is not encrypted"));
+        }
+
+        let salt = &header[MAGIC.len()..MAGIC.len() + SALT_LEN];
+        let nonce_prefix = &header[MAGIC.len() + SALT_LEN..];
+        let key = find_key(salt).ok_or_else(locked_error)?;
+
+        let cipher = XChaCha20Poly1305::new(&key.key);
+        Ok(Self {
+            inner,
+            decryptor: Some(DecryptorBE32::from_aead(cipher, GenericArray::from_slice(nonce_prefix))),
+            pending: Vec::with_capacity(CHUNK_SIZE + TAG_LEN + 1),
+            plain: Vec::new(),
+            position: 0,
+        })
+    }
+
+    /// Decrypts the next chunk, or returns false after the last one.
+    fn decrypt_chunk(&mut self) -> Result<bool, io::Error> {
+        if self.decryptor.is_none() {
+            return Ok(false);
+        }
+
+        // Read one byte past a whole chunk to find out whether another chunk follows
+        while self.pending.len() <= CHUNK_SIZE + TAG_LEN {
+            let mut buffer = [0; 8192];
+            let wanted = (CHUNK_SIZE + TAG_LEN + 1 - self.pending.len()).min(buffer.len());
+            let read = self.inner.read(&mut buffer[..wanted])?;
+            if read == 0 {
+                break;
+            }
+            self.pending.extend_from_slice(&buffer[..read]);
+        }
+
+        self.plain = if self.pending.len() > CHUNK_SIZE + TAG_LEN {
+            let rest = self.pending.split_off(CHUNK_SIZE + TAG_LEN);
+            let chunk = std::mem::replace(&mut self.pending, rest);
+            let decryptor = self.decryptor.as_mut().ok_or_else(decryption_error)?;
+            decryptor.decrypt_next(chunk.as_slice()).map_err(|_| decryption_error())?
+        } else {
+            let decryptor = self.decryptor.take().ok_or_else(decryption_error)?;
+            let chunk = std::mem::take(&mut self.pending);
+            decryptor.decrypt_last(chunk.as_slice()).map_err(|_| decryption_error())?
+        };
+        self.position = 0;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plain.len() {
+            if !self.decrypt_chunk()? {
+                return Ok(0);
+            }
+        }
+
+        let len = buffer.len().min(self.plain.len() - self.position);
+        buffer[..len].copy_from_slice(&self.plain[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+fn encryption_error() -> io::Error {
+    io::Error::other("failed to encrypt")
+}
+
+fn decryption_error() -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, "the encrypted file is damaged")
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, io::Error> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(io::Error::new(ErrorKind::InvalidData, "invalid hex string"));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            u8::from_str_radix(&hex[index..index + 2], 16)
+                .map_err(|_| io::Error::new(ErrorKind::InvalidData, "invalid hex string"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a key that is already unlocked. Every test uses its own salt, as the unlocked keys are
+    /// shared by the whole process.
+    fn unlocked_test_key(salt: u8) -> BackupKey {
+        let mut key = Key::default();
+        OsRng.fill_bytes(&mut key);
+        let key = BackupKey { salt: [salt; SALT_LEN], key };
+        remember(key.clone());
+        key
+    }
+
+    fn encrypt(data: &[u8], key: &BackupKey) -> Vec<u8> {
+        let mut writer = EncryptWriter::new(Vec::new(), key).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn decrypt(data: &[u8]) -> Result<Vec<u8>, io::Error> {
+        let mut plain = Vec::new();
+        DecryptReader::new(data)?.read_to_end(&mut plain)?;
+        Ok(plain)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let key = unlocked_test_key(1);
+
+        for len in [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1, 2 * CHUNK_SIZE, 5 * CHUNK_SIZE / 2] {
+            let data: Vec<u8> = (0..len).map(|index| (index % 251) as u8).collect();
+            let encrypted = encrypt(&data, &key);
+
+            assert!(is_encrypted(&encrypted));
+            assert_eq!(decrypt(&encrypted).unwrap(), data, "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn test_damaged_file_fails_to_decrypt() {
+        let key = unlocked_test_key(2);
+        let mut encrypted = encrypt(b"save file", &key);
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+
+        assert_eq!(decrypt(&encrypted).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_truncated_file_fails_to_decrypt() {
+        let key = unlocked_test_key(3);
+        let encrypted = encrypt(&vec![7; 2 * CHUNK_SIZE + 10], &key);
+        let truncated = &encrypted[..encrypted.len() - (10 + TAG_LEN)];
+
+        assert!(decrypt(truncated).is_err());
+    }
+
+    #[test]
+    fn test_locked_key_fails_to_decrypt() {
+        let mut key = Key::default();
+        OsRng.fill_bytes(&mut key);
+        let encrypted = encrypt(b"save file", &BackupKey { salt: [4; SALT_LEN], key });
+
+        assert_eq!(decrypt(&encrypted).unwrap_err().kind(), ErrorKind::PermissionDenied);
+    }
+}
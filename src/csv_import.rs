@@ -0,0 +1,250 @@
+use std::fmt;
+use std::io::Read;
+
+use chrono::NaiveDate;
+
+use crate::db::Game;
+use crate::error::{Error, Result};
+use crate::game_saves::BackupReport;
+
+/// The extension of the CSV files games are imported from.
+pub const CSV_EXTENSION: &str = "csv";
+
+/// The formats release dates are read in, tried in order.
+const DATE_FORMATS: [&str; 3] = ["%Y-%m-%d", "%Y%m%d", "%Y/%m/%d"];
+
+/// A game and its save file location read from a row of a CSV file.
+#[derive(Clone, Debug)]
+pub struct CsvGame {
+    /// The line of the row in the file.
+    pub line: u64,
+    pub game: Game,
+    pub platform: String,
+    pub save_path: String,
+}
+
+/// A row of a CSV file that could not be imported.
+#[derive(Clone, Debug)]
+pub struct CsvRowError {
+    /// The line of the row in the file.
+    pub line: u64,
+    /// The title in the row, empty if it has none.
+    pub title: String,
+    pub message: String,
+}
+
+impl fmt::Display for CsvRowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.title.is_empty() {
+            write!(f, "line {}: {}", self.line, self.message)
+        } else {
+            write!(f, "line {} ('{}'): {}", self.line, self.title, self.message)
+        }
+    }
+}
+
+/// What importing games from a CSV file added to the database.
+#[derive(Clone, Debug, Default)]
+pub struct CsvImportReport {
+    pub games_added: usize,
+    pub saves_added: usize,
+    /// The rows that were skipped.
+    pub errors: Vec<CsvRowError>,
+    /// The first backup of the added saves, if it was asked for.
+    pub backup: BackupReport,
+}
+
+impl fmt::Display for CsvImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "imported {} games with {} saves", self.games_added, self.saves_added)?;
+        if !self.backup.snapshot_ids.is_empty() {
+            write!(f, ", created {} snapshots", self.backup.snapshot_ids.len())?;
+        }
+        if !self.backup.failed_games.is_empty() {
+            write!(f, ", {} backups failed", self.backup.failed_games.len())?;
+        }
+        if !self.errors.is_empty() {
+            write!(f, ", {} rows failed", self.errors.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// The columns of a CSV file by their position.
+struct Columns {
+    title: usize,
+    publisher: Option<usize>,
+    release_date: Option<usize>,
+    platform: usize,
+    save_path: usize,
+}
+
+impl Columns {
+    /// Finds the columns by their names in the header row. Case, spaces and underscores don't matter,
+    /// so "Release date" and "release_date" are the same column.
+    fn from_header(header: &csv::StringRecord) -> Result<Self> {
+        let find = |names: &[&str]| {
+            header.iter().position(|column| {
+                let column = column
+                    .chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .collect::<String>()
+                    .to_lowercase();
+                names.contains(&column.as_str())
+            })
+        };
+        let required = |label: &str, names: &[&str]| {
+            find(names).ok_or_else(|| Error::Validation(format!("the CSV file has no {} column", label)))
+        };
+
+        Ok(Self {
+            title: required("title", &["title", "name", "game"])?,
+            publisher: find(&["publisher"]),
+            release_date: find(&["releasedate", "released", "date"]),
+            platform: required("platform", &["platform"])?,
+            save_path: required("save path", &["savepath", "path", "location", "savelocation"])?,
+        })
+    }
+}
+
+/// Reads the games from a CSV file with a header row naming its columns.
+///
+/// The title, platform and save path columns are required, the publisher and release date columns are
+/// optional. Release dates are written like 2023-04-30 or 20230430.
+///
+/// # Errors
+///
+/// This function will return an error if the file cannot be read or is missing a required column. Rows
+/// that cannot be read are returned as errors next to the games.
+pub fn read_csv_games(reader: impl Read) -> Result<Vec<Result<CsvGame, CsvRowError>>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let header = reader
+        .headers()
+        .map_err(|e| match e.kind() {
+            csv::ErrorKind::Io(_) => Error::Io(e.into()),
+            _ => Error::Validation(format!("the CSV file is not valid: {}", e)),
+        })?
+        .clone();
+    let columns = Columns::from_header(&header)?;
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let row = match record {
+            Ok(record) => read_row(&columns, &record),
+            Err(e) => Err(CsvRowError {
+                line: e.position().map_or(0, |position| position.line()),
+                title: String::new(),
+                message: e.to_string(),
+            }),
+        };
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn read_row(columns: &Columns, record: &csv::StringRecord) -> Result<CsvGame, CsvRowError> {
+    let line = record.position().map_or(0, |position| position.line());
+    let field = |column: Option<usize>| column.and_then(|column| record.get(column)).unwrap_or_default();
+    let title = field(Some(columns.title)).to_string();
+    let error = |message: String| CsvRowError { line, title: title.clone(), message };
+
+    if title.is_empty() {
+        return Err(error("the title is empty".to_string()));
+    }
+    let platform = field(Some(columns.platform));
+    if platform.is_empty() {
+        return Err(error("the platform is empty".to_string()));
+    }
+    let save_path = field(Some(columns.save_path));
+    if save_path.is_empty() {
+        return Err(error("the save path is empty".to_string()));
+    }
+
+    let release_date = field(columns.release_date);
+    let release_date = if release_date.is_empty() {
+        0
+    } else {
+        DATE_FORMATS
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(release_date, format).ok())
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .ok_or_else(|| error(format!("'{}' is not a date like 2023-04-30", release_date)))?
+            .timestamp()
+    };
+
+    Ok(CsvGame {
+        line,
+        game: Game {
+            id: -1,
+            title: title.clone(),
+            publisher: field(columns.publisher).to_string(),
+            release_date,
+        },
+        platform: platform.to_string(),
+        save_path: save_path.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(csv: &str) -> Vec<Result<CsvGame, CsvRowError>> {
+        read_csv_games(csv.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_reads_games() {
+        let rows = read("Title,Publisher,Release date,Platform,Save path\nGame, Studio ,2023-04-30,PC,/saves\n");
+
+        let game = rows[0].as_ref().unwrap();
+        assert_eq!(game.line, 2);
+        assert_eq!(game.game.title, "Game");
+        assert_eq!(game.game.publisher, "Studio");
+        assert_eq!(
+            game.game.release_date,
+            NaiveDate::from_ymd_opt(2023, 4, 30).unwrap().and_hms_opt(0, 0, 0).unwrap().timestamp()
+        );
+        assert_eq!(game.platform, "PC");
+        assert_eq!(game.save_path, "/saves");
+    }
+
+    #[test]
+    fn test_column_names_and_order_dont_matter() {
+        let rows = read("save_path,PLATFORM,name\n/saves,PC,Game\n");
+
+        let game = rows[0].as_ref().unwrap();
+        assert_eq!(game.game.title, "Game");
+        assert_eq!(game.game.publisher, "");
+        assert_eq!(game.game.release_date, 0);
+        assert_eq!(game.save_path, "/saves");
+    }
+
+    #[test]
+    fn test_release_date_formats() {
+        let rows = read("title,platform,path,date\nA,PC,/a,20230430\nB,PC,/b,2023/04/30\nC,PC,/c,30.04.2023\n");
+
+        assert_eq!(rows[0].as_ref().unwrap().game.release_date, rows[1].as_ref().unwrap().game.release_date);
+        let error = rows[2].as_ref().unwrap_err();
+        assert_eq!(error.line, 4);
+        assert_eq!(error.title, "C");
+    }
+
+    #[test]
+    fn test_rows_missing_fields_are_errors() {
+        let rows = read("title,platform,path\n,PC,/a\nB,,/b\nC,PC\n");
+
+        let messages: Vec<String> = rows.iter().map(|row| row.as_ref().unwrap_err().message.clone()).collect();
+        assert_eq!(messages, ["the title is empty", "the platform is empty", "the save path is empty"]);
+    }
+
+    #[test]
+    fn test_missing_required_column() {
+        let result = read_csv_games("title,path\nGame,/saves\n".as_bytes());
+
+        assert!(matches!(result, Err(Error::Validation(message)) if message == "the CSV file has no platform column"));
+    }
+}
@@ -1,10 +1,46 @@
-use rusqlite::{params, Connection, Result};
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use rusqlite::{ffi, params, Connection, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::library::{GameRetentionPolicy, Library, LIBRARY_VERSION};
+use crate::retention::RetentionPolicy;
+use crate::scheduler::ScheduleTiming;
+
+/// The steps that migrate the schema from one version to the next, the first one creating the tables
+/// of a new database. Add new steps at the end and never change the existing ones.
+const MIGRATIONS: [fn(&Db) -> Result<()>; 6] = [
+    Db::create_game_tables,
+    Db::create_snapshot_table,
+    Db::add_location_patterns,
+    Db::create_retention_policy_table,
+    Db::create_schedule_tables,
+    Db::cascade_deletes,
+];
+
+/// The version of the schema this version of the app uses, stored as the `user_version` of the database.
+pub const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
 
 pub struct Db {
     conn: Connection,
 }
 
-#[derive(Clone, Debug, Default)]
+/// An open savepoint of [`Db::in_transaction`], rolled back when it is dropped before it was released.
+struct Savepoint<'a> {
+    conn: &'a Connection,
+    released: bool,
+}
+
+impl Drop for Savepoint<'_> {
+    fn drop(&mut self) {
+        if !self.released {
+            let _ = self.conn.execute_batch("ROLLBACK TO changes; RELEASE changes");
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Game {
     pub id: i32,
     pub title: String,
@@ -12,17 +48,24 @@ pub struct Game {
     pub release_date: i64,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Platform {
     pub id: i32,
     pub platform_name: String,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Location {
     pub id: i32,
     pub location_path: String,
     pub description: String,
+    /// Glob patterns of the files to back up. Every file is backed up if there are none.
+    pub include_patterns: Vec<String>,
+    /// Glob patterns of the files to skip when backing up and restoring.
+    pub exclude_patterns: Vec<String>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Save {
     pub id: i32,
     pub game_id: i32,
@@ -31,6 +74,128 @@ pub struct Save {
     pub platform_id: i32,
 }
 
+/// How the files of a snapshot are stored in the backup folder.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupFormat {
+    /// A plain copy of the save folder.
+    Directory,
+    /// A manifest pointing at files in the content-addressed object store.
+    #[default]
+    Deduplicated,
+    /// A single zstd compressed tar archive.
+    TarZst,
+    /// A single zip archive.
+    Zip,
+    /// A single zstd compressed tar archive, encrypted with the key derived from the backup passphrase.
+    Encrypted,
+}
+
+impl BackupFormat {
+    pub const ALL: [BackupFormat; 5] = [
+        BackupFormat::Directory,
+        BackupFormat::Deduplicated,
+        BackupFormat::TarZst,
+        BackupFormat::Zip,
+        BackupFormat::Encrypted,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackupFormat::Directory => "directory",
+            BackupFormat::Deduplicated => "deduplicated",
+            BackupFormat::TarZst => "tar_zst",
+            BackupFormat::Zip => "zip",
+            BackupFormat::Encrypted => "encrypted",
+        }
+    }
+
+    /// Parses a format stored in the database, falling back to `Directory` for snapshots that were
+    /// taken before the format was recorded.
+    pub fn from_name(format: &str) -> Self {
+        match format {
+            "deduplicated" => BackupFormat::Deduplicated,
+            "tar_zst" => BackupFormat::TarZst,
+            "zip" => BackupFormat::Zip,
+            "encrypted" => BackupFormat::Encrypted,
+            _ => BackupFormat::Directory,
+        }
+    }
+
+    /// Returns a human readable name of the format.
+    pub fn label(&self) -> &'static str {
+        match self {
+            BackupFormat::Directory => "Plain copy",
+            BackupFormat::Deduplicated => "Deduplicated",
+            BackupFormat::TarZst => "Archive (tar.zst)",
+            BackupFormat::Zip => "Archive (zip)",
+            BackupFormat::Encrypted => "Encrypted archive",
+        }
+    }
+
+    /// Returns the name of the archive file inside the snapshot folder, or `None` if the format doesn't
+    /// store the snapshot as an archive.
+    pub fn archive_file_name(&self) -> Option<&'static str> {
+        match self {
+            BackupFormat::TarZst => Some("files.tar.zst"),
+            BackupFormat::Zip => Some("files.zip"),
+            BackupFormat::Encrypted => Some("files.tar.zst.enc"),
+            BackupFormat::Directory | BackupFormat::Deduplicated => None,
+        }
+    }
+}
+
+/// Why a snapshot was taken.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotKind {
+    /// A regular backup of the save.
+    #[default]
+    Backup,
+    /// The files that were in the save file location right before a restore overwrote them.
+    PreRestore,
+}
+
+impl SnapshotKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SnapshotKind::Backup => "backup",
+            SnapshotKind::PreRestore => "pre_restore",
+        }
+    }
+
+    /// Parses a kind stored in the database, falling back to `Backup` for snapshots that were taken
+    /// before the kind was recorded.
+    pub fn from_name(kind: &str) -> Self {
+        match kind {
+            "pre_restore" => SnapshotKind::PreRestore,
+            _ => SnapshotKind::Backup,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: i32,
+    pub save_id: i32,
+    pub created_at: i64,
+    pub backup_path: String,
+    pub format: BackupFormat,
+    pub kind: SnapshotKind,
+}
+
+/// Backs up games automatically at the times given by its timing.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: i32,
+    /// Whether the schedule backs up every game instead of only those in `game_ids`.
+    pub all_games: bool,
+    pub game_ids: Vec<i32>,
+    pub timing: ScheduleTiming,
+    /// The unix timestamp of the last run, 0 if the schedule never ran.
+    pub last_run: i64,
+}
+
 impl Db {
     /// Opens a new connection to a SQLite database file.
     ///
@@ -51,16 +216,120 @@ impl Db {
     /// ```
     pub fn new(filename: &str) -> Result<Self> {
         let conn = Connection::open(filename)?;
+        // SQLite only enforces foreign keys and deletes the rows referring to deleted ones when asked to
+        conn.pragma_update(None, "foreign_keys", true)?;
         Ok(Self { conn })
     }
 
-    /// Create the necessary database tables if they do not already exist.
+    /// Creates the database tables if they do not already exist and migrates the tables of a database
+    /// made by an older version of the app to the current schema.
+    ///
+    /// The pending migration steps run in order, each in its own transaction that also records the new
+    /// schema version. Before the first step a copy of the database is written next to it.
+    ///
+    /// # Returns
+    ///
+    /// Returns the path of the copy if the database was migrated, so the user can be told where the
+    /// previous database is kept.
     ///
     /// # Errors
     ///
-    /// This function will return an error if there is an issue executing the SQL statements to create the tables.
-    pub fn create_tables(&self) -> Result<()> {
-        // Check if the tables in the database exist If they don't, create them
+    /// This function will return an error if the database was made by a newer version of the app, the
+    /// copy cannot be written or a migration step fails. The database keeps the version of the last
+    /// step that succeeded in that case.
+    pub fn create_tables(&self) -> Result<Option<PathBuf>> {
+        let version = self.schema_version()?;
+        if version > SCHEMA_VERSION {
+            return Err(rusqlite::Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_CANTOPEN),
+                Some(format!(
+                    "the database was made by a newer version of the app (schema version {}, this version \
+                     supports up to {})",
+                    version, SCHEMA_VERSION
+                )),
+            ));
+        }
+        if version == SCHEMA_VERSION {
+            return Ok(None);
+        }
+
+        // Databases made before the schema had a version have tables but version 0
+        let backup_path = if version > 0 || self.has_tables()? {
+            self.backup_before_migration(version)?
+        } else {
+            None
+        };
+
+        // Tables are rebuilt by copying them, which must not delete the rows referring to the old table.
+        // The setting can't change inside a transaction, so it is switched off around all steps.
+        self.conn.pragma_update(None, "foreign_keys", false)?;
+        let migrated = MIGRATIONS
+            .iter()
+            .enumerate()
+            .skip(version as usize)
+            .try_for_each(|(step, migrate)| {
+                self.in_transaction(|| {
+                    migrate(self)?;
+                    self.conn.pragma_update(None, "user_version", step as i32 + 1)
+                })
+            });
+        self.conn.pragma_update(None, "foreign_keys", true)?;
+
+        migrated.map(|()| backup_path)
+    }
+
+    /// Returns the schema version recorded in the database, 0 for a new database or one made before the
+    /// schema had a version.
+    pub fn schema_version(&self) -> Result<i32> {
+        self.conn.pragma_query_value(None, "user_version", |row| row.get(0))
+    }
+
+    fn has_tables(&self) -> Result<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table')",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    /// Copies the database next to its file before it is migrated.
+    ///
+    /// # Returns
+    ///
+    /// Returns the path of the copy, or `None` if the database has no file.
+    fn backup_before_migration(&self, version: i32) -> Result<Option<PathBuf>> {
+        let db_path = match self.conn.path() {
+            Some(db_path) if !db_path.as_os_str().is_empty() && db_path != Path::new(":memory:") => db_path,
+            _ => return Ok(None),
+        };
+
+        let mut file_name = db_path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(format!(".v{}-{}.bak", version, Local::now().format("%Y-%m-%d_%H-%M-%S")));
+        let backup_path = db_path.with_file_name(file_name);
+        self.copy_to(&backup_path.display().to_string())?;
+
+        Ok(Some(backup_path))
+    }
+
+    /// Adds a column to a table unless it already has it.
+    ///
+    /// Databases made before the schema had a version may already have some of the columns that the
+    /// migration steps add.
+    fn add_column(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let columns = stmt.query_map([], |row| row.get::<_, String>(1))?;
+        for existing in columns {
+            if existing? == column {
+                return Ok(());
+            }
+        }
+
+        self.conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+        Ok(())
+    }
+
+    /// Schema version 1: the games and their saves.
+    fn create_game_tables(&self) -> Result<()> {
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS Game (
                 id INTEGER PRIMARY KEY,
@@ -105,6 +374,142 @@ impl Db {
         Ok(())
     }
 
+    /// Schema version 2: the snapshots of every save, with their format and why they were taken.
+    fn create_snapshot_table(&self) -> Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS Snapshot (
+                id INTEGER PRIMARY KEY,
+                save_id INTEGER,
+                created_at INTEGER,
+                backup_path TEXT,
+                FOREIGN KEY (save_id) REFERENCES Save(id)
+            )",
+            params![],
+        )?;
+        self.add_column("Snapshot", "format", "TEXT")?;
+        self.add_column("Snapshot", "kind", "TEXT")?;
+
+        Ok(())
+    }
+
+    /// Schema version 3: the include and exclude patterns of save file locations.
+    fn add_location_patterns(&self) -> Result<()> {
+        self.add_column("Location", "include_patterns", "TEXT")?;
+        self.add_column("Location", "exclude_patterns", "TEXT")?;
+
+        Ok(())
+    }
+
+    /// Schema version 4: the retention policies of games.
+    fn create_retention_policy_table(&self) -> Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS RetentionPolicy (
+                game_id INTEGER PRIMARY KEY,
+                keep_last INTEGER,
+                keep_daily INTEGER,
+                keep_weekly INTEGER,
+                keep_monthly INTEGER,
+                max_total_size INTEGER,
+                FOREIGN KEY (game_id) REFERENCES Game(id)
+            )",
+            params![],
+        )?;
+
+        Ok(())
+    }
+
+    /// Schema version 5: the backup schedules and the games they back up.
+    fn create_schedule_tables(&self) -> Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS Schedule (
+                id INTEGER PRIMARY KEY,
+                all_games INTEGER,
+                interval_minutes INTEGER,
+                daily_at INTEGER,
+                last_run INTEGER
+            )",
+            params![],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS ScheduleGame (
+                schedule_id INTEGER,
+                game_id INTEGER,
+                PRIMARY KEY (schedule_id, game_id),
+                FOREIGN KEY (schedule_id) REFERENCES Schedule(id),
+                FOREIGN KEY (game_id) REFERENCES Game(id)
+            )",
+            params![],
+        )?;
+
+        Ok(())
+    }
+
+    /// Schema version 6: deleting a game deletes its saves, retention policy and schedule entries, and
+    /// deleting a save deletes its snapshots.
+    ///
+    /// SQLite can't change the foreign keys of a table, so the tables are rebuilt. Rows that refer to
+    /// rows that no longer exist are left out.
+    fn cascade_deletes(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE Save_new (
+                id INTEGER PRIMARY KEY,
+                game_id INTEGER NOT NULL REFERENCES Game(id) ON DELETE CASCADE,
+                location_id INTEGER NOT NULL REFERENCES Location(id),
+                metadata TEXT,
+                platform_id INTEGER NOT NULL REFERENCES Platform(id)
+            );
+            INSERT INTO Save_new (id, game_id, location_id, metadata, platform_id)
+                SELECT id, game_id, location_id, metadata, platform_id FROM Save
+                WHERE game_id IN (SELECT id FROM Game)
+                AND location_id IN (SELECT id FROM Location)
+                AND platform_id IN (SELECT id FROM Platform);
+            DROP TABLE Save;
+            ALTER TABLE Save_new RENAME TO Save;
+
+            CREATE TABLE Snapshot_new (
+                id INTEGER PRIMARY KEY,
+                save_id INTEGER NOT NULL REFERENCES Save(id) ON DELETE CASCADE,
+                created_at INTEGER,
+                backup_path TEXT,
+                format TEXT,
+                kind TEXT
+            );
+            INSERT INTO Snapshot_new (id, save_id, created_at, backup_path, format, kind)
+                SELECT id, save_id, created_at, backup_path, format, kind FROM Snapshot
+                WHERE save_id IN (SELECT id FROM Save);
+            DROP TABLE Snapshot;
+            ALTER TABLE Snapshot_new RENAME TO Snapshot;
+
+            CREATE TABLE RetentionPolicy_new (
+                game_id INTEGER PRIMARY KEY REFERENCES Game(id) ON DELETE CASCADE,
+                keep_last INTEGER,
+                keep_daily INTEGER,
+                keep_weekly INTEGER,
+                keep_monthly INTEGER,
+                max_total_size INTEGER
+            );
+            INSERT INTO RetentionPolicy_new
+                SELECT game_id, keep_last, keep_daily, keep_weekly, keep_monthly, max_total_size
+                FROM RetentionPolicy WHERE game_id IN (SELECT id FROM Game);
+            DROP TABLE RetentionPolicy;
+            ALTER TABLE RetentionPolicy_new RENAME TO RetentionPolicy;
+
+            CREATE TABLE ScheduleGame_new (
+                schedule_id INTEGER REFERENCES Schedule(id) ON DELETE CASCADE,
+                game_id INTEGER REFERENCES Game(id) ON DELETE CASCADE,
+                PRIMARY KEY (schedule_id, game_id)
+            );
+            INSERT INTO ScheduleGame_new
+                SELECT schedule_id, game_id FROM ScheduleGame
+                WHERE schedule_id IN (SELECT id FROM Schedule) AND game_id IN (SELECT id FROM Game);
+            DROP TABLE ScheduleGame;
+            ALTER TABLE ScheduleGame_new RENAME TO ScheduleGame;",
+        )?;
+
+        Ok(())
+    }
+
     /// Inserts a new game into the database with the given title, publisher, and release date.
     ///
     /// # Arguments
@@ -172,6 +577,8 @@ impl Db {
     ///
     /// * `location_path` - The path of the location to be inserted.
     /// * `description` - A description of the location to be inserted.
+    /// * `include_patterns` - Glob patterns of the files to back up, empty to back up every file.
+    /// * `exclude_patterns` - Glob patterns of the files to skip.
     ///
     /// # Errors
     ///
@@ -180,10 +587,22 @@ impl Db {
     /// # Returns
     ///
     /// Returns the ID of the newly inserted location on success.
-    pub fn insert_location(&self, location_path: &str, description: &str) -> Result<i32> {
+    pub fn insert_location(
+        &self,
+        location_path: &str,
+        description: &str,
+        include_patterns: &[String],
+        exclude_patterns: &[String],
+    ) -> Result<i32> {
         self.conn.execute(
-            "INSERT INTO Location (location_path, description) VALUES (?1, ?2)",
-            params![location_path, description],
+            "INSERT INTO Location (location_path, description, include_patterns, exclude_patterns)
+            VALUES (?1, ?2, ?3, ?4)",
+            params![
+                location_path,
+                description,
+                include_patterns.join("\n"),
+                exclude_patterns.join("\n")
+            ],
         )?;
         // return the last inserted row id
         let id = self.conn.last_insert_rowid() as i32;
@@ -222,6 +641,40 @@ impl Db {
         Ok(id)
     }
 
+    /// Inserts a new snapshot of a save into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `save_id` - The ID of the save that the snapshot was taken of.
+    /// * `created_at` - The unix timestamp at which the snapshot was taken.
+    /// * `backup_path` - The folder the snapshot files were copied to.
+    /// * `format` - How the snapshot files are stored in `backup_path`.
+    /// * `kind` - Why the snapshot was taken.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there was a problem inserting the snapshot into the database.
+    ///
+    /// # Returns
+    ///
+    /// Returns the ID of the newly inserted snapshot on success.
+    pub fn insert_snapshot(
+        &self,
+        save_id: i32,
+        created_at: i64,
+        backup_path: &str,
+        format: BackupFormat,
+        kind: SnapshotKind,
+    ) -> Result<i32> {
+        self.conn.execute(
+            "INSERT INTO Snapshot (save_id, created_at, backup_path, format, kind) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![save_id, created_at, backup_path, format.as_str(), kind.as_str()],
+        )?;
+        // return the last inserted row id
+        let id = self.conn.last_insert_rowid() as i32;
+        Ok(id)
+    }
+
     /// Updates the details of a game in the database.
     ///
     /// # Arguments
@@ -229,7 +682,7 @@ impl Db {
     /// * `game_id` - An integer representing the ID of the game to be updated.
     /// * `title` - A string slice representing the updated title of the game.
     /// * `publisher` - A string slice representing the updated publisher of the game.
-    /// * `release_date` - A string slice representing the updated release date of the game.
+    /// * `release_date` - The updated release date of the game as a unix timestamp.
     ///
     /// # Errors
     ///
@@ -239,7 +692,7 @@ impl Db {
         game_id: i32,
         title: &str,
         publisher: &str,
-        release_date: &str,
+        release_date: i64,
     ) -> Result<()> {
         self.conn.execute(
             "UPDATE Game SET title = ?1, publisher = ?2, release_date = ?3 WHERE id = ?4",
@@ -248,71 +701,42 @@ impl Db {
         Ok(())
     }
 
-    /// Retrieves the game data from the database with the specified ID.
+    /// Moves a save to another save file location and platform.
     ///
     /// # Arguments
     ///
-    /// * `game_id` - The ID of the game to retrieve from the database.
-    ///
-    /// # Returns
-    ///
-    /// Returns a `Result` containing the retrieved `Game` struct, or an error if the game
-    /// cannot be found in the database.
+    /// * `save_id` - The ID of the save to update.
+    /// * `location_id` - The ID of the location where the save is stored now.
+    /// * `platform_id` - The ID of the platform the save is for now.
     ///
     /// # Errors
     ///
-    /// This function will return an error if the game cannot be found in the database, or if there
-    /// is an issue with the SQLite query.
-    pub fn get_game(&self, game_id: i32) -> Result<Game> {
-        let mut stmt = self.conn.prepare("SELECT * FROM Game WHERE id = ?1")?;
-        let game_iter = stmt.query_map(params![game_id], |row| {
-            Ok(Game {
-                id: row.get(0)?,
-                title: row.get(1).unwrap_or_default(),
-                publisher: row.get(2).unwrap_or_default(),
-                release_date: row.get(3).unwrap_or_default(),
-            })
-        })?;
-
-        for game in game_iter {
-            return Ok(game?);
-        }
-
-        Ok(Game {
-            id: -1,
-            title: String::from(""),
-            publisher: String::from(""),
-            release_date: i64::from(0),
-        })
+    /// This function will return an error if the update operation fails, e.g. because the location or
+    /// platform doesn't exist.
+    pub fn update_save(&self, save_id: i32, location_id: i32, platform_id: i32) -> Result<()> {
+        self.conn.execute(
+            "UPDATE Save SET location_id = ?1, platform_id = ?2 WHERE id = ?3",
+            params![location_id, platform_id, save_id],
+        )?;
+        Ok(())
     }
 
-    /// Retrieves a game from the database by its title.
+    /// Retrieves the game data from the database with the specified ID.
     ///
     /// # Arguments
     ///
-    /// * `title` - The title of the game to retrieve.
+    /// * `game_id` - The ID of the game to retrieve from the database.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` containing a `Game` struct representing the retrieved game if successful, or an error if the operation failed.
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// let game_title = "The Legend of Zelda: Breath of the Wild";
-    /// let game = db.get_game_by_title(game_title)?;
-    ///
-    /// println!("Game Title: {}", game.title);
-    /// println!("Publisher: {}", game.publisher);
-    /// println!("Release Date: {}", game.release_date);
-    /// ```
+    /// Returns the retrieved `Game` struct, or `None` if there is no game with the given ID.
     ///
     /// # Errors
     ///
-    /// This function will return an error if the database operation fails for any reason.
-    pub fn get_game_by_title(&self, title: &str) -> Result<Game> {
-        let mut stmt = self.conn.prepare("SELECT * FROM Game WHERE title = ?1")?;
-        let game_iter = stmt.query_map(params![title], |row| {
+    /// This function will return an error if there is an issue with the SQLite query.
+    pub fn get_game(&self, game_id: i32) -> Result<Option<Game>> {
+        let mut stmt = self.conn.prepare("SELECT * FROM Game WHERE id = ?1")?;
+        let game_iter = stmt.query_map(params![game_id], |row| {
             Ok(Game {
                 id: row.get(0)?,
                 title: row.get(1).unwrap_or_default(),
@@ -322,15 +746,10 @@ impl Db {
         })?;
 
         for game in game_iter {
-            return Ok(game?);
+            return Ok(Some(game?));
         }
 
-        Ok(Game {
-            id: -1,
-            title: String::from(""),
-            publisher: String::from(""),
-            release_date: i64::from(0),
-        })
+        Ok(None)
     }
 
     /// Retrieves a vector of all games in the database that have a title starting with the specified string.
@@ -375,14 +794,13 @@ impl Db {
     ///
     /// # Returns
     ///
-    /// Returns a `Result` containing a `Platform` struct representing the platform retrieved from the database.
-    /// If the platform is not found in the database, the function returns a `Platform` struct with an ID of -1
-    /// and an empty `platform_name` field.
+    /// Returns a `Platform` struct representing the platform retrieved from the database, or `None` if
+    /// the platform is not found in the database.
     ///
     /// # Errors
     ///
     /// This function will return an error if there is a problem executing the SQL statement to retrieve the platform.
-    pub fn get_platform(&self, platform_id: i32) -> Result<Platform> {
+    pub fn get_platform(&self, platform_id: i32) -> Result<Option<Platform>> {
         let mut stmt = self
             .conn
             .prepare("SELECT platform_name FROM Platform WHERE id = ?1")?;
@@ -394,13 +812,10 @@ impl Db {
         })?;
 
         for platform in platform_iter {
-            return Ok(platform?);
+            return Ok(Some(platform?));
         }
 
-        Ok(Platform {
-            id: -1,
-            platform_name: String::from(""),
-        })
+        Ok(None)
     }
 
     /// Retrieves a location record from the database with the given location ID.
@@ -411,46 +826,133 @@ impl Db {
     ///
     /// # Returns
     ///
-    /// A `Location` struct containing the location information, or a `Location` struct with default values if the location
-    /// with the given ID is not found.
+    /// A `Location` struct containing the location information, or `None` if the location with the given
+    /// ID is not found.
     ///
     /// # Errors
     ///
     /// This function will return an error if the database connection fails or if the SQL query fails.
-    pub fn get_location(&self, location_id: i32) -> Result<Location> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT location_path, description FROM Location WHERE id = ?1")?;
+    pub fn get_location(&self, location_id: i32) -> Result<Option<Location>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT location_path, description, include_patterns, exclude_patterns
+            FROM Location WHERE id = ?1",
+        )?;
         let location_iter = stmt.query_map(params![location_id], |row| {
             Ok(Location {
                 id: location_id,
                 location_path: row.get(0).unwrap_or_default(),
                 description: row.get(1).unwrap_or_default(),
+                include_patterns: split_patterns(row.get(2).unwrap_or_default()),
+                exclude_patterns: split_patterns(row.get(3).unwrap_or_default()),
             })
         })?;
 
         for location in location_iter {
-            return Ok(location?);
+            return Ok(Some(location?));
         }
 
-        Ok(Location {
-            id: -1,
-            location_path: String::from(""),
-            description: String::from(""),
-        })
+        Ok(None)
     }
 
-    pub fn get_save(&self, save_id: i32) -> Result<String> {
+    /// Retrieves a save record from the database with the given save ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `save_id` - An integer representing the ID of the save to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A `Save` struct containing the save information, or `None` if the save with the given ID is not
+    /// found.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the database connection fails or if the SQL query fails.
+    pub fn get_save(&self, save_id: i32) -> Result<Option<Save>> {
+        let mut stmt = self.conn.prepare("SELECT * FROM Save WHERE id = ?1")?;
+        let save_iter = stmt.query_map(params![save_id], |row| {
+            Ok(Save {
+                id: row.get(0)?,
+                game_id: row.get(1)?,
+                location_id: row.get(2)?,
+                metadata: row.get(3).unwrap_or_default(),
+                platform_id: row.get(4)?,
+            })
+        })?;
+
+        for save in save_iter {
+            return Ok(Some(save?));
+        }
+
+        Ok(None)
+    }
+
+    /// Retrieves a snapshot record from the database with the given snapshot ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot_id` - An integer representing the ID of the snapshot to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A `Snapshot` struct containing the snapshot information, or `None` if the snapshot with the given
+    /// ID is not found.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the database connection fails or if the SQL query fails.
+    pub fn get_snapshot(&self, snapshot_id: i32) -> Result<Option<Snapshot>> {
         let mut stmt = self
             .conn
-            .prepare("SELECT metadata FROM Save WHERE id = ?1")?;
-        let save_iter = stmt.query_map(params![save_id], |row| Ok(row.get(0)?))?;
+            .prepare("SELECT id, save_id, created_at, backup_path, format, kind FROM Snapshot WHERE id = ?1")?;
+        let snapshot_iter = stmt.query_map(params![snapshot_id], |row| {
+            Ok(Snapshot {
+                id: row.get(0)?,
+                save_id: row.get(1)?,
+                created_at: row.get(2)?,
+                backup_path: row.get(3).unwrap_or_default(),
+                format: BackupFormat::from_name(&row.get::<_, String>(4).unwrap_or_default()),
+                kind: SnapshotKind::from_name(&row.get::<_, String>(5).unwrap_or_default()),
+            })
+        })?;
 
-        for save in save_iter {
-            return Ok(save?);
+        for snapshot in snapshot_iter {
+            return Ok(Some(snapshot?));
+        }
+
+        Ok(None)
+    }
+
+    /// Returns all snapshots of a save, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `save_id` - The ID of the save to retrieve snapshots for.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the query fails.
+    pub fn get_snapshots_by_save_id(&self, save_id: i32) -> Result<Vec<Snapshot>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, save_id, created_at, backup_path, format, kind FROM Snapshot WHERE save_id = ?1 ORDER BY created_at, id",
+        )?;
+        let rows = stmt.query_map(params![save_id], |row| {
+            Ok(Snapshot {
+                id: row.get(0)?,
+                save_id: row.get(1)?,
+                created_at: row.get(2)?,
+                backup_path: row.get(3).unwrap_or_default(),
+                format: BackupFormat::from_name(&row.get::<_, String>(4).unwrap_or_default()),
+                kind: SnapshotKind::from_name(&row.get::<_, String>(5).unwrap_or_default()),
+            })
+        })?;
+
+        let mut snapshots = Vec::new();
+        for snapshot in rows {
+            snapshots.push(snapshot?);
         }
 
-        Ok(String::from("No save found"))
+        Ok(snapshots)
     }
 
     /// Returns a vector of `Save` objects for a given `game_id`.
@@ -482,6 +984,229 @@ impl Db {
         Ok(saves)
     }
 
+    /// Returns every snapshot stored in the given format.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The format of the snapshots to retrieve.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the query fails.
+    pub fn get_snapshots_by_format(&self, format: BackupFormat) -> Result<Vec<Snapshot>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, save_id, created_at, backup_path, format, kind FROM Snapshot WHERE format = ?1",
+        )?;
+        let rows = stmt.query_map(params![format.as_str()], |row| {
+            Ok(Snapshot {
+                id: row.get(0)?,
+                save_id: row.get(1)?,
+                created_at: row.get(2)?,
+                backup_path: row.get(3).unwrap_or_default(),
+                format: BackupFormat::from_name(&row.get::<_, String>(4).unwrap_or_default()),
+                kind: SnapshotKind::from_name(&row.get::<_, String>(5).unwrap_or_default()),
+            })
+        })?;
+
+        let mut snapshots = Vec::new();
+        for snapshot in rows {
+            snapshots.push(snapshot?);
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Returns every snapshot of every save.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the query fails.
+    pub fn get_all_snapshots(&self) -> Result<Vec<Snapshot>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, save_id, created_at, backup_path, format, kind FROM Snapshot ORDER BY id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Snapshot {
+                id: row.get(0)?,
+                save_id: row.get(1)?,
+                created_at: row.get(2)?,
+                backup_path: row.get(3).unwrap_or_default(),
+                format: BackupFormat::from_name(&row.get::<_, String>(4).unwrap_or_default()),
+                kind: SnapshotKind::from_name(&row.get::<_, String>(5).unwrap_or_default()),
+            })
+        })?;
+
+        let mut snapshots = Vec::new();
+        for snapshot in rows {
+            snapshots.push(snapshot?);
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Changes where the files of a snapshot are stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot_id` - The ID of the snapshot.
+    /// * `backup_path` - The new path of the snapshot folder.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the update fails.
+    pub fn update_snapshot_backup_path(&self, snapshot_id: i32, backup_path: &str) -> Result<()> {
+        let mut stmt = self.conn.prepare("UPDATE Snapshot SET backup_path = ?1 WHERE id = ?2")?;
+        stmt.execute(params![backup_path, snapshot_id])?;
+
+        Ok(())
+    }
+
+    /// Writes a consistent copy of the database to a new file, even while other connections use it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the copy, which must not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file already exists or cannot be written.
+    pub fn copy_to(&self, path: &str) -> Result<()> {
+        self.conn.execute("VACUUM INTO ?1", params![path])?;
+
+        Ok(())
+    }
+
+    /// Runs the given database operations as a whole: if one of them fails, none of them are kept.
+    ///
+    /// Calls can be nested, the outermost call decides whether the changes are kept. The changes are
+    /// also undone if the operations panic.
+    ///
+    /// # Errors
+    ///
+    /// This function will return the error of the failed operation, or an error if the changes cannot be
+    /// committed.
+    pub fn in_transaction<T, E: From<rusqlite::Error>>(&self, operations: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        self.conn.execute_batch("SAVEPOINT changes")?;
+        let mut savepoint = Savepoint { conn: &self.conn, released: false };
+
+        let value = operations()?;
+        self.conn.execute_batch("RELEASE changes")?;
+        savepoint.released = true;
+
+        Ok(value)
+    }
+
+    /// Reads every row of every table.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if one of the tables cannot be read.
+    pub fn export_library(&self) -> Result<Library> {
+        Ok(Library {
+            version: LIBRARY_VERSION,
+            exported_at: Local::now().timestamp(),
+            games: self.get_all_games()?,
+            platforms: self.get_all_platforms()?,
+            locations: self.get_all_locations()?,
+            saves: self.get_all_save_records()?,
+            snapshots: self.get_all_snapshots()?,
+            retention_policies: self.get_all_retention_policies()?,
+            schedules: self.get_all_schedules()?,
+        })
+    }
+
+    /// Deletes every row of every table and inserts the rows of a library with the IDs they have in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `library` - The rows to insert.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a row cannot be deleted or inserted, in which case the
+    /// database is left as it was.
+    pub fn replace_library(&self, library: &Library) -> Result<()> {
+        self.in_transaction(|| {
+            self.conn.execute_batch(
+                "DELETE FROM ScheduleGame;
+                DELETE FROM Schedule;
+                DELETE FROM RetentionPolicy;
+                DELETE FROM Snapshot;
+                DELETE FROM Save;
+                DELETE FROM Location;
+                DELETE FROM Platform;
+                DELETE FROM Game;",
+            )?;
+
+            for game in &library.games {
+                self.conn.execute(
+                    "INSERT INTO Game (id, title, publisher, release_date) VALUES (?1, ?2, ?3, ?4)",
+                    params![game.id, game.title, game.publisher, game.release_date],
+                )?;
+            }
+            for platform in &library.platforms {
+                self.conn.execute(
+                    "INSERT INTO Platform (id, platform_name) VALUES (?1, ?2)",
+                    params![platform.id, platform.platform_name],
+                )?;
+            }
+            for location in &library.locations {
+                self.conn.execute(
+                    "INSERT INTO Location (id, location_path, description, include_patterns, exclude_patterns)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        location.id,
+                        location.location_path,
+                        location.description,
+                        location.include_patterns.join("\n"),
+                        location.exclude_patterns.join("\n")
+                    ],
+                )?;
+            }
+            for save in &library.saves {
+                self.conn.execute(
+                    "INSERT INTO Save (id, game_id, location_id, metadata, platform_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![save.id, save.game_id, save.location_id, save.metadata, save.platform_id],
+                )?;
+            }
+            for snapshot in &library.snapshots {
+                self.conn.execute(
+                    "INSERT INTO Snapshot (id, save_id, created_at, backup_path, format, kind)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        snapshot.id,
+                        snapshot.save_id,
+                        snapshot.created_at,
+                        snapshot.backup_path,
+                        snapshot.format.as_str(),
+                        snapshot.kind.as_str()
+                    ],
+                )?;
+            }
+            for retention_policy in &library.retention_policies {
+                self.set_retention_policy(retention_policy.game_id, &retention_policy.policy)?;
+            }
+            for schedule in &library.schedules {
+                let (interval_minutes, daily_at) = match schedule.timing {
+                    ScheduleTiming::Interval { minutes } => (Some(minutes), None),
+                    ScheduleTiming::Daily { minute_of_day } => (None, Some(minute_of_day)),
+                };
+                self.conn.execute(
+                    "INSERT INTO Schedule (id, all_games, interval_minutes, daily_at, last_run) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![schedule.id, schedule.all_games, interval_minutes, daily_at, schedule.last_run],
+                )?;
+                for game_id in &schedule.game_ids {
+                    self.conn.execute(
+                        "INSERT OR IGNORE INTO ScheduleGame (schedule_id, game_id) VALUES (?1, ?2)",
+                        params![schedule.id, game_id],
+                    )?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+
     pub fn get_all_games(&self) -> Result<Vec<Game>> {
         let mut stmt = self.conn.prepare("SELECT * FROM Game")?;
         let rows = stmt.query_map([], |row| {
@@ -519,12 +1244,16 @@ impl Db {
     }
 
     pub fn get_all_locations(&self) -> Result<Vec<Location>> {
-        let mut stmt = self.conn.prepare("SELECT location_path FROM Location")?;
+        let mut stmt = self.conn.prepare(
+            "SELECT id, location_path, description, include_patterns, exclude_patterns FROM Location",
+        )?;
         let rows = stmt.query_map([], |row| {
             Ok(Location {
                 id: row.get(0)?,
-                location_path: row.get(1)?,
-                description: row.get(2)?,
+                location_path: row.get(1).unwrap_or_default(),
+                description: row.get(2).unwrap_or_default(),
+                include_patterns: split_patterns(row.get(3).unwrap_or_default()),
+                exclude_patterns: split_patterns(row.get(4).unwrap_or_default()),
             })
         })?;
 
@@ -548,6 +1277,31 @@ impl Db {
         Ok(saves)
     }
 
+    /// Returns every save of every game.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the query fails.
+    pub fn get_all_save_records(&self) -> Result<Vec<Save>> {
+        let mut stmt = self.conn.prepare("SELECT id, game_id, location_id, metadata, platform_id FROM Save ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Save {
+                id: row.get(0)?,
+                game_id: row.get(1)?,
+                location_id: row.get(2)?,
+                metadata: row.get(3).unwrap_or_default(),
+                platform_id: row.get(4)?,
+            })
+        })?;
+
+        let mut saves = Vec::new();
+        for save in rows {
+            saves.push(save?);
+        }
+
+        Ok(saves)
+    }
+
     pub fn get_all_saves_by_id(&self, game_id: i32) -> Result<Vec<Save>> {
         let mut stmt = self.conn.prepare("SELECT * FROM Save WHERE game_id = ?1")?;
         let rows = stmt.query_map(params![game_id], |row| {
@@ -667,6 +1421,201 @@ impl Db {
         Ok(saves)
     }
 
+    /// Retrieves the retention policy of a game.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there was a problem querying the database.
+    ///
+    /// # Returns
+    ///
+    /// Returns the policy of the game, or `None` if the game uses the global policy.
+    pub fn get_retention_policy(&self, game_id: i32) -> Result<Option<RetentionPolicy>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT keep_last, keep_daily, keep_weekly, keep_monthly, max_total_size
+            FROM RetentionPolicy WHERE game_id = ?1",
+        )?;
+        let mut rows = stmt.query(params![game_id])?;
+
+        match rows.next()? {
+            Some(row) => Ok(Some(RetentionPolicy {
+                keep_last: row.get(0)?,
+                keep_daily: row.get(1)?,
+                keep_weekly: row.get(2)?,
+                keep_monthly: row.get(3)?,
+                max_total_size: row.get::<_, Option<i64>>(4)?.map(|size| size as u64),
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieves the retention policy of every game that doesn't use the global policy.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there was a problem querying the database.
+    pub fn get_all_retention_policies(&self) -> Result<Vec<GameRetentionPolicy>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT game_id, keep_last, keep_daily, keep_weekly, keep_monthly, max_total_size
+            FROM RetentionPolicy ORDER BY game_id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(GameRetentionPolicy {
+                game_id: row.get(0)?,
+                policy: RetentionPolicy {
+                    keep_last: row.get(1)?,
+                    keep_daily: row.get(2)?,
+                    keep_weekly: row.get(3)?,
+                    keep_monthly: row.get(4)?,
+                    max_total_size: row.get::<_, Option<i64>>(5)?.map(|size| size as u64),
+                },
+            })
+        })?;
+
+        let mut policies = Vec::new();
+        for policy in rows {
+            policies.push(policy?);
+        }
+
+        Ok(policies)
+    }
+
+    /// Sets the retention policy of a game, replacing any policy it had before.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game.
+    /// * `policy` - The policy for the game's snapshots.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there was a problem writing to the database.
+    pub fn set_retention_policy(&self, game_id: i32, policy: &RetentionPolicy) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO RetentionPolicy
+            (game_id, keep_last, keep_daily, keep_weekly, keep_monthly, max_total_size)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                game_id,
+                policy.keep_last,
+                policy.keep_daily,
+                policy.keep_weekly,
+                policy.keep_monthly,
+                policy.max_total_size.map(|size| size as i64)
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Removes the retention policy of a game, so it uses the global policy again.
+    pub fn delete_retention_policy(&self, game_id: i32) -> Result<()> {
+        let mut stmt = self.conn.prepare("DELETE FROM RetentionPolicy WHERE game_id = ?1")?;
+        stmt.execute(params![game_id])?;
+
+        Ok(())
+    }
+
+    /// Inserts a new backup schedule together with the games it backs up.
+    ///
+    /// # Arguments
+    ///
+    /// * `schedule` - The schedule to insert. Its ID is ignored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there was a problem inserting the schedule into the database.
+    ///
+    /// # Returns
+    ///
+    /// Returns the ID of the newly inserted schedule on success.
+    pub fn insert_schedule(&self, schedule: &Schedule) -> Result<i32> {
+        let (interval_minutes, daily_at) = match schedule.timing {
+            ScheduleTiming::Interval { minutes } => (Some(minutes), None),
+            ScheduleTiming::Daily { minute_of_day } => (None, Some(minute_of_day)),
+        };
+
+        self.conn.execute(
+            "INSERT INTO Schedule (all_games, interval_minutes, daily_at, last_run) VALUES (?1, ?2, ?3, ?4)",
+            params![schedule.all_games, interval_minutes, daily_at, schedule.last_run],
+        )?;
+        let id = self.conn.last_insert_rowid() as i32;
+
+        for game_id in &schedule.game_ids {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO ScheduleGame (schedule_id, game_id) VALUES (?1, ?2)",
+                params![id, game_id],
+            )?;
+        }
+
+        Ok(id)
+    }
+
+    /// Retrieves every backup schedule from the database.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there was a problem querying the database.
+    pub fn get_all_schedules(&self) -> Result<Vec<Schedule>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, all_games, interval_minutes, daily_at, last_run FROM Schedule ORDER BY id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let interval_minutes: Option<u32> = row.get(2)?;
+            let daily_at: Option<u32> = row.get(3)?;
+
+            Ok(Schedule {
+                id: row.get(0)?,
+                all_games: row.get(1)?,
+                game_ids: Vec::new(),
+                timing: match interval_minutes {
+                    Some(minutes) => ScheduleTiming::Interval { minutes },
+                    None => ScheduleTiming::Daily { minute_of_day: daily_at.unwrap_or_default() },
+                },
+                last_run: row.get::<_, Option<i64>>(4)?.unwrap_or_default(),
+            })
+        })?;
+
+        let mut schedules = Vec::new();
+        for schedule in rows {
+            let mut schedule = schedule?;
+
+            let mut stmt = self.conn
+                .prepare("SELECT game_id FROM ScheduleGame WHERE schedule_id = ?1 ORDER BY game_id")?;
+            let game_ids = stmt.query_map(params![schedule.id], |row| row.get(0))?;
+            for game_id in game_ids {
+                schedule.game_ids.push(game_id?);
+            }
+
+            schedules.push(schedule);
+        }
+
+        Ok(schedules)
+    }
+
+    /// Records when a backup schedule last ran.
+    pub fn update_schedule_last_run(&self, schedule_id: i32, last_run: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE Schedule SET last_run = ?1 WHERE id = ?2",
+            params![last_run, schedule_id],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn delete_schedule(&self, schedule_id: i32) -> Result<()> {
+        let mut stmt = self.conn.prepare("DELETE FROM ScheduleGame WHERE schedule_id = ?1")?;
+        stmt.execute(params![schedule_id])?;
+        let mut stmt = self.conn.prepare("DELETE FROM Schedule WHERE id = ?1")?;
+        stmt.execute(params![schedule_id])?;
+
+        Ok(())
+    }
+
     pub fn delete_game(&self, game_id: i32) -> Result<()> {
         let mut stmt = self.conn.prepare("DELETE FROM Game WHERE id = ?1")?;
         stmt.execute(params![game_id])?;
@@ -681,10 +1630,141 @@ impl Db {
         Ok(())
     }
 
+    pub fn delete_snapshot(&self, snapshot_id: i32) -> Result<()> {
+        let mut stmt = self.conn.prepare("DELETE FROM Snapshot WHERE id = ?1")?;
+        stmt.execute(params![snapshot_id])?;
+
+        Ok(())
+    }
+
+    /// Deletes a save file location, unless a save still uses it.
     pub fn delete_location(&self, location_id: i32) -> Result<()> {
-        let mut stmt = self.conn.prepare("DELETE FROM Location WHERE id = ?1")?;
+        let mut stmt = self.conn.prepare(
+            "DELETE FROM Location WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM Save WHERE location_id = ?1)",
+        )?;
         stmt.execute(params![location_id])?;
 
         Ok(())
     }
 }
+
+/// Splits the newline separated glob patterns stored for a location.
+fn split_patterns(patterns: String) -> Vec<String> {
+    patterns
+        .lines()
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::tests::TestDir;
+
+    /// The tables of a database made before the schema had a version.
+    const UNVERSIONED_SCHEMA: &str = "
+        CREATE TABLE Game (id INTEGER PRIMARY KEY, title TEXT, publisher TEXT, release_date INTEGER);
+        CREATE TABLE Platform (id INTEGER PRIMARY KEY AUTOINCREMENT, platform_name TEXT NOT NULL UNIQUE);
+        CREATE TABLE Location (id INTEGER PRIMARY KEY, location_path TEXT, description TEXT);
+        CREATE TABLE Save (
+            id INTEGER PRIMARY KEY,
+            game_id INTEGER,
+            location_id INTEGER,
+            metadata TEXT,
+            platform_id INTEGER,
+            FOREIGN KEY (game_id) REFERENCES Game(id),
+            FOREIGN KEY (location_id) REFERENCES Location(id),
+            FOREIGN KEY (platform_id) REFERENCES Platform(id)
+        );
+        INSERT INTO Game (id, title, publisher, release_date) VALUES (1, 'Game', 'Studio', 0);
+        INSERT INTO Platform (id, platform_name) VALUES (1, 'PC');
+        INSERT INTO Location (id, location_path, description) VALUES (1, '/saves', '');
+        INSERT INTO Save (id, game_id, location_id, metadata, platform_id) VALUES (1, 1, 1, '', 1);
+    ";
+
+    fn unversioned_db() -> Db {
+        let db = Db::new(":memory:").unwrap();
+        db.conn.execute_batch(UNVERSIONED_SCHEMA).unwrap();
+        db
+    }
+
+    #[test]
+    fn test_new_database_gets_the_current_schema() {
+        let db = Db::new(":memory:").unwrap();
+        assert!(db.create_tables().unwrap().is_none());
+
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        assert!(db.get_all_games().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_migration_returns_the_copy_of_the_previous_database() {
+        let dir = TestDir::new("migration_copy");
+        let db = Db::new(&dir.join("games.db").display().to_string()).unwrap();
+        db.conn.execute_batch(UNVERSIONED_SCHEMA).unwrap();
+
+        let backup_path = db.create_tables().unwrap().unwrap();
+
+        let previous = Db::new(&backup_path.display().to_string()).unwrap();
+        assert_eq!(previous.schema_version().unwrap(), 0);
+        assert!(db.create_tables().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_migrates_unversioned_database() {
+        let db = unversioned_db();
+        db.create_tables().unwrap();
+
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        assert_eq!(db.get_all_games().unwrap()[0].title, "Game");
+        let location = db.get_location(1).unwrap().unwrap();
+        assert_eq!(location.location_path, "/saves");
+        assert!(location.include_patterns.is_empty());
+
+        // The tables added by the migrations can be used
+        db.insert_snapshot(1, 0, "/backups/1/1/1/snapshot", BackupFormat::Directory, SnapshotKind::Backup)
+            .unwrap();
+        db.set_retention_policy(1, &RetentionPolicy { keep_last: Some(3), ..Default::default() }).unwrap();
+        assert_eq!(db.get_retention_policy(1).unwrap().unwrap().keep_last, Some(3));
+
+        // Deleting a game deletes the rows referring to it
+        db.delete_game(1).unwrap();
+        assert!(db.get_save(1).unwrap().is_none());
+        assert!(db.get_snapshots_by_save_id(1).unwrap().is_empty());
+        assert!(db.get_retention_policy(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_migrates_from_each_version() {
+        for version in 1..SCHEMA_VERSION {
+            let db = Db::new(":memory:").unwrap();
+            for (step, migrate) in MIGRATIONS.iter().enumerate().take(version as usize) {
+                migrate(&db).unwrap();
+                db.conn.pragma_update(None, "user_version", step as i32 + 1).unwrap();
+            }
+
+            db.create_tables().unwrap();
+            assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION, "from version {}", version);
+        }
+    }
+
+    #[test]
+    fn test_migrating_twice_changes_nothing() {
+        let db = unversioned_db();
+        db.create_tables().unwrap();
+        db.create_tables().unwrap();
+
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        assert_eq!(db.get_all_games().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_newer_database() {
+        let db = Db::new(":memory:").unwrap();
+        db.conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+
+        assert!(db.create_tables().is_err());
+    }
+}
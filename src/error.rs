@@ -0,0 +1,78 @@
+use std::{fmt, io};
+
+/// The errors of the app's operations, shown to the user as messages.
+#[derive(Debug)]
+pub enum Error {
+    /// A game, save or other row that was asked for is not in the database, e.g. because it was removed
+    /// in the meantime.
+    NotFound(String),
+    /// The database could not be read or written.
+    Db(rusqlite::Error),
+    /// A file could not be read or written.
+    Io(io::Error),
+    /// The input, e.g. a form or an imported file, is not valid.
+    Validation(String),
+}
+
+/// The result of the app's operations.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Returns the error for a missing row.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - What is missing, e.g. "game".
+    /// * `id` - The ID or other key it was looked up by.
+    pub fn not_found(kind: &str, id: impl fmt::Display) -> Self {
+        Error::NotFound(format!("{} {}", kind, id))
+    }
+
+    /// Returns whether the operation stopped because the job it ran in was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, Error::Io(e) if e.kind() == io::ErrorKind::Interrupted)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(what) => write!(f, "{} was not found", what),
+            Error::Db(e) => write!(f, "database error: {}", e),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Validation(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Db(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::NotFound(_) | Error::Validation(_) => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Self {
+        Error::Db(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// Lets the file operations that report I/O errors, like restoring a file, pass the app's errors on.
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(e) => e,
+            error => io::Error::other(error),
+        }
+    }
+}
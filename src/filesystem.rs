@@ -1,65 +1,1560 @@
-use std::fs::{self};
-use std::path::{Path};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 
-pub struct Filesystem {}
+use glob::{MatchOptions, Pattern, PatternError};
+use sha2::{Digest, Sha256};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::crypto::{self, BackupKey, DecryptReader, EncryptWriter};
+use crate::db::{BackupFormat, Location};
+use crate::error::Error;
+use crate::jobs::JobProgress;
+use crate::manifest::{Manifest, ManifestEntry};
+
+pub struct Filesystem {
+    /// The job the file operations are reported to and cancelled by, if they run as one.
+    progress: Option<Arc<JobProgress>>,
+}
 
 impl Filesystem {
     // This function creates a new instance of the Filesystem struct
     pub fn new() -> Self {
-        Self {}
+        Self { progress: None }
+    }
+
+    /// Creates a Filesystem that reports every file it copies to a job and stops copying when the job
+    /// is cancelled.
+    pub fn with_progress(progress: Arc<JobProgress>) -> Self {
+        Self { progress: Some(progress) }
+    }
+
+    /// Returns whether the job the file operations run in was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.progress.as_ref().is_some_and(|progress| progress.is_cancelled())
+    }
+
+    /// Adds files that are about to be copied to the totals of the job.
+    pub fn add_to_total(&self, files: usize, bytes: u64) {
+        if let Some(progress) = &self.progress {
+            progress.add_total(files as u64, bytes);
+        }
+    }
+
+    /// Reports that a file is about to be copied.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an `Interrupted` error if the job was cancelled.
+    pub fn begin_file(&self, path: &str) -> Result<(), io::Error> {
+        if let Some(progress) = &self.progress {
+            if progress.is_cancelled() {
+                return Err(io::Error::new(ErrorKind::Interrupted, "cancelled"));
+            }
+            progress.start_file(path);
+        }
+        Ok(())
+    }
+
+    /// Reports that a file of the given size was copied, or skipped because it's already stored.
+    pub fn finish_file(&self, bytes: u64) {
+        if let Some(progress) = &self.progress {
+            progress.finish_file(bytes);
+        }
     }
 
-    /// Copies all the files in a folder to another folder and creates it if it doesn't exist.
+    /// Copies all the files in a folder that match a filter to another folder and creates it if it
+    /// doesn't exist.
+    ///
+    /// Files and subfolders that cannot be read or copied are skipped and recorded in the report, the
+    /// remaining files are still copied.
     ///
     /// # Arguments
     ///
     /// * `source_dir` - The path to the source directory.
     /// * `dest_dir` - The path to the destination directory.
+    /// * `filter` - Decides which files are copied.
     ///
     /// # Errors
     ///
-    /// This function will return an error if it fails to create the destination directory or if it
-    /// fails to copy any of the files.
-    pub fn copy_files(&self, source_dir: &Path, dest_dir: &Path) -> Result<(), std::io::Error> {
+    /// This function will return an error if it fails to create the destination directory, cannot
+    /// read the source directory at all or the job it runs in is cancelled.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the copied files and the files that failed to copy.
+    pub fn copy_files(&self, source_dir: &Path, dest_dir: &Path, filter: &FileFilter) -> Result<FileReport, io::Error> {
         // Create destination directory if it doesn't exist
         if !dest_dir.exists() {
             fs::create_dir_all(dest_dir)?;
         }
 
-        // Iterate over files in source directory
-        for entry in fs::read_dir(source_dir)? {
-            let entry = entry?;
-            let path = entry.path();
+        // Copy every matching file, creating its subdirectories in the destination directory
+        let mut report = FileReport::default();
+        for relative_path in self.walk_files(source_dir, filter, &mut report)? {
+            let source_file = source_dir.join(&relative_path);
+            self.begin_file(&manifest_path(&relative_path))?;
+            match self.copy_file(source_dir, &relative_path, dest_dir) {
+                Ok(size) => {
+                    report.record_success(size);
+                    self.finish_file(size);
+                }
+                Err(e) => report.record_failure(source_file, &e),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Deletes all the files in a folder and its subfolders, then the folders that were emptied.
+    ///
+    /// Files and subfolders that cannot be deleted are skipped and recorded in the report, together
+    /// with the folders that still contain them.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The path to the folder to delete.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the folder cannot be read at all.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the deleted files and their combined size, and the files that failed to
+    /// delete.
+    pub fn delete_files(&self, dir: &Path) -> Result<FileReport, io::Error> {
+        let mut report = FileReport::default();
+
+        for relative_path in self.walk_files(dir, &FileFilter::default(), &mut report)? {
+            let path = dir.join(&relative_path);
+            let size = fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or_default();
+            match fs::remove_file(&path) {
+                Ok(()) => report.record_success(size),
+                Err(e) => report.record_failure(path, &e),
+            }
+        }
+
+        // Remove the emptied folders, deepest first so every folder is empty by the time it's removed
+        let mut folders = vec![dir.to_path_buf()];
+        let mut index = 0;
+        while index < folders.len() {
+            if let Ok(entries) = fs::read_dir(&folders[index]) {
+                let subfolders: Vec<PathBuf> = entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_dir())
+                    .collect();
+                folders.extend(subfolders);
+            }
+            index += 1;
+        }
+        for folder in folders.iter().rev() {
+            let still_has_failures = report.failed.iter().any(|error| error.path.starts_with(folder));
+            if !still_has_failures {
+                if let Err(e) = fs::remove_dir(folder) {
+                    report.record_failure(folder.clone(), &e);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Lists the files in a folder that match a filter like [`Filesystem::list_matching_files`], but
+    /// records the subfolders that cannot be read in a report instead of failing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the folder itself cannot be read.
+    fn walk_files(&self, dir: &Path, filter: &FileFilter, report: &mut FileReport) -> Result<Vec<PathBuf>, io::Error> {
+        Self::walk(dir, filter, |folder, e| {
+            report.record_failure(folder, &e);
+            Ok(())
+        })
+    }
+
+    /// Lists the files in a folder and its subfolders that match a filter, relative to that folder,
+    /// without searching the subfolders the filter excludes entirely.
+    ///
+    /// `on_error` is called with the subfolder and the error whenever a subfolder cannot be read, and
+    /// decides whether the walk goes on without it or fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the folder itself cannot be read, or `on_error` returns one.
+    fn walk(
+        dir: &Path,
+        filter: &FileFilter,
+        mut on_error: impl FnMut(PathBuf, io::Error) -> Result<(), io::Error>,
+    ) -> Result<Vec<PathBuf>, io::Error> {
+        let mut files = Vec::new();
+        let mut pending = vec![PathBuf::new()];
+
+        while let Some(relative_dir) = pending.pop() {
+            let entries = match fs::read_dir(dir.join(&relative_dir)) {
+                Ok(entries) => entries,
+                Err(e) if relative_dir.as_os_str().is_empty() => return Err(e),
+                Err(e) => {
+                    on_error(dir.join(&relative_dir), e)?;
+                    continue;
+                }
+            };
+
+            for entry in entries {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        on_error(dir.join(&relative_dir), e)?;
+                        continue;
+                    }
+                };
+                let path = entry.path();
+                let relative_path = relative_dir.join(entry.file_name());
+
+                if path.is_file() {
+                    if filter.matches(&relative_path) {
+                        files.push(relative_path);
+                    }
+                } else if path.is_dir() && !filter.excludes_folder(&relative_path) {
+                    pending.push(relative_path);
+                }
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+
+    /// Returns the paths of all files in a folder and its subfolders, relative to that folder.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The path to the folder.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any of the folders cannot be read.
+    pub fn list_files(&self, dir: &Path) -> Result<Vec<PathBuf>, io::Error> {
+        self.list_matching_files(dir, &FileFilter::default())
+    }
+
+    /// Returns the paths of all files in a folder and its subfolders that match a filter, relative to
+    /// that folder. Subfolders the filter excludes entirely are not searched.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The path to the folder.
+    /// * `filter` - Decides which files are listed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any of the folders cannot be read.
+    pub fn list_matching_files(&self, dir: &Path, filter: &FileFilter) -> Result<Vec<PathBuf>, io::Error> {
+        Self::walk(dir, filter, |_, e| Err(e))
+    }
+
+    /// Returns the combined size of all files in a folder and its subfolders.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The path to the folder.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any of the folders or files cannot be read.
+    pub fn disk_usage(&self, dir: &Path) -> Result<u64, io::Error> {
+        let mut size = 0;
+        for relative_path in self.list_files(dir)? {
+            size += fs::metadata(dir.join(relative_path))?.len();
+        }
+        Ok(size)
+    }
+
+    /// Returns the SHA-256 hash of a file's contents as a lowercase hex string.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file to hash.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be read.
+    pub fn hash_file(&self, path: &Path) -> Result<String, io::Error> {
+        let (_, sha256) = hash_reader(&mut File::open(path)?)?;
+        Ok(sha256)
+    }
+
+    /// Hashes every file in a folder and its subfolders.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_dir` - The path to the folder.
+    /// * `filter` - Decides which files are listed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any of the files cannot be read.
+    ///
+    /// # Returns
+    ///
+    /// Returns a manifest listing the relative path, size, modification time and hash of every file.
+    pub fn build_manifest(&self, source_dir: &Path, filter: &FileFilter) -> Result<Manifest, io::Error> {
+        let mut manifest = Manifest::default();
+
+        for relative_path in self.list_matching_files(source_dir, filter)? {
+            let path = source_dir.join(&relative_path);
+            let metadata = fs::metadata(&path)?;
+
+            manifest.files.push(ManifestEntry {
+                path: manifest_path(&relative_path),
+                size: metadata.len(),
+                mtime: modified_time(&metadata),
+                sha256: self.hash_file(&path)?,
+                stored_in: None,
+            });
+        }
+
+        Ok(manifest)
+    }
+
+    /// Hashes every file in a folder that changed since an earlier manifest was built.
+    ///
+    /// Files whose size and modification time match their entry in `previous` are assumed unchanged and
+    /// keep their previous hash, unless `compare_hash` is set. Files and subfolders that cannot be read
+    /// are left out of the manifest and recorded in `report`.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_dir` - The path to the folder.
+    /// * `previous` - The manifest of an earlier snapshot of the same folder.
+    /// * `compare_hash` - Whether to hash every file even if its size and modification time didn't change.
+    /// * `filter` - Decides which files are listed.
+    /// * `report` - Collects the files that cannot be read.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the folder itself cannot be read.
+    ///
+    /// # Returns
+    ///
+    /// Returns a manifest of the folder listing the files of `previous` that were deleted since.
+    pub fn scan_changes(
+        &self,
+        source_dir: &Path,
+        previous: &Manifest,
+        compare_hash: bool,
+        filter: &FileFilter,
+        report: &mut FileReport,
+    ) -> Result<Manifest, io::Error> {
+        let mut manifest = Manifest::default();
+
+        for relative_path in self.walk_files(source_dir, filter, report)? {
+            // A cancelled scan returns the files scanned so far, the caller throws them away
+            if self.begin_file(&manifest_path(&relative_path)).is_err() {
+                break;
+            }
+
+            let path = source_dir.join(&relative_path);
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    report.record_failure(path, &e);
+                    continue;
+                }
+            };
+            let relative_path = manifest_path(&relative_path);
+            let size = metadata.len();
+            let mtime = modified_time(&metadata);
+
+            let sha256 = match previous.get(&relative_path) {
+                Some(entry) if !compare_hash && entry.size == size && entry.mtime == mtime => {
+                    entry.sha256.clone()
+                }
+                _ => match self.hash_file(&path) {
+                    Ok(sha256) => sha256,
+                    Err(e) => {
+                        report.record_failure(path, &e);
+                        continue;
+                    }
+                },
+            };
+
+            manifest.files.push(ManifestEntry {
+                path: relative_path,
+                size,
+                mtime,
+                sha256,
+                stored_in: None,
+            });
+        }
+
+        manifest.deleted = previous
+            .files
+            .iter()
+            .filter(|entry| manifest.get(&entry.path).is_none())
+            .map(|entry| entry.path.clone())
+            .collect();
+
+        Ok(manifest)
+    }
+
+    /// Returns the path of the object holding the contents with the given hash in an object store.
+    ///
+    /// The hash has to be 64 hex digits, like the hashes of the manifests read by [`Manifest::load`].
+    pub fn object_path(objects_dir: &Path, sha256: &str) -> PathBuf {
+        objects_dir.join(&sha256[..2]).join(sha256)
+    }
+
+    /// Copies the files listed in a manifest into a content-addressed object store, skipping every file
+    /// whose contents are already stored.
+    ///
+    /// Files are hashed again while they are copied. If a file changed since the manifest was built, it
+    /// is stored under the hash of the copied contents and its entry in the manifest is updated.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_dir` - The path to the source directory.
+    /// * `manifest` - The manifest of the source directory.
+    /// * `objects_dir` - The path to the object store.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the stored files and the files that could not be copied into the store. If
+    /// the job it runs in is cancelled, the remaining files are left out of the report.
+    pub fn store_objects(&self, source_dir: &Path, manifest: &mut Manifest, objects_dir: &Path) -> FileReport {
+        let mut report = FileReport::default();
+
+        for entry in &mut manifest.files {
+            if self.begin_file(&entry.path).is_err() {
+                break;
+            }
+
+            let object = Self::object_path(objects_dir, &entry.sha256);
+            if object.exists() {
+                self.finish_file(entry.size);
+                continue;
+            }
+
+            let source_file = source_dir.join(&entry.path);
+            // Copy to a temporary name first so an interrupted copy never leaves a broken object
+            let partial_object = objects_dir.join(format!("{}.partial", entry.sha256));
+            let result = fs::create_dir_all(objects_dir)
+                .and_then(|()| Self::store_object(&source_file, &partial_object, objects_dir));
+            if result.is_err() {
+                let _ = fs::remove_file(&partial_object);
+            }
+
+            match result {
+                Ok((size, sha256)) => {
+                    entry.size = size;
+                    entry.sha256 = sha256;
+                    report.record_success(size);
+                    self.finish_file(size);
+                }
+                Err(e) => report.record_failure(source_file, &e),
+            }
+        }
+
+        report
+    }
+
+    /// Copies a file to a temporary file in an object store and renames it to the object of the hash of
+    /// the copied contents, or removes it if that object is already stored.
+    ///
+    /// # Returns
+    ///
+    /// Returns the size and hash of the copied contents.
+    fn store_object(source_file: &Path, partial_object: &Path, objects_dir: &Path) -> Result<(u64, String), io::Error> {
+        let mut writer = HashWriter::new(File::create(partial_object)?);
+        let size = io::copy(&mut File::open(source_file)?, &mut writer)?;
+        let sha256 = writer.finish()?;
+
+        let object = Self::object_path(objects_dir, &sha256);
+        if object.exists() {
+            fs::remove_file(partial_object)?;
+        } else {
+            if let Some(parent) = object.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(partial_object, &object)?;
+        }
+        Ok((size, sha256))
+    }
+
+    /// Copies a single file from one folder to the same relative path in another folder, creating its
+    /// parent folders if they don't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_dir` - The path to the source directory.
+    /// * `relative_path` - The path of the file relative to both folders.
+    /// * `dest_dir` - The path to the destination directory.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be copied.
+    ///
+    /// # Returns
+    ///
+    /// Returns the size of the copied file in bytes.
+    pub fn copy_file(&self, source_dir: &Path, relative_path: &Path, dest_dir: &Path) -> Result<u64, io::Error> {
+        let dest_file = dest_dir.join(relative_path);
+        if let Some(parent) = dest_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::copy(source_dir.join(relative_path), &dest_file)
+    }
+
+    /// Rebuilds the files listed in a manifest from a content-addressed object store.
+    ///
+    /// # Arguments
+    ///
+    /// * `manifest` - The manifest listing the files to restore.
+    /// * `objects_dir` - The path to the object store.
+    /// * `dest_dir` - The path to the destination directory.
+    /// * `filter` - Decides which files are restored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an object is missing from the store or cannot be copied, or
+    /// if the job it runs in is cancelled.
+    pub fn restore_files(
+        &self,
+        manifest: &Manifest,
+        objects_dir: &Path,
+        dest_dir: &Path,
+        filter: &FileFilter,
+    ) -> Result<(), io::Error> {
+        for entry in manifest.files.iter().filter(|entry| filter.matches(Path::new(&entry.path))) {
+            self.begin_file(&entry.path)?;
+            let dest_file = dest_dir.join(&entry.path);
+            if let Some(parent) = dest_file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            fs::copy(Self::object_path(objects_dir, &entry.sha256), &dest_file)?;
+            self.finish_file(entry.size);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the files listed in a manifest into a single compressed archive.
+    ///
+    /// Every file is read once, hashing it on the way into the archive, and its entry in the manifest is
+    /// updated with the size and hash of the archived contents. Files that cannot be opened are left out
+    /// of the archive and recorded in the report.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_dir` - The path to the source directory.
+    /// * `archive_path` - The path of the archive to create.
+    /// * `format` - The archive format, `BackupFormat::TarZst`, `BackupFormat::Zip` or
+    ///   `BackupFormat::Encrypted`.
+    /// * `manifest` - The manifest listing the files to add to the archive.
+    /// * `key` - The key to encrypt the archive with, only used for `BackupFormat::Encrypted`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the archive cannot be written, if `format` is not an
+    /// archive format or if an encrypted archive is written without a key. A file that fails to read
+    /// after it was opened, or gets shorter while it's read, fails the archive too, as its entry was
+    /// already started.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the archived files and the files that could not be read. If the job it runs
+    /// in is cancelled, the archive is left incomplete.
+    pub fn archive_files(
+        &self,
+        source_dir: &Path,
+        archive_path: &Path,
+        format: BackupFormat,
+        manifest: &mut Manifest,
+        key: Option<&BackupKey>,
+    ) -> Result<FileReport, io::Error> {
+        if let Some(parent) = archive_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut report = FileReport::default();
+        let archive = File::create(archive_path)?;
+
+        match format {
+            BackupFormat::TarZst => {
+                let encoder = zstd::Encoder::new(archive, 0)?;
+                self.append_to_tar(encoder, source_dir, manifest, &mut report)?.finish()?;
+            }
+            BackupFormat::Encrypted => {
+                let writer = EncryptWriter::new(archive, key.ok_or_else(crypto::locked_error)?)?;
+                let encoder = zstd::Encoder::new(writer, 0)?;
+                self.append_to_tar(encoder, source_dir, manifest, &mut report)?
+                    .finish()?
+                    .finish()?;
+            }
+            BackupFormat::Zip => {
+                let mut writer = ZipWriter::new(archive);
+                let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+                for entry in &mut manifest.files {
+                    if self.begin_file(&entry.path).is_err() {
+                        break;
+                    }
+                    if let Some((mut file, metadata)) = open_for_archive(source_dir, &entry.path, &mut report) {
+                        writer.start_file(entry.path.as_str(), options).map_err(zip_error)?;
+                        let mut hashed = HashWriter::new(&mut writer);
+                        let size = io::copy(&mut file, &mut hashed)?;
+                        entry.size = size;
+                        entry.mtime = modified_time(&metadata);
+                        entry.sha256 = hashed.finish()?;
+                        report.record_success(size);
+                        self.finish_file(size);
+                    }
+                }
+                writer.finish().map_err(zip_error)?;
+            }
+            BackupFormat::Directory | BackupFormat::Deduplicated => {
+                return Err(not_an_archive(format));
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Adds the files listed in a manifest to a tar archive and returns the writer the archive was
+    /// written to.
+    fn append_to_tar<W: io::Write>(
+        &self,
+        writer: W,
+        source_dir: &Path,
+        manifest: &mut Manifest,
+        report: &mut FileReport,
+    ) -> Result<W, io::Error> {
+        let mut builder = tar::Builder::new(writer);
+        for entry in &mut manifest.files {
+            if self.begin_file(&entry.path).is_err() {
+                break;
+            }
+            if let Some((file, metadata)) = open_for_archive(source_dir, &entry.path, report) {
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(&metadata);
+
+                // The size is written before the contents, a file that grows since is cut off there
+                let mut hashed = HashReader::new(file.take(metadata.len()), metadata.len());
+                builder.append_data(&mut header, &entry.path, &mut hashed)?;
+                entry.size = metadata.len();
+                entry.mtime = modified_time(&metadata);
+                entry.sha256 = hashed.finish();
+                report.record_success(metadata.len());
+                self.finish_file(metadata.len());
+            }
+        }
+        builder.into_inner()
+    }
+
+    /// Unpacks all the files of an archive that match a filter into a folder, reading the archive as a
+    /// stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive_path` - The path of the archive to unpack.
+    /// * `format` - The archive format, `BackupFormat::TarZst`, `BackupFormat::Zip` or
+    ///   `BackupFormat::Encrypted`.
+    /// * `dest_dir` - The path to the destination directory.
+    /// * `filter` - Decides which files are unpacked.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the archive cannot be read, if any of the files cannot be
+    /// written, if `format` is not an archive format or if the job it runs in is cancelled.
+    pub fn extract_archive(
+        &self,
+        archive_path: &Path,
+        format: BackupFormat,
+        dest_dir: &Path,
+        filter: &FileFilter,
+    ) -> Result<(), io::Error> {
+        self.extract_archive_files(archive_path, format, dest_dir, |path| filter.matches(path))
+    }
+
+    /// Unpacks a single file of an archive into a folder.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive_path` - The path of the archive to unpack from.
+    /// * `format` - The archive format, `BackupFormat::TarZst`, `BackupFormat::Zip` or
+    ///   `BackupFormat::Encrypted`.
+    /// * `file` - The path of the file inside the archive.
+    /// * `dest_dir` - The path to the destination directory.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the archive cannot be read, if the file is not part of the
+    /// archive or if it cannot be written.
+    pub fn extract_archive_file(
+        &self,
+        archive_path: &Path,
+        format: BackupFormat,
+        file: &Path,
+        dest_dir: &Path,
+    ) -> Result<(), io::Error> {
+        let mut found = false;
+        self.extract_archive_files(archive_path, format, dest_dir, |path| {
+            let matches = path == file;
+            found |= matches;
+            matches
+        })?;
+
+        if !found {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("{} is not part of the archive", file.display()),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the paths of all files in an archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive_path` - The path of the archive.
+    /// * `format` - The archive format, `BackupFormat::TarZst`, `BackupFormat::Zip` or
+    ///   `BackupFormat::Encrypted`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the archive cannot be read or if `format` is not an archive
+    /// format.
+    pub fn list_archive_files(
+        &self,
+        archive_path: &Path,
+        format: BackupFormat,
+    ) -> Result<Vec<PathBuf>, io::Error> {
+        let archive = File::open(archive_path)?;
+        let mut files = Vec::new();
+
+        match format {
+            BackupFormat::TarZst | BackupFormat::Encrypted => {
+                let mut reader = open_tar(archive, format)?;
+                for entry in reader.entries()? {
+                    let entry = entry?;
+                    if entry.header().entry_type().is_file() {
+                        files.push(entry.path()?.into_owned());
+                    }
+                }
+            }
+            BackupFormat::Zip => {
+                let mut reader = ZipArchive::new(archive).map_err(zip_error)?;
+                for index in 0..reader.len() {
+                    let entry = reader.by_index(index).map_err(zip_error)?;
+                    if let Some(path) = entry.enclosed_name() {
+                        if entry.is_file() {
+                            files.push(path.to_path_buf());
+                        }
+                    }
+                }
+            }
+            BackupFormat::Directory | BackupFormat::Deduplicated => {
+                return Err(not_an_archive(format));
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Hashes every file in an archive without unpacking it.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive_path` - The path of the archive.
+    /// * `format` - The archive format, `BackupFormat::TarZst`, `BackupFormat::Zip` or
+    ///   `BackupFormat::Encrypted`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the archive cannot be read or if `format` is not an archive
+    /// format.
+    ///
+    /// # Returns
+    ///
+    /// Returns a manifest of the archive contents. Modification times are only known for tar archives.
+    pub fn build_archive_manifest(
+        &self,
+        archive_path: &Path,
+        format: BackupFormat,
+    ) -> Result<Manifest, io::Error> {
+        let archive = File::open(archive_path)?;
+        let mut manifest = Manifest::default();
+
+        match format {
+            BackupFormat::TarZst | BackupFormat::Encrypted => {
+                let mut reader = open_tar(archive, format)?;
+                for entry in reader.entries()? {
+                    let mut entry = entry?;
+                    if !entry.header().entry_type().is_file() {
+                        continue;
+                    }
 
-            if path.is_file() {
-                // Copy file to destination directory
-                let dest_file = dest_dir.join(path.file_name().unwrap());
-                fs::copy(&path, &dest_file)?;
-            } else if path.is_dir() {
-                // Recursively copy subdirectory to destination directory
-                let dest_subdir = dest_dir.join(path.file_name().unwrap());
-                let _= &self.copy_files(&path, &dest_subdir).expect("Failed to copy files");
+                    let path = manifest_path(&entry.path()?);
+                    let mtime = entry.header().mtime().unwrap_or_default() as i64;
+                    let (size, sha256) = hash_reader(&mut entry)?;
+                    manifest.files.push(ManifestEntry { path, size, mtime, sha256, stored_in: None });
+                }
             }
+            BackupFormat::Zip => {
+                let mut reader = ZipArchive::new(archive).map_err(zip_error)?;
+                for index in 0..reader.len() {
+                    let mut entry = reader.by_index(index).map_err(zip_error)?;
+                    let path = match entry.enclosed_name() {
+                        Some(path) if entry.is_file() => manifest_path(path),
+                        _ => continue,
+                    };
+
+                    let (size, sha256) = hash_reader(&mut entry)?;
+                    manifest.files.push(ManifestEntry { path, size, mtime: 0, sha256, stored_in: None });
+                }
+            }
+            BackupFormat::Directory | BackupFormat::Deduplicated => {
+                return Err(not_an_archive(format));
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// Writes a bundle, a zstd compressed tar archive that starts with a description of its contents
+    /// followed by the files it describes.
+    ///
+    /// # Arguments
+    ///
+    /// * `bundle_path` - The path of the bundle file to write.
+    /// * `description_name` - The name of the description inside the bundle.
+    /// * `description` - The contents of the description.
+    /// * `files` - The files to add and their paths inside the bundle.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any of the files cannot be read, the bundle cannot be
+    /// written or the job it runs in is cancelled.
+    pub fn write_bundle(
+        &self,
+        bundle_path: &Path,
+        description_name: &str,
+        description: &[u8],
+        files: &[(PathBuf, String)],
+    ) -> Result<(), io::Error> {
+        if let Some(parent) = bundle_path.parent() {
+            fs::create_dir_all(parent)?;
         }
+        let mut builder = tar::Builder::new(zstd::Encoder::new(File::create(bundle_path)?, 0)?);
 
+        let mut header = tar::Header::new_gnu();
+        header.set_size(description.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, description_name, description)?;
+
+        for (source, path) in files {
+            self.begin_file(path)?;
+            let mut file = File::open(source)?;
+            let size = file.metadata()?.len();
+            builder.append_file(path, &mut file)?;
+            self.finish_file(size);
+        }
+
+        builder.into_inner()?.finish()?;
         Ok(())
     }
 
-    pub fn delete_files(&self, dir: &Path) -> Result<(), std::io::Error> {
-        // Iterate over files in source directory
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
+    /// Reads the description at the start of a bundle without unpacking the rest of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `bundle_path` - The path of the bundle file.
+    /// * `description_name` - The name of the description inside the bundle.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the bundle cannot be read or doesn't start with the
+    /// description.
+    pub fn read_bundle_description(&self, bundle_path: &Path, description_name: &str) -> Result<Vec<u8>, io::Error> {
+        let mut reader = open_tar(File::open(bundle_path)?, BackupFormat::TarZst)?;
 
-            if path.is_file() {
-                // Delete file
-                fs::remove_file(&path)?;
-            } else if path.is_dir() {
-                // Recursively delete subdirectory
-                let _= &self.delete_files(&path).expect("Failed to delete files");
+        if let Some(entry) = reader.entries()?.next() {
+            let mut entry = entry?;
+            if entry.path()? == Path::new(description_name) {
+                let mut description = Vec::new();
+                entry.read_to_end(&mut description)?;
+                return Ok(description);
+            }
+        }
+
+        Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{} is not a bundle", bundle_path.display()),
+        ))
+    }
+
+    /// Unpacks the files of a bundle into a folder, leaving out its description.
+    ///
+    /// # Arguments
+    ///
+    /// * `bundle_path` - The path of the bundle file.
+    /// * `description_name` - The name of the description inside the bundle.
+    /// * `dest_dir` - The path to the destination directory.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the bundle cannot be read, any of the files cannot be
+    /// written or the job it runs in is cancelled.
+    pub fn extract_bundle(&self, bundle_path: &Path, description_name: &str, dest_dir: &Path) -> Result<(), io::Error> {
+        self.extract_archive_files(bundle_path, BackupFormat::TarZst, dest_dir, |path| {
+            path != Path::new(description_name)
+        })
+    }
+
+    /// Unpacks the files of an archive for which `filter` returns true into a folder.
+    fn extract_archive_files(
+        &self,
+        archive_path: &Path,
+        format: BackupFormat,
+        dest_dir: &Path,
+        mut filter: impl FnMut(&Path) -> bool,
+    ) -> Result<(), io::Error> {
+        let archive = File::open(archive_path)?;
+        fs::create_dir_all(dest_dir)?;
+
+        match format {
+            BackupFormat::TarZst | BackupFormat::Encrypted => {
+                let mut reader = open_tar(archive, format)?;
+                for entry in reader.entries()? {
+                    let mut entry = entry?;
+                    let path = entry.path()?.into_owned();
+                    if filter(&path) {
+                        self.begin_file(&manifest_path(&path))?;
+                        // unpack_in refuses paths that would end up outside of dest_dir
+                        entry.unpack_in(dest_dir)?;
+                        self.finish_file(entry.size());
+                    }
+                }
+            }
+            BackupFormat::Zip => {
+                let mut reader = ZipArchive::new(archive).map_err(zip_error)?;
+                for index in 0..reader.len() {
+                    let mut entry = reader.by_index(index).map_err(zip_error)?;
+                    let path = match entry.enclosed_name() {
+                        Some(path) => path.to_path_buf(),
+                        None => continue,
+                    };
+                    if !entry.is_file() || !filter(&path) {
+                        continue;
+                    }
+
+                    self.begin_file(&manifest_path(&path))?;
+                    let dest_file = dest_dir.join(&path);
+                    if let Some(parent) = dest_file.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let size = io::copy(&mut entry, &mut File::create(&dest_file)?)?;
+                    self.finish_file(size);
+                }
+            }
+            BackupFormat::Directory | BackupFormat::Deduplicated => {
+                return Err(not_an_archive(format));
             }
         }
 
         Ok(())
     }
 }
+
+/// Why a single file or folder could not be copied or deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileErrorKind {
+    /// The current user isn't allowed to read or write the file.
+    PermissionDenied,
+    /// Another program, usually the running game, has the file open or locked.
+    Locked,
+    /// The path is longer than the file system or platform allows.
+    PathTooLong,
+    /// The file disappeared while it was being copied.
+    NotFound,
+    Other,
+}
+
+impl FileErrorKind {
+    /// Classifies an I/O error that occurred while handling a single file.
+    pub fn from_io_error(error: &io::Error) -> Self {
+        match error.kind() {
+            ErrorKind::PermissionDenied => return FileErrorKind::PermissionDenied,
+            ErrorKind::NotFound => return FileErrorKind::NotFound,
+            ErrorKind::WouldBlock => return FileErrorKind::Locked,
+            _ => {}
+        }
+
+        match error.raw_os_error() {
+            // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+            #[cfg(windows)]
+            Some(32) | Some(33) => FileErrorKind::Locked,
+            // ERROR_FILENAME_EXCED_RANGE
+            #[cfg(windows)]
+            Some(206) => FileErrorKind::PathTooLong,
+            // EBUSY and ETXTBSY
+            #[cfg(unix)]
+            Some(16) | Some(26) => FileErrorKind::Locked,
+            // ENAMETOOLONG
+            #[cfg(target_os = "linux")]
+            Some(36) => FileErrorKind::PathTooLong,
+            #[cfg(all(unix, not(target_os = "linux")))]
+            Some(63) => FileErrorKind::PathTooLong,
+            _ => FileErrorKind::Other,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileErrorKind::PermissionDenied => "permission denied",
+            FileErrorKind::Locked => "file is locked",
+            FileErrorKind::PathTooLong => "path is too long",
+            FileErrorKind::NotFound => "file not found",
+            FileErrorKind::Other => "error",
+        }
+    }
+}
+
+/// A file or folder that could not be copied or deleted.
+#[derive(Clone, Debug)]
+pub struct FileError {
+    pub path: PathBuf,
+    pub kind: FileErrorKind,
+    /// The message of the underlying I/O error.
+    pub message: String,
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.path.display(), self.kind.label(), self.message)
+    }
+}
+
+/// The outcome of copying or deleting many files, where a failing file doesn't stop the others.
+#[derive(Clone, Debug, Default)]
+pub struct FileReport {
+    /// The number of files that were copied or deleted.
+    pub succeeded: usize,
+    /// The combined size of those files in bytes.
+    pub bytes: u64,
+    pub failed: Vec<FileError>,
+}
+
+impl FileReport {
+    /// Returns whether every file was copied or deleted.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Returns whether copying or deleting the file at `path` failed.
+    pub fn has_failed(&self, path: &Path) -> bool {
+        self.failed.iter().any(|error| error.path == path)
+    }
+
+    pub fn record_success(&mut self, size: u64) {
+        self.succeeded += 1;
+        self.bytes += size;
+    }
+
+    pub fn record_failure(&mut self, path: PathBuf, error: &io::Error) {
+        self.failed.push(FileError {
+            path,
+            kind: FileErrorKind::from_io_error(error),
+            message: error.to_string(),
+        });
+    }
+
+    /// Adds the files of another report to this one.
+    pub fn merge(&mut self, other: FileReport) {
+        self.succeeded += other.succeeded;
+        self.bytes += other.bytes;
+        self.failed.extend(other.failed);
+    }
+}
+
+impl fmt::Display for FileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} files ({} bytes) done, {} failed",
+            self.succeeded,
+            self.bytes,
+            self.failed.len()
+        )?;
+        for error in &self.failed {
+            writeln!(f, "  {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Decides which files of a save file location are backed up and restored, based on the include and
+/// exclude glob patterns of the location.
+///
+/// A pattern ending in `/` only matches folders and everything inside them. A pattern containing
+/// another `/` is matched against the whole path relative to the save file location, any other pattern
+/// against every file and folder name along the path. A file is used if it matches an include pattern,
+/// or there are none, and matches no exclude pattern.
+#[derive(Clone, Debug, Default)]
+pub struct FileFilter {
+    include: Vec<FilePattern>,
+    exclude: Vec<FilePattern>,
+}
+
+#[derive(Clone, Debug)]
+struct FilePattern {
+    pattern: Pattern,
+    /// Whether the pattern ends in `/` and therefore only matches folders.
+    folder: bool,
+    /// Whether the pattern is matched against the whole relative path instead of single names.
+    anchored: bool,
+}
+
+impl FileFilter {
+    /// Compiles include and exclude glob patterns into a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `include` - The patterns of the files to use. Every file is used if there are none.
+    /// * `exclude` - The patterns of the files to skip, even if they match an include pattern.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any of the patterns is not a valid glob pattern.
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, PatternError> {
+        Ok(Self {
+            include: include.iter().map(|pattern| FilePattern::new(pattern)).collect::<Result<_, _>>()?,
+            exclude: exclude.iter().map(|pattern| FilePattern::new(pattern)).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Compiles the include and exclude patterns stored for a save file location into a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The save file location.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a validation error if any of the patterns is not a valid glob
+    /// pattern, e.g. because it was imported from a file that was edited by hand.
+    pub fn for_location(location: &Location) -> Result<Self, Error> {
+        Self::new(&location.include_patterns, &location.exclude_patterns).map_err(|e| {
+            Error::Validation(format!("the file patterns of {} are not valid: {}", location.location_path, e))
+        })
+    }
+
+    /// Returns whether a file is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path of the file relative to the save file location.
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        let components = path_components(relative_path);
+
+        (self.include.is_empty() || self.include.iter().any(|pattern| pattern.matches(&components, false)))
+            && !self.exclude.iter().any(|pattern| pattern.matches(&components, false))
+    }
+
+    /// Returns whether every file inside a folder is skipped, so it doesn't need to be searched.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_dir` - The path of the folder relative to the save file location.
+    pub fn excludes_folder(&self, relative_dir: &Path) -> bool {
+        let components = path_components(relative_dir);
+
+        self.exclude.iter().any(|pattern| pattern.matches(&components, true))
+    }
+}
+
+impl FilePattern {
+    fn new(pattern: &str) -> Result<Self, PatternError> {
+        let pattern = pattern.trim().replace('\\', "/");
+        let folder = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/').trim_start_matches('/');
+
+        Ok(Self {
+            pattern: Pattern::new(pattern)?,
+            folder,
+            anchored: pattern.contains('/'),
+        })
+    }
+
+    /// Returns whether the pattern matches a path given as its components.
+    ///
+    /// If `is_folder` is set, the last component is a folder, otherwise it is a file.
+    fn matches(&self, components: &[String], is_folder: bool) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        // Folder patterns can't match the file itself, only the folders containing it
+        let candidates = if self.folder && !is_folder {
+            components.len().saturating_sub(1)
+        } else {
+            components.len()
+        };
+
+        if self.anchored {
+            (1..=candidates).any(|len| self.pattern.matches_with(&components[..len].join("/"), options))
+        } else {
+            components[..candidates]
+                .iter()
+                .any(|name| self.pattern.matches_with(name, options))
+        }
+    }
+}
+
+/// Reads everything from a reader and returns the number of bytes read and their SHA-256 hash.
+fn hash_reader(reader: &mut impl io::Read) -> Result<(u64, String), io::Error> {
+    let mut hasher = Sha256::new();
+    let size = io::copy(reader, &mut hasher)?;
+
+    Ok((size, to_hex(&hasher.finalize())))
+}
+
+/// Reads a given number of bytes from another reader and hashes them on the way.
+struct HashReader<R: io::Read> {
+    inner: R,
+    hasher: Sha256,
+    /// How many of the bytes are still to be read.
+    remaining: u64,
+}
+
+impl<R: io::Read> HashReader<R> {
+    fn new(inner: R, size: u64) -> Self {
+        Self { inner, hasher: Sha256::new(), remaining: size }
+    }
+
+    /// Returns the SHA-256 hash of everything read.
+    fn finish(self) -> String {
+        to_hex(&self.hasher.finalize())
+    }
+}
+
+impl<R: io::Read> io::Read for HashReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        if read == 0 && self.remaining > 0 && !buffer.is_empty() {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "the file got shorter while it was read"));
+        }
+        self.hasher.update(&buffer[..read]);
+        self.remaining = self.remaining.saturating_sub(read as u64);
+        Ok(read)
+    }
+}
+
+/// Writes everything written to it into another writer and hashes it on the way.
+struct HashWriter<W: io::Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: io::Write> HashWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, hasher: Sha256::new() }
+    }
+
+    /// Flushes the inner writer and returns the SHA-256 hash of everything written.
+    fn finish(mut self) -> Result<String, io::Error> {
+        self.inner.flush()?;
+        Ok(to_hex(&self.hasher.finalize()))
+    }
+}
+
+impl<W: io::Write> io::Write for HashWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(data)?;
+        self.hasher.update(&data[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Formats a hash as a lowercase hex string.
+fn to_hex(hash: &[u8]) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Splits a relative path into the names of its components.
+fn path_components(relative_path: &Path) -> Vec<String> {
+    relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Converts a relative path into the `/` separated form used in manifests and archives.
+pub fn manifest_path(relative_path: &Path) -> String {
+    relative_path.to_string_lossy().replace('\\', "/")
+}
+
+/// Returns the modification time of a file as a unix timestamp, or 0 if the platform doesn't record it.
+pub fn modified_time(metadata: &fs::Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Opens a file that is about to be added to an archive, recording it in the report if it can't be.
+fn open_for_archive(source_dir: &Path, relative_path: &str, report: &mut FileReport) -> Option<(File, fs::Metadata)> {
+    let path = source_dir.join(relative_path);
+    match File::open(&path).and_then(|file| {
+        let metadata = file.metadata()?;
+        Ok((file, metadata))
+    }) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            report.record_failure(path, &e);
+            None
+        }
+    }
+}
+
+/// Opens a tar archive for reading, decrypting it first if it's encrypted.
+fn open_tar(archive: File, format: BackupFormat) -> Result<tar::Archive<Box<dyn io::Read>>, io::Error> {
+    let reader: Box<dyn io::Read> = if format == BackupFormat::Encrypted {
+        Box::new(DecryptReader::new(archive)?)
+    } else {
+        Box::new(archive)
+    };
+    Ok(tar::Archive::new(Box::new(zstd::Decoder::new(reader)?)))
+}
+
+fn zip_error(error: zip::result::ZipError) -> io::Error {
+    match error {
+        zip::result::ZipError::Io(error) => error,
+        error => io::Error::new(ErrorKind::InvalidData, error),
+    }
+}
+
+fn not_an_archive(format: BackupFormat) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidInput,
+        format!("{} is not an archive format", format.label()),
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::ops::Deref;
+
+    use super::*;
+
+    /// An empty folder in the temporary folder for a single test, removed again when it's dropped.
+    pub(crate) struct TestDir(PathBuf);
+
+    impl TestDir {
+        pub(crate) fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("sharks_save_haven-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Deref for TestDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes a file, creating its parent folders.
+    pub(crate) fn write_file(dir: &Path, relative_path: &str, contents: &str) {
+        let path = dir.join(relative_path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_scan_changes() {
+        let dir = TestDir::new("scan_changes");
+        write_file(&dir, "unchanged.sav", "unchanged");
+        write_file(&dir, "modified.sav", "before");
+        write_file(&dir, "deleted.sav", "deleted");
+        let fs = Filesystem::new();
+        let mut previous = fs.build_manifest(&dir, &FileFilter::default()).unwrap();
+        // Unchanged files keep their previous hash without being hashed again
+        let unchanged_hash = "0".repeat(64);
+        let unchanged = previous.files.iter_mut().find(|entry| entry.path == "unchanged.sav").unwrap();
+        unchanged.sha256 = unchanged_hash.clone();
+        write_file(&dir, "modified.sav", "after, with a different size");
+        fs::remove_file(dir.join("deleted.sav")).unwrap();
+
+        let mut report = FileReport::default();
+        let manifest = fs.scan_changes(&dir, &previous, false, &FileFilter::default(), &mut report).unwrap();
+
+        assert!(report.is_complete());
+        assert_eq!(manifest.get("unchanged.sav").unwrap().sha256, unchanged_hash);
+        assert_eq!(manifest.get("modified.sav").unwrap().sha256, fs.hash_file(&dir.join("modified.sav")).unwrap());
+        assert!(manifest.get("deleted.sav").is_none());
+        assert_eq!(manifest.deleted, ["deleted.sav"]);
+
+        let manifest = fs.scan_changes(&dir, &previous, true, &FileFilter::default(), &mut report).unwrap();
+        let unchanged_hash = fs.hash_file(&dir.join("unchanged.sav")).unwrap();
+        assert_eq!(manifest.get("unchanged.sav").unwrap().sha256, unchanged_hash);
+    }
+
+    #[test]
+    fn test_store_objects() {
+        let dir = TestDir::new("store_objects");
+        let (source, objects) = (dir.join("source"), dir.join("objects"));
+        write_file(&source, "a.sav", "same");
+        write_file(&source, "b/b.sav", "same");
+        let fs = Filesystem::new();
+        let mut manifest = fs.build_manifest(&source, &FileFilter::default()).unwrap();
+
+        let report = fs.store_objects(&source, &mut manifest, &objects);
+
+        assert!(report.is_complete());
+        let object = Filesystem::object_path(&objects, &manifest.files[0].sha256);
+        assert_eq!(fs::read_to_string(object).unwrap(), "same");
+        assert_eq!(fs.list_files(&objects).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_archive_manifest_lists_the_archived_contents() {
+        let dir = TestDir::new("archive_files");
+        let source = dir.join("source");
+        write_file(&source, "a.sav", "before");
+        write_file(&source, "b/b.sav", "b");
+        let fs = Filesystem::new();
+
+        for format in [BackupFormat::TarZst, BackupFormat::Zip] {
+            let mut manifest = fs.build_manifest(&source, &FileFilter::default()).unwrap();
+            // A file that changes after it was hashed is archived as it is when it's read
+            write_file(&source, "a.sav", "after the manifest was built");
+            let archive = dir.join(format.archive_file_name().unwrap());
+
+            let report = fs.archive_files(&source, &archive, format, &mut manifest, None).unwrap();
+
+            assert!(report.is_complete());
+            let archived = fs.build_archive_manifest(&archive, format).unwrap();
+            let hashes = |manifest: &Manifest| {
+                manifest
+                    .files
+                    .iter()
+                    .map(|entry| (entry.path.clone(), entry.size, entry.sha256.clone()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(hashes(&archived), hashes(&manifest), "{}", format.label());
+            write_file(&source, "a.sav", "before");
+        }
+    }
+
+    #[test]
+    fn test_store_objects_removes_partial_object_of_failed_copy() {
+        let dir = TestDir::new("store_objects_failed");
+        let (source, objects) = (dir.join("source"), dir.join("objects"));
+        // Opening a folder works, reading from it fails halfway through the copy
+        fs::create_dir_all(source.join("save.sav")).unwrap();
+        let mut manifest = Manifest::default();
+        manifest.files.push(ManifestEntry {
+            path: "save.sav".to_string(),
+            size: 4,
+            mtime: 0,
+            sha256: "a".repeat(64),
+            stored_in: None,
+        });
+
+        let report = Filesystem::new().store_objects(&source, &mut manifest, &objects);
+
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(fs::read_dir(&objects).unwrap().count(), 0);
+    }
+
+    fn filter(include: &[&str], exclude: &[&str]) -> FileFilter {
+        let to_strings = |patterns: &[&str]| patterns.iter().map(|pattern| pattern.to_string()).collect::<Vec<_>>();
+        FileFilter::new(&to_strings(include), &to_strings(exclude)).unwrap()
+    }
+
+    #[test]
+    fn test_filter_without_include_patterns_matches_everything() {
+        let filter = filter(&[], &[]);
+
+        assert!(filter.matches(Path::new("save.sav")));
+        assert!(filter.matches(Path::new("profiles/1/save.sav")));
+        assert!(!filter.excludes_folder(Path::new("profiles")));
+    }
+
+    #[test]
+    fn test_filter_name_pattern_matches_in_every_folder() {
+        let filter = filter(&["*.log"], &[]);
+
+        assert!(filter.matches(Path::new("game.log")));
+        assert!(filter.matches(Path::new("logs/old/game.log")));
+        assert!(!filter.matches(Path::new("save.sav")));
+        assert!(!filter.matches(Path::new("game.log.sav")));
+    }
+
+    #[test]
+    fn test_filter_folder_pattern_excludes_everything_inside() {
+        let filter = filter(&[], &["shadercache/"]);
+
+        assert!(!filter.matches(Path::new("shadercache/cache.bin")));
+        assert!(!filter.matches(Path::new("profiles/shadercache/nested/cache.bin")));
+        assert!(filter.excludes_folder(Path::new("shadercache")));
+        assert!(filter.excludes_folder(Path::new("profiles/shadercache")));
+        // A file with the folder's name is not a folder
+        assert!(filter.matches(Path::new("shadercache")));
+        assert!(!filter.excludes_folder(Path::new("profiles")));
+    }
+
+    #[test]
+    fn test_filter_exclude_patterns_win_over_include_patterns() {
+        let filter = filter(&["*.sav", "profiles/"], &["backup_*", "profiles/temp/"]);
+
+        assert!(filter.matches(Path::new("slot1.sav")));
+        assert!(filter.matches(Path::new("profiles/settings.ini")));
+        assert!(!filter.matches(Path::new("backup_slot1.sav")));
+        assert!(!filter.matches(Path::new("profiles/temp/slot1.sav")));
+        assert!(!filter.matches(Path::new("settings.ini")));
+        assert!(filter.excludes_folder(Path::new("profiles/temp")));
+        // Folders the include patterns don't name may still contain matching files
+        assert!(!filter.excludes_folder(Path::new("other")));
+    }
+
+    #[test]
+    fn test_list_matching_files_skips_excluded_folders() {
+        let dir = TestDir::new("list_matching_files");
+        write_file(&dir, "slot1.sav", "");
+        write_file(&dir, "game.log", "");
+        write_file(&dir, "profiles/slot2.sav", "");
+        write_file(&dir, "shadercache/cache.sav", "");
+        let fs = Filesystem::new();
+
+        let files = fs.list_matching_files(&dir, &filter(&["*.sav"], &["shadercache/"])).unwrap();
+
+        assert_eq!(files, vec![PathBuf::from("profiles/slot2.sav"), PathBuf::from("slot1.sav")]);
+        assert!(fs.list_matching_files(&dir.join("missing"), &FileFilter::default()).is_err());
+    }
+}
@@ -1,328 +1,3112 @@
-use std::{io::{self, Write}, path::{PathBuf, Path}, fs};
+use std::{collections::{HashMap, HashSet}, fmt, io, path::{Component, PathBuf, Path}, fs};
+use std::sync::{Mutex, MutexGuard};
 
-use crate::{db::{self, Db, Game}, filesystem};
+use chrono::Local;
+
+use crate::{
+    bundle::{
+        BundledSave, BundledSnapshot, GameBundle, ImportReport, BUNDLE_FILE_NAME, BUNDLE_OBJECTS_FOLDER,
+        BUNDLE_SNAPSHOTS_FOLDER, BUNDLE_VERSION,
+    },
+    crypto::{self, BackupKey},
+    csv_import::{read_csv_games, CsvImportReport, CsvRowError},
+    db::{self, BackupFormat, Db, Game, SnapshotKind},
+    error::{Error, Result},
+    filesystem::{self, FileFilter, FileReport, Filesystem},
+    library::{ImportMode, Library, LibraryConflict, LibraryImportReport},
+    manifest::{self, Manifest, ManifestEntry, MANIFEST_FILE_NAME},
+    retention::{RetentionPolicy, SnapshotUsage},
+    settings::Settings,
+};
+
+/// The name of the folder inside a plain copy snapshot that holds the copied files.
+const DIRECTORY_FILES_NAME: &str = "files";
+
+/// The folder inside the backup root that the kept backups of removed games are moved to, out of the
+/// way of games that are added later with the same ID.
+const REMOVED_FOLDER_NAME: &str = "removed";
+
+/// Held while files are added to or deleted from the backup root. The GUI's jobs, the scheduler and the
+/// watcher back up from their own threads, and the garbage collection must not delete the objects of a
+/// snapshot whose manifest isn't in the database yet.
+static BACKUP_LOCK: Mutex<()> = Mutex::new(());
+
+/// Waits until no other thread of the process writes to the backup root, see [`BACKUP_LOCK`].
+fn lock_backups() -> MutexGuard<'static, ()> {
+    // A thread that panicked while writing leaves nothing the lock protects in a broken state
+    BACKUP_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The result of checking the stored files of a snapshot against its manifest.
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotCheck {
+    pub snapshot_id: i32,
+    pub backup_path: String,
+    /// Files listed in the manifest that are no longer stored.
+    pub missing: Vec<String>,
+    /// Stored files that are not listed in the manifest.
+    pub extra: Vec<String>,
+    /// Stored files whose size or contents don't match the manifest.
+    pub corrupted: Vec<String>,
+    /// Set when the snapshot could not be checked at all, e.g. because it has no manifest.
+    pub error: Option<String>,
+}
+
+impl SnapshotCheck {
+    pub fn is_intact(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.corrupted.is_empty() && self.error.is_none()
+    }
+}
+
+/// The results of checking all snapshots of a game.
+#[derive(Clone, Debug, Default)]
+pub struct VerifyReport {
+    pub title: String,
+    pub snapshots: Vec<SnapshotCheck>,
+}
+
+impl VerifyReport {
+    pub fn is_intact(&self) -> bool {
+        self.snapshots.iter().all(SnapshotCheck::is_intact)
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let damaged = self.snapshots.iter().filter(|check| !check.is_intact()).count();
+        writeln!(
+            f,
+            "{}: {} snapshots checked, {} damaged",
+            self.title,
+            self.snapshots.len(),
+            damaged
+        )?;
+
+        for check in self.snapshots.iter().filter(|check| !check.is_intact()) {
+            writeln!(f, "  Snapshot {} ({})", check.snapshot_id, check.backup_path)?;
+            if let Some(error) = &check.error {
+                writeln!(f, "    error: {}", error)?;
+            }
+            for file in &check.missing {
+                writeln!(f, "    missing: {}", file)?;
+            }
+            for file in &check.extra {
+                writeln!(f, "    extra: {}", file)?;
+            }
+            for file in &check.corrupted {
+                writeln!(f, "    corrupted: {}", file)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The outcome of backing up one or more saves.
+#[derive(Clone, Debug, Default)]
+pub struct BackupReport {
+    /// The IDs of the newly created snapshots.
+    pub snapshot_ids: Vec<i32>,
+    /// The files copied into the snapshots and the files that could not be backed up.
+    pub files: FileReport,
+    /// The games that could not be backed up at all, each with its title and the error that stopped it.
+    pub failed_games: Vec<String>,
+}
+
+impl BackupReport {
+    /// Returns whether every game was backed up with all of its files.
+    pub fn is_complete(&self) -> bool {
+        self.files.is_complete() && self.failed_games.is_empty()
+    }
+
+    /// Records a game that could not be backed up.
+    pub fn record_game_failure(&mut self, title: &str, error: &Error) {
+        self.failed_games.push(format!("'{}': {}", title, error));
+    }
+
+    /// Adds the snapshots, files and failed games of another report to this one.
+    pub fn merge(&mut self, other: BackupReport) {
+        self.snapshot_ids.extend(other.snapshot_ids);
+        self.files.merge(other.files);
+        self.failed_games.extend(other.failed_games);
+    }
+}
+
+impl fmt::Display for BackupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Created {} snapshots, {}", self.snapshot_ids.len(), self.files)?;
+        for failure in &self.failed_games {
+            writeln!(f, "  {}", failure)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The outcome of moving the backups to another backup root.
+#[derive(Clone, Debug, Default)]
+pub struct MoveReport {
+    pub snapshots_moved: usize,
+    /// The files deleted from the old backup root after they were copied, and the ones left behind.
+    /// Empty if the backup root could be renamed.
+    pub old_files: FileReport,
+}
+
+/// The outcome of deleting the snapshots a retention policy no longer keeps.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PruneReport {
+    pub snapshots_deleted: usize,
+    /// The disk space the deleted snapshots took up, in bytes.
+    pub bytes_freed: u64,
+}
+
+/// A save of a game together with its platform, save file location and backups.
+#[derive(Clone, Debug)]
+pub struct SaveSummary {
+    pub save: db::Save,
+    pub platform_name: String,
+    pub location: db::Location,
+    /// When the most recent backup was taken as a unix timestamp, `None` if there is none yet.
+    pub last_backup: Option<i64>,
+    /// The disk space the snapshots of the save take up, in bytes.
+    pub backup_size: u64,
+}
+
+/// The outcome of removing a game.
+#[derive(Clone, Debug, Default)]
+pub struct RemoveReport {
+    pub title: String,
+    /// The deleted backup files and the ones that could not be deleted. Empty if the backups were kept.
+    pub files: FileReport,
+    /// The folder the backups were moved to if they were kept and there were any.
+    pub kept_in: Option<PathBuf>,
+}
+
+impl fmt::Display for RemoveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Removed '{}'", self.title)?;
+        match &self.kept_in {
+            Some(kept_in) => write!(f, ", kept its backups in {}", kept_in.display())?,
+            None => write!(f, ", freed {} files ({} bytes)", self.files.succeeded, self.files.bytes)?,
+        }
+        if !self.files.is_complete() {
+            write!(f, ", {} files could not be deleted", self.files.failed.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// What restoring a snapshot would do to a single file in the save file location.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RestoreAction {
+    /// The file doesn't exist yet and would be created.
+    Add,
+    /// The file exists with different contents and would be overwritten.
+    Overwrite,
+    /// The file already has the contents stored in the snapshot.
+    Unchanged,
+    /// The file is not part of the snapshot and would be left as it is.
+    NotInBackup,
+}
+
+impl RestoreAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RestoreAction::Add => "add",
+            RestoreAction::Overwrite => "overwrite",
+            RestoreAction::Unchanged => "unchanged",
+            RestoreAction::NotInBackup => "not in backup",
+        }
+    }
+}
+
+/// A file in the preview of a restore.
+#[derive(Clone, Debug)]
+pub struct RestorePreviewEntry {
+    /// The path of the file relative to the save file location, using `/` as separator.
+    pub path: String,
+    pub action: RestoreAction,
+    /// The size and modification time of the file in the snapshot, if it is part of it.
+    pub backup: Option<(u64, i64)>,
+    /// The size and modification time of the file in the save file location, if it exists.
+    pub current: Option<(u64, i64)>,
+}
+
+impl RestorePreviewEntry {
+    /// Returns whether the file in the snapshot was modified after the file it would overwrite.
+    ///
+    /// Returns `None` if either file is missing, the modification times are equal or the snapshot
+    /// doesn't record modification times.
+    pub fn backup_is_newer(&self) -> Option<bool> {
+        match (self.backup, self.current) {
+            (Some((_, backup_mtime)), Some((_, current_mtime)))
+                if backup_mtime != 0 && backup_mtime != current_mtime =>
+            {
+                Some(backup_mtime > current_mtime)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What restoring a snapshot would change in the save file location of its save.
+#[derive(Clone, Debug, Default)]
+pub struct RestorePreview {
+    pub save_id: i32,
+    /// When the snapshot was taken as a unix timestamp, 0 for backups made before snapshots existed.
+    pub created_at: i64,
+    pub location_path: String,
+    pub entries: Vec<RestorePreviewEntry>,
+}
+
+impl RestorePreview {
+    /// Returns the number of files the restore would handle with the given action.
+    pub fn count(&self, action: RestoreAction) -> usize {
+        self.entries.iter().filter(|entry| entry.action == action).count()
+    }
+
+    /// Returns whether the restore would write any file.
+    pub fn has_changes(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| matches!(entry.action, RestoreAction::Add | RestoreAction::Overwrite))
+    }
+}
+
+impl fmt::Display for RestorePreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} to add, {} to overwrite, {} unchanged, {} not in backup",
+            self.location_path,
+            self.count(RestoreAction::Add),
+            self.count(RestoreAction::Overwrite),
+            self.count(RestoreAction::Unchanged),
+            self.count(RestoreAction::NotInBackup)
+        )?;
+
+        for entry in &self.entries {
+            write!(f, "  {}: {}", entry.action.label(), entry.path)?;
+            if let Some((size, _)) = entry.backup {
+                write!(f, " ({} bytes", size)?;
+                if let Some((current_size, _)) = entry.current {
+                    write!(f, ", currently {} bytes", current_size)?;
+                }
+                match entry.backup_is_newer() {
+                    Some(true) => write!(f, ", backup is newer")?,
+                    Some(false) => write!(f, ", backup is older")?,
+                    None => {}
+                }
+                write!(f, ")")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A snapshot folder of a merged library and where the snapshot added for it keeps its copy.
+struct SnapshotCopy {
+    source: PathBuf,
+    destination: PathBuf,
+    format: BackupFormat,
+}
 
 pub struct GameSaves<'a>{
     db: &'a db::Db,
-    fs: &'a filesystem::Filesystem,
+    fs: &'a Filesystem,
+    settings: &'a Settings,
 }
 
-impl<'a> GameSaves<'a>{
-    pub fn new(db: &'a Db, fs: &'a filesystem::Filesystem) -> Self{
-        Self{db, fs}
-    }
-
-    /// get game title by id
-    pub fn get_game_title_by_id(&self, game_id: i32) -> String {
-        let game = self.db.get_game(game_id).expect("Failed to get game");
-        game.title
-    }
-
-    pub fn remove_game_save(&self, game_id: i32) {    
-    
-            // Check if the game exists in the database
-            let existing_game = self.db.get_game(game_id).expect("Failed to get game");
-    
-            // Delete the game's save files by using get_all_saves
-            let saves = self.db
-                .get_all_saves_by_id(game_id)
-                .expect("Failed to get saves");
-            for save in saves {
-                let backup_file_location = PathBuf::from(&format!(
-                    "backups/{}/{}/{}/",
-                    save.game_id, save.platform_id, save.id
-                ));
-    
-                if backup_file_location.exists() {
-                    fs::remove_dir_all(&backup_file_location).expect("Failed to delete game save");
-                    println!("Game save for '{}' deleted", existing_game.title);
-                } else {
-                    println!("No save files found for '{}'", existing_game.title);
-                }
-    
-                // Delete the save from the database
-                self.db.delete_save(save.id).expect("Failed to delete save");
-                self.db.delete_location(save.location_id)
-                    .expect("Failed to delete location");
-            }
-    
-            // Delete the game from the database
-            self.db.delete_game(game_id).expect("Failed to delete game");
-    
-            println!("'{}' deleted", existing_game.title);
-        }
-    
-    pub fn edit_game_save(&self) {
-        // Get the game title from the user
-        print!("Enter the game title: ");
-        io::stdout().flush().unwrap();
-        let mut title = String::new();
-        io::stdin()
-            .read_line(&mut title)
-            .expect("Failed to read line");
-    
-        // Retrieve games from the database and display them to the user
-        let games = self.db
-            .get_games_by_title(title.trim())
-            .expect("Failed to get games");
-        if games.is_empty() {
-            println!("No games found with that title");
+impl<'a> GameSaves<'a>{
+    pub fn new(db: &'a Db, fs: &'a Filesystem, settings: &'a Settings) -> Self{
+        Self{db, fs, settings}
+    }
+
+    /// Returns a game by its ID.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a not found error if there is no game with the ID.
+    pub fn game(&self, game_id: i32) -> Result<Game> {
+        self.db.get_game(game_id)?.ok_or_else(|| Error::not_found("game", game_id))
+    }
+
+    /// Returns a platform by its ID, see [`GameSaves::game`].
+    pub fn platform(&self, platform_id: i32) -> Result<db::Platform> {
+        self.db.get_platform(platform_id)?.ok_or_else(|| Error::not_found("platform", platform_id))
+    }
+
+    /// Returns a save file location by its ID, see [`GameSaves::game`].
+    pub fn location(&self, location_id: i32) -> Result<db::Location> {
+        self.db.get_location(location_id)?.ok_or_else(|| Error::not_found("save file location", location_id))
+    }
+
+    fn save(&self, save_id: i32) -> Result<db::Save> {
+        self.db.get_save(save_id)?.ok_or_else(|| Error::not_found("save", save_id))
+    }
+
+    fn snapshot(&self, snapshot_id: i32) -> Result<db::Snapshot> {
+        self.db.get_snapshot(snapshot_id)?.ok_or_else(|| Error::not_found("snapshot", snapshot_id))
+    }
+
+    /// Returns the title of a game.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the game cannot be found in the database.
+    pub fn get_game_title_by_id(&self, game_id: i32) -> Result<String> {
+        Ok(self.game(game_id)?.title)
+    }
+
+    /// Removes a game with its saves and snapshots, and deletes or keeps its backup files.
+    ///
+    /// The rows are deleted in a single transaction, so a failure never leaves saves without their game.
+    /// Kept backups are moved to the `removed` folder of the backup root before that, where a game added
+    /// later can't mistake them for its own. Deleted backups are deleted after the rows, backup files
+    /// that cannot be deleted are reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to remove.
+    /// * `keep_backups` - Whether to keep the backup files and only remove the game from the catalogue.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the game cannot be found or deleted from the database, or
+    /// its backups should be kept and cannot be moved. Nothing is removed in that case.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the deleted files and their combined size, or where the backups were kept.
+    pub fn remove_game_save(&self, game_id: i32, keep_backups: bool) -> Result<RemoveReport> {
+        // Check if the game exists in the database
+        let existing_game = self.game(game_id)?;
+        let saves = self.db.get_all_saves_by_id(game_id)?;
+        let mut snapshots = Vec::new();
+        for save in &saves {
+            snapshots.extend(self.db.get_snapshots_by_save_id(save.id)?);
+        }
+
+        let mut report = RemoveReport { title: existing_game.title, ..Default::default() };
+        let game_backup_location = self.settings.backup_root().join(game_id.to_string());
+
+        // The backups are moved before the rows are deleted, so a game whose backups can't be moved stays
+        if keep_backups && game_backup_location.is_dir() {
+            let kept_in = self.settings
+                .backup_root()
+                .join(REMOVED_FOLDER_NAME)
+                .join(format!("{}_{}", game_id, Local::now().format("%Y-%m-%d_%H-%M-%S%.3f")));
+            fs::create_dir_all(self.settings.backup_root().join(REMOVED_FOLDER_NAME))?;
+            fs::rename(&game_backup_location, &kept_in)?;
+            report.kept_in = Some(kept_in);
+        }
+
+        // Deleting the game deletes its saves, snapshots, retention policy and schedule entries with it
+        let deleted = self.db.in_transaction(|| -> Result<(), rusqlite::Error> {
+            self.db.delete_game(game_id)?;
+            for save in &saves {
+                self.db.delete_location(save.location_id)?;
+            }
+            Ok(())
+        });
+        if let Err(e) = deleted {
+            if let Some(kept_in) = &report.kept_in {
+                let _ = fs::rename(kept_in, &game_backup_location);
+            }
+            return Err(e.into());
+        }
+
+        if keep_backups {
+            return Ok(report);
+        }
+
+        report.files = self.delete_backup_files(&saves, &snapshots);
+        let _ = fs::remove_dir(&game_backup_location);
+
+        // Remove the stored files no other snapshot refers to
+        self.clean_up_objects(&mut report.files);
+
+        Ok(report)
+    }
+
+    /// Deletes the backup folders of saves, and the folders of their snapshots that were imported from
+    /// somewhere else.
+    ///
+    /// # Arguments
+    ///
+    /// * `saves` - The saves whose backups to delete.
+    /// * `snapshots` - The snapshots of those saves.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the deleted files and their combined size, and the files that could not be
+    /// deleted.
+    fn delete_backup_files(&self, saves: &[db::Save], snapshots: &[db::Snapshot]) -> FileReport {
+        let mut report = FileReport::default();
+
+        let mut backup_file_locations: Vec<PathBuf> = saves.iter().map(|save| self.save_backup_location(save)).collect();
+        for snapshot in snapshots {
+            let backup_file_location = PathBuf::from(&snapshot.backup_path);
+            if !backup_file_locations.iter().any(|location| backup_file_location.starts_with(location)) {
+                backup_file_locations.push(backup_file_location);
+            }
+        }
+        for backup_file_location in &backup_file_locations {
+            if backup_file_location.exists() {
+                match self.fs.delete_files(backup_file_location) {
+                    Ok(files) => report.merge(files),
+                    Err(e) => report.record_failure(backup_file_location.clone(), &e),
+                }
+            }
+        }
+
+        // Leave no empty platform folders behind
+        for save in saves {
+            if let Some(platform_location) = self.save_backup_location(save).parent() {
+                let _ = fs::remove_dir(platform_location);
+            }
+        }
+
+        report
+    }
+
+    /// Adds a new game save to the database and creates a backup of the save files in the backup folder.
+    ///
+    /// The rows are only kept if every save file could be backed up, so a save file location that can't
+    /// be read doesn't leave a game behind that has nothing to restore. The rows are inserted first and
+    /// deleted again if the backup fails, so the database isn't locked while the files are copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game to add.
+    /// * `path` - The save file location of the game.
+    /// * `platform` - The name of the platform the save is for.
+    /// * `include_patterns` - Glob patterns of the files to back up, empty to back up every file.
+    /// * `exclude_patterns` - Glob patterns of the files to skip.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a validation error if the title, save file location or platform is
+    /// empty or a file pattern is not valid, and an error if the game cannot be added to the database or
+    /// any of its save files cannot be backed up.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the first snapshot.
+    pub fn add_game_save(
+        &self,
+        game: db::Game,
+        path: String,
+        platform: String,
+        include_patterns: Vec<String>,
+        exclude_patterns: Vec<String>,
+    ) -> Result<BackupReport> {
+        if game.title.trim().is_empty() {
+            return Err(Error::Validation("the game needs a title".to_string()));
+        }
+        if path.trim().is_empty() {
+            return Err(Error::Validation("the game needs a save file location".to_string()));
+        }
+        if platform.trim().is_empty() {
+            return Err(Error::Validation("the game needs a platform".to_string()));
+        }
+        check_patterns(&include_patterns, &exclude_patterns)?;
+
+        let (game_id, save) = self.db.in_transaction(|| -> Result<_> {
+            let game_id = self.db.insert_game(game)?;
+            let save = self.insert_save(game_id, &path, &platform, &include_patterns, &exclude_patterns)?;
+            Ok((game_id, save))
+        })?;
+        self.back_up_added_save(&save, Some(game_id))
+    }
+
+    /// Adds another save file location to a game and creates the first backup of it, e.g. for a game
+    /// that keeps its configuration in one folder and its saves in another.
+    ///
+    /// Like with [`GameSaves::add_game_save`], the save is only kept if every save file could be backed up.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to add the save to.
+    /// * `path` - The save file location.
+    /// * `platform` - The name of the platform the save is for.
+    /// * `include_patterns` - Glob patterns of the files to back up, empty to back up every file.
+    /// * `exclude_patterns` - Glob patterns of the files to skip.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a validation error if the save file location or platform is empty, a
+    /// file pattern is not valid or the game already has a save in the same location on the same
+    /// platform. It returns an error if the game cannot be found, the save cannot be added to the database
+    /// or any of its save files cannot be backed up.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the first snapshot.
+    pub fn add_save(
+        &self,
+        game_id: i32,
+        path: &str,
+        platform: &str,
+        include_patterns: &[String],
+        exclude_patterns: &[String],
+    ) -> Result<BackupReport> {
+        if path.trim().is_empty() {
+            return Err(Error::Validation("the save needs a save file location".to_string()));
+        }
+        if platform.trim().is_empty() {
+            return Err(Error::Validation("the save needs a platform".to_string()));
+        }
+        check_patterns(include_patterns, exclude_patterns)?;
+        let game = self.game(game_id)?;
+        for save in self.db.get_saves_by_game_id(game_id)? {
+            let location = self.location(save.location_id)?;
+            let platform_name = self.platform(save.platform_id)?.platform_name;
+            if location.location_path == path.trim() && platform_name.to_lowercase() == platform.trim().to_lowercase() {
+                return Err(Error::Validation(format!(
+                    "'{}' already has a save in {} on {}",
+                    game.title, location.location_path, platform_name
+                )));
+            }
+        }
+
+        let save = self.db.in_transaction(|| {
+            self.insert_save(game_id, path, platform, include_patterns, exclude_patterns)
+        })?;
+        self.back_up_added_save(&save, None)
+    }
+
+    /// Inserts a save of a game with its platform and location.
+    fn insert_save(
+        &self,
+        game_id: i32,
+        path: &str,
+        platform: &str,
+        include_patterns: &[String],
+        exclude_patterns: &[String],
+    ) -> Result<db::Save> {
+        let platform_id = self.db.insert_platform(platform.trim())?;
+        let location_id = self.db.insert_location(path.trim(), "", include_patterns, exclude_patterns)?;
+        let save_id = self.db.insert_save(game_id, location_id, "", platform_id)?;
+        self.save(save_id)
+    }
+
+    /// Takes the first snapshot of a save that was just added, and removes the save again, together
+    /// with its game if that was added with it, unless every save file was backed up.
+    ///
+    /// # Arguments
+    ///
+    /// * `save` - The save that was added.
+    /// * `added_game_id` - The ID of the game if it was added together with the save.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshot failed, missed any save file or was
+    /// cancelled. The save is removed in that case.
+    fn back_up_added_save(&self, save: &db::Save, added_game_id: Option<i32>) -> Result<BackupReport> {
+        let backed_up = self.create_snapshot(save).and_then(|report| {
+            if !report.files.is_complete() {
+                return Err(Error::Io(io::Error::other(format!(
+                    "the save files could not be backed up, {}",
+                    report.files
+                ))));
+            }
+            if report.snapshot_ids.is_empty() {
+                return Err(Error::Io(io::Error::new(io::ErrorKind::Interrupted, "the backup was cancelled")));
+            }
+            Ok(report)
+        });
+        if backed_up.is_ok() {
+            return backed_up;
+        }
+
+        // Deleting the save deletes the snapshot it may have got with it
+        self.db.in_transaction(|| -> Result<(), rusqlite::Error> {
+            match added_game_id {
+                Some(game_id) => self.db.delete_game(game_id)?,
+                None => self.db.delete_save(save.id)?,
+            }
+            self.db.delete_location(save.location_id)?;
+            Ok(())
+        })?;
+        let _ = fs::remove_dir_all(self.save_backup_location(save));
+        if self.settings.backup_format == BackupFormat::Deduplicated {
+            let _ = self.delete_unreferenced_objects();
+        }
+
+        backed_up
+    }
+
+    /// Removes a single save of a game with its snapshots and backup files. The game stays, even if it
+    /// has no saves left.
+    ///
+    /// # Arguments
+    ///
+    /// * `save_id` - The ID of the save to remove.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the save cannot be found or deleted from the database.
+    /// Nothing is removed in that case.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the deleted files and their combined size, and the files that could not be
+    /// deleted.
+    pub fn remove_save(&self, save_id: i32) -> Result<FileReport> {
+        let save = self.save(save_id)?;
+        let snapshots = self.db.get_snapshots_by_save_id(save_id)?;
+
+        // Deleting the save deletes its snapshots with it
+        self.db.in_transaction(|| -> Result<(), rusqlite::Error> {
+            self.db.delete_save(save_id)?;
+            self.db.delete_location(save.location_id)?;
+            Ok(())
+        })?;
+
+        let mut report = self.delete_backup_files(&[save], &snapshots);
+        self.clean_up_objects(&mut report);
+        Ok(report)
+    }
+
+    /// Lists the saves of a game with their platform, save file location and backups.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to list the saves of.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the saves cannot be read from the database.
+    pub fn list_saves(&self, game_id: i32) -> Result<Vec<SaveSummary>> {
+        let mut summaries = Vec::new();
+        for save in self.db.get_saves_by_game_id(game_id)? {
+            let snapshots = self.db.get_snapshots_by_save_id(save.id)?;
+            let last_backup = snapshots
+                .iter()
+                .filter(|snapshot| snapshot.kind == SnapshotKind::Backup)
+                .map(|snapshot| snapshot.created_at)
+                .max();
+            let backup_size = self.snapshot_usage(&snapshots).iter().map(|usage| usage.size).sum();
+
+            summaries.push(SaveSummary {
+                platform_name: self.platform(save.platform_id)?.platform_name,
+                location: self.location(save.location_id)?,
+                save,
+                last_backup,
+                backup_size,
+            });
+        }
+        Ok(summaries)
+    }
+
+    /// Updates the title, publisher and release date of a game.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game with its new details.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a validation error if the title is empty, and an error if the game
+    /// cannot be found or updated in the database.
+    pub fn update_game(&self, game: &db::Game) -> Result<()> {
+        if game.title.trim().is_empty() {
+            return Err(Error::Validation("the game needs a title".to_string()));
+        }
+        self.game(game.id)?;
+
+        self.db.update_game(game.id, game.title.trim(), game.publisher.trim(), game.release_date)?;
+        Ok(())
+    }
+
+    /// Changes the save file location, file patterns and platform of a save.
+    ///
+    /// The backup folder of a save is named after its platform, so changing the platform moves the
+    /// save's backups to the folder of the new platform. Either everything is changed or nothing is.
+    ///
+    /// # Arguments
+    ///
+    /// * `save_id` - The ID of the save to change.
+    /// * `path` - The new save file location.
+    /// * `platform` - The name of the new platform.
+    /// * `include_patterns` - Glob patterns of the files to back up, empty to back up every file.
+    /// * `exclude_patterns` - Glob patterns of the files to skip.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a validation error if the save file location or platform is empty or a
+    /// file pattern is not valid, and an error if the save cannot be found or updated, or its backups
+    /// cannot be moved.
+    pub fn update_save(
+        &self,
+        save_id: i32,
+        path: &str,
+        platform: &str,
+        include_patterns: &[String],
+        exclude_patterns: &[String],
+    ) -> Result<()> {
+        if path.trim().is_empty() {
+            return Err(Error::Validation("the game needs a save file location".to_string()));
+        }
+        if platform.trim().is_empty() {
+            return Err(Error::Validation("the game needs a platform".to_string()));
+        }
+        check_patterns(include_patterns, exclude_patterns)?;
+
+        self.db.in_transaction(|| {
+            let save = self.save(save_id)?;
+            let location = self.location(save.location_id)?;
+
+            // Another save may share the location, so a changed location is added as a new one
+            let location_id = if location.location_path == path.trim()
+                && location.include_patterns == include_patterns
+                && location.exclude_patterns == exclude_patterns
+            {
+                location.id
+            } else {
+                self.db.insert_location(path.trim(), &location.description, include_patterns, exclude_patterns)?
+            };
+            let platform_id = self.db.insert_platform(platform.trim())?;
+            self.db.update_save(save_id, location_id, platform_id)?;
+            if location_id != location.id {
+                self.db.delete_location(location.id)?;
+            }
+
+            if platform_id != save.platform_id {
+                let updated = db::Save { location_id, platform_id, ..save.clone() };
+                self.move_save_backups(&save, &updated)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Moves the backup folder of a save to the one of its updated row and updates the paths of its
+    /// snapshots. The folder is renamed last, so a failure leaves the files where the database, once
+    /// rolled back, expects them.
+    fn move_save_backups(&self, save: &db::Save, updated: &db::Save) -> Result<()> {
+        let old_location = self.save_backup_location(save);
+        let new_location = self.save_backup_location(updated);
+        if !old_location.exists() {
+            return Ok(());
+        }
+        if new_location.exists() {
+            return Err(Error::Validation(format!("{} already exists", new_location.display())));
+        }
+
+        for snapshot in self.db.get_snapshots_by_save_id(save.id)? {
+            if let Ok(relative_path) = Path::new(&snapshot.backup_path).strip_prefix(&old_location) {
+                self.db
+                    .update_snapshot_backup_path(snapshot.id, &new_location.join(relative_path).display().to_string())?;
+            }
+        }
+
+        if let Some(parent) = new_location.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&old_location, &new_location)?;
+        Ok(())
+    }
+
+    /// Returns the folder holding every backup of a save.
+    fn save_backup_location(&self, save: &db::Save) -> PathBuf {
+        self.settings
+            .backup_root()
+            .join(save.game_id.to_string())
+            .join(save.platform_id.to_string())
+            .join(save.id.to_string())
+    }
+
+    /// Returns the content-addressed object store shared by all deduplicated snapshots.
+    fn objects_location(&self) -> PathBuf {
+        self.settings.backup_root().join("objects")
+    }
+
+    /// Returns the folder holding the copied files of a plain copy snapshot.
+    ///
+    /// Plain copies that were made before snapshots had a manifest keep their files directly in the
+    /// snapshot folder.
+    fn directory_files_location(backup_file_location: &Path) -> PathBuf {
+        let files_location = backup_file_location.join(DIRECTORY_FILES_NAME);
+        if files_location.is_dir() && backup_file_location.join(MANIFEST_FILE_NAME).is_file() {
+            files_location
+        } else {
+            backup_file_location.to_path_buf()
+        }
+    }
+
+    /// Returns where a file listed in the manifest of a plain copy snapshot is stored.
+    ///
+    /// Files that were unchanged in an incremental backup are stored in the folder of an earlier snapshot.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the earlier snapshot cannot be found in the database.
+    fn directory_file_location(&self, snapshot: &db::Snapshot, entry: &ManifestEntry) -> Result<PathBuf, io::Error> {
+        let backup_path = match entry.stored_in {
+            Some(snapshot_id) => {
+                self.snapshot(snapshot_id)
+                    .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?
+                    .backup_path
+            }
+            None => snapshot.backup_path.clone(),
+        };
+
+        Ok(Self::directory_files_location(&PathBuf::from(backup_path)).join(&entry.path))
+    }
+
+    /// Reads the manifest of a snapshot.
+    fn load_manifest(snapshot: &db::Snapshot) -> Result<Manifest> {
+        Manifest::load(&PathBuf::from(&snapshot.backup_path).join(MANIFEST_FILE_NAME))
+    }
+
+    /// Returns the ID and manifest of the most recent snapshot of a save that was stored in the given
+    /// format, which incremental backups compare against, or `None` if there is none with a readable
+    /// manifest.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshots cannot be read from the database.
+    fn previous_manifest(&self, save: &db::Save, format: BackupFormat) -> Result<Option<(i32, Manifest)>> {
+        let snapshots = self.db.get_snapshots_by_save_id(save.id)?;
+
+        let previous = snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.format == format)
+            .and_then(|snapshot| Some((snapshot.id, Self::load_manifest(snapshot).ok()?)));
+        Ok(previous)
+    }
+
+    /// Returns the path of the archive inside a snapshot folder stored in an archive format.
+    fn archive_location(backup_file_location: &Path, format: BackupFormat) -> PathBuf {
+        backup_file_location.join(format.archive_file_name().unwrap_or_default())
+    }
+
+    /// Copies the current files of a save into a new timestamped snapshot.
+    ///
+    /// Every call creates a new folder inside the save's backup folder, so earlier snapshots are never
+    /// overwritten. The files are stored in the backup format chosen in the settings. Files that cannot
+    /// be read are left out of the snapshot and listed in the report.
+    ///
+    /// # Arguments
+    ///
+    /// * `save` - The save to take a snapshot of.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshot folder, archive or manifest cannot be written,
+    /// or the snapshot cannot be added to the database. The partly written snapshot folder is removed.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report with the ID of the newly created snapshot, or without any snapshot if the job
+    /// the backup runs in was cancelled.
+    pub fn create_snapshot(&self, save: &db::Save) -> Result<BackupReport> {
+        self.take_snapshot(save, SnapshotKind::Backup)
+    }
+
+    /// Copies the current files of a save into a new snapshot of the given kind, see
+    /// [`GameSaves::create_snapshot`].
+    fn take_snapshot(&self, save: &db::Save, kind: SnapshotKind) -> Result<BackupReport> {
+        let _backups = lock_backups();
+        let location = self.location(save.location_id)?;
+
+        let now = Local::now();
+        let format = self.settings.backup_format;
+
+        // Encrypted backups can't be taken before the passphrase was entered
+        let key = crypto::unlocked_key(&self.settings.backup_root());
+        if format == BackupFormat::Encrypted && key.is_none() {
+            let mut report = FileReport::default();
+            report.record_failure(PathBuf::from(&location.location_path), &crypto::locked_error());
+            return Ok(BackupReport { files: report, ..Default::default() });
+        }
+
+        // Snapshots taken within the same millisecond, like the one before a restore and the one before
+        // undoing it, must not share a folder
+        let folder_name = now.format("%Y-%m-%d_%H-%M-%S%.3f").to_string();
+        let mut backup_file_location = self.save_backup_location(save).join(&folder_name);
+        let mut suffix = 1;
+        while backup_file_location.exists() {
+            backup_file_location = self.save_backup_location(save).join(format!("{}-{}", folder_name, suffix));
+            suffix += 1;
+        }
+
+        let save_file_location = PathBuf::from(&location.location_path);
+        let filter = FileFilter::for_location(&location)?;
+        let previous = if self.settings.incremental {
+            self.previous_manifest(save, format)?
+        } else {
+            None
+        };
+
+        let mut report = FileReport::default();
+        let scanned = self.fs.scan_changes(
+            &save_file_location,
+            previous.as_ref().map_or(&Manifest::default(), |(_, previous)| previous),
+            self.settings.incremental_compare_hash,
+            &filter,
+            &mut report,
+        );
+        let mut manifest = match scanned {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                report.record_failure(save_file_location, &e);
+                return Ok(BackupReport { files: report, ..Default::default() });
+            }
+        };
+        self.fs.add_to_total(manifest.files.len(), manifest.files.iter().map(|entry| entry.size).sum());
+
+        let stored = self.store_snapshot_files(
+            &save_file_location,
+            &backup_file_location,
+            previous.as_ref(),
+            &mut manifest,
+            key.as_ref(),
+            &mut report,
+        );
+
+        // A cancelled backup leaves no partial snapshot behind
+        if self.fs.is_cancelled() {
+            let _ = fs::remove_dir_all(&backup_file_location);
+            return Ok(BackupReport::default());
+        }
+
+        // Files that failed to copy are not part of the snapshot
+        manifest
+            .files
+            .retain(|entry| !report.has_failed(&save_file_location.join(&entry.path)));
+
+        let inserted = (|| -> Result<i32> {
+            stored?;
+            let manifest_path = backup_file_location.join(MANIFEST_FILE_NAME);
+            match key.as_ref().filter(|_| format == BackupFormat::Encrypted) {
+                Some(key) => manifest.save_encrypted(&manifest_path, key)?,
+                None => manifest.save(&manifest_path)?,
+            }
+            Ok(self.db.insert_snapshot(
+                save.id,
+                now.timestamp(),
+                &backup_file_location.display().to_string(),
+                format,
+                kind,
+            )?)
+        })();
+
+        // A snapshot that isn't in the database is never cleaned up, so don't leave its folder behind
+        match inserted {
+            Ok(snapshot_id) => Ok(BackupReport {
+                snapshot_ids: vec![snapshot_id],
+                files: report,
+                ..Default::default()
+            }),
+            Err(e) => {
+                let _ = fs::remove_dir_all(&backup_file_location);
+                Err(e)
+            }
+        }
+    }
+
+    /// Copies the files listed in the manifest of a new snapshot into its folder, or into the object
+    /// store, in the backup format chosen in the settings.
+    ///
+    /// Plain copies only copy the files that changed since `previous` and point the others at the
+    /// snapshot that stores them. Files that cannot be read are recorded in the report.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshot folder or archive cannot be written.
+    fn store_snapshot_files(
+        &self,
+        save_file_location: &Path,
+        backup_file_location: &Path,
+        previous: Option<&(i32, Manifest)>,
+        manifest: &mut Manifest,
+        key: Option<&BackupKey>,
+        report: &mut FileReport,
+    ) -> Result<(), io::Error> {
+        let format = self.settings.backup_format;
+        match format {
+            BackupFormat::Directory => {
+                let files_location = backup_file_location.join(DIRECTORY_FILES_NAME);
+                fs::create_dir_all(&files_location)?;
+
+                // Only copy new and changed files, the rest stay where the previous snapshot keeps them
+                for entry in &mut manifest.files {
+                    if self.fs.begin_file(&entry.path).is_err() {
+                        break;
+                    }
+                    let previous_entry = previous
+                        .and_then(|(previous_id, previous)| Some((*previous_id, previous.get(&entry.path)?)));
+
+                    match previous_entry {
+                        Some((previous_id, previous_entry)) if previous_entry.sha256 == entry.sha256 => {
+                            entry.stored_in = previous_entry.stored_in.or(Some(previous_id));
+                            self.fs.finish_file(entry.size);
+                        }
+                        _ => match self.fs.copy_file(save_file_location, Path::new(&entry.path), &files_location) {
+                            Ok(size) => {
+                                report.record_success(size);
+                                self.fs.finish_file(size);
+                            }
+                            Err(e) => report.record_failure(save_file_location.join(&entry.path), &e),
+                        },
+                    }
+                }
+            }
+            BackupFormat::Deduplicated => {
+                report.merge(self.fs.store_objects(save_file_location, manifest, &self.objects_location()));
+            }
+            BackupFormat::TarZst | BackupFormat::Zip | BackupFormat::Encrypted => {
+                let archive = Self::archive_location(backup_file_location, format);
+                report.merge(self.fs.archive_files(save_file_location, &archive, format, manifest, key)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Backs up every save of a game again by taking a new snapshot of its save file location, then
+    /// prunes the snapshots its retention policy no longer keeps.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to back up.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the saves cannot be read from the database, a snapshot
+    /// cannot be written, see [`GameSaves::create_snapshot`], or the game fails to prune afterwards. The
+    /// snapshots taken before are kept.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the newly created snapshots and the files that could not be backed up.
+    pub fn backup_game(&self, game_id: i32) -> Result<BackupReport> {
+        let saves = self.db.get_saves_by_game_id(game_id)?;
+
+        let mut report = BackupReport::default();
+        for save in &saves {
+            if self.fs.is_cancelled() {
+                return Ok(report);
+            }
+            report.merge(self.create_snapshot(save)?);
+        }
+
+        // Make room for the new snapshots according to the game's retention policy
+        self.prune_game(game_id)?;
+
+        Ok(report)
+    }
+
+    /// Takes a new snapshot of a save if its files differ from its most recent backup.
+    ///
+    /// # Arguments
+    ///
+    /// * `save` - The save to back up.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the save file location cannot be found or the snapshot
+    /// cannot be written, see [`GameSaves::create_snapshot`].
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the new snapshot, or `None` if nothing changed since the last backup.
+    pub fn backup_save_if_changed(&self, save: &db::Save) -> Result<Option<BackupReport>> {
+        let location = self.location(save.location_id)?;
+
+        let previous = match self.latest_snapshot(save)? {
+            Some(snapshot) => self.snapshot_manifest(&snapshot).ok(),
+            None => None,
+        };
+        if let Some(previous) = previous {
+            let current = self.fs.scan_changes(
+                Path::new(&location.location_path),
+                &previous,
+                false,
+                &FileFilter::for_location(&location)?,
+                &mut FileReport::default(),
+            );
+
+            let unchanged = match current {
+                Ok(current) => {
+                    current.files.len() == previous.files.len()
+                        && current.files.iter().all(|entry| {
+                            previous
+                                .get(&entry.path)
+                                .is_some_and(|previous_entry| previous_entry.sha256 == entry.sha256)
+                        })
+                }
+                Err(_) => false,
+            };
+            if unchanged {
+                return Ok(None);
+            }
+        }
+
+        self.create_snapshot(save).map(Some)
+    }
+
+    /// Backs up every save of every game in the database.
+    ///
+    /// A game that fails to back up, see [`GameSaves::backup_game`], doesn't stop the games after it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the games cannot be read from the database.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the newly created snapshots, the files that could not be backed up and the
+    /// games that failed.
+    pub fn backup_all_games(&self) -> Result<BackupReport> {
+        let games = self.db.get_all_games()?;
+
+        let mut report = BackupReport::default();
+        for game in &games {
+            if self.fs.is_cancelled() {
+                break;
+            }
+            match self.backup_game(game.id) {
+                Ok(game_report) => report.merge(game_report),
+                Err(e) => report.record_game_failure(&game.title, &e),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Returns every snapshot taken of a game's saves, oldest first per save.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to list the snapshots of.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshots cannot be read from the database.
+    pub fn list_snapshots(&self, game_id: i32) -> Result<Vec<db::Snapshot>> {
+        let mut snapshots = Vec::new();
+        for save in self.db.get_saves_by_game_id(game_id)? {
+            snapshots.extend(self.db.get_snapshots_by_save_id(save.id)?);
+        }
+        Ok(snapshots)
+    }
+
+    /// Restores the most recent snapshot of every save of a game.
+    ///
+    /// The files currently in each save file location are kept in a pre-restore snapshot first. If any
+    /// file of a save fails to restore, its save file location is rolled back to that snapshot, and a
+    /// successful restore can be undone later with [`GameSaves::undo_game_restore`].
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to restore.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any save fails to restore. The saves that were restored
+    /// before the failing one are rolled back to their pre-restore snapshots as well.
+    ///
+    /// # Returns
+    ///
+    /// Returns the IDs of the pre-restore snapshots.
+    pub fn restore_game(&self, game_id: i32) -> Result<Vec<i32>> {
+        let saves = self.db.get_saves_by_game_id(game_id)?;
+
+        let mut pre_restore_ids = Vec::new();
+        for save in &saves {
+            let restored = self
+                .restore_source(save)
+                .and_then(|snapshot| self.restore_safely(&snapshot, None, false));
+            match restored {
+                Ok(pre_restore_id) => pre_restore_ids.push(pre_restore_id),
+                Err(e) => return Err(self.roll_back_saves(&pre_restore_ids, e)),
+            }
+        }
+
+        Ok(pre_restore_ids)
+    }
+
+    /// Puts back the files of saves that were restored before another save of the same game failed to
+    /// restore, so the game isn't left half-restored.
+    ///
+    /// The rollback runs to the end even if the job the restore runs in was cancelled.
+    ///
+    /// # Returns
+    ///
+    /// Returns the error the restore failed with, extended with the saves that could not be rolled back.
+    fn roll_back_saves(&self, pre_restore_ids: &[i32], error: Error) -> Error {
+        let fs = Filesystem::new();
+        let game_save = GameSaves::new(self.db, &fs, self.settings);
+
+        let mut kept_in = Vec::new();
+        for pre_restore_id in pre_restore_ids.iter().rev() {
+            let rolled_back = self
+                .snapshot(*pre_restore_id)
+                .and_then(|pre_restore| game_save.restore_safely(&pre_restore, None, true));
+            if rolled_back.is_err() {
+                kept_in.push(pre_restore_id.to_string());
+            }
+        }
+
+        if kept_in.is_empty() {
+            error
+        } else {
+            Error::Io(io::Error::other(format!(
+                "{}, rolling back the other saves failed as well, their previous files are kept in snapshots {}",
+                error,
+                kept_in.join(", ")
+            )))
+        }
+    }
+
+    /// Returns whether restoring a game, or undoing its restore, needs the backup passphrase that hasn't
+    /// been entered yet, because one of its snapshots is encrypted or the pre-restore snapshot would be.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to restore.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshots cannot be read from the database.
+    pub fn needs_passphrase(&self, game_id: i32) -> Result<bool> {
+        if crypto::is_unlocked(&self.settings.backup_root()) {
+            return Ok(false);
+        }
+
+        Ok(self.settings.backup_format == BackupFormat::Encrypted
+            || self
+                .list_snapshots(game_id)?
+                .iter()
+                .any(|snapshot| snapshot.format == BackupFormat::Encrypted))
+    }
+
+    /// Undoes the most recent restore of a save by restoring its latest pre-restore snapshot.
+    ///
+    /// Files that the restore added are removed again. The undo itself takes a pre-restore snapshot,
+    /// so undoing twice redoes the restore.
+    ///
+    /// # Arguments
+    ///
+    /// * `save_id` - The ID of the save to undo the restore of.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any of the files cannot be restored. The save file
+    /// location is rolled back before the error is returned.
+    ///
+    /// # Returns
+    ///
+    /// Returns the ID of the new pre-restore snapshot, or `None` if the save was never restored.
+    pub fn undo_restore(&self, save_id: i32) -> Result<Option<i32>> {
+        let snapshots = self.db.get_snapshots_by_save_id(save_id)?;
+
+        match snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.kind == SnapshotKind::PreRestore)
+        {
+            Some(pre_restore) => self.restore_safely(pre_restore, None, true).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Undoes the most recent restore of every save of a game.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to undo the restore of.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any save fails to restore.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of saves whose restore was undone.
+    pub fn undo_game_restore(&self, game_id: i32) -> Result<usize> {
+        let saves = self.db.get_saves_by_game_id(game_id)?;
+
+        let mut undone = 0;
+        for save in saves {
+            if self.undo_restore(save.id)?.is_some() {
+                undone += 1;
+            }
+        }
+        Ok(undone)
+    }
+
+    /// Lists what restoring the most recent snapshot of every save of a game would change, without
+    /// writing anything.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to preview.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a snapshot or the files in a save file location cannot be
+    /// read.
+    pub fn preview_game_restore(&self, game_id: i32) -> Result<Vec<RestorePreview>> {
+        let saves = self.db.get_saves_by_game_id(game_id)?;
+
+        saves
+            .iter()
+            .map(|save| self.preview_snapshot(&self.restore_source(save)?))
+            .collect()
+    }
+
+    /// Compares the files of a snapshot with the files in the save file location of its save.
+    fn preview_snapshot(&self, snapshot: &db::Snapshot) -> Result<RestorePreview> {
+        let save = self.save(snapshot.save_id)?;
+        let location = self.location(save.location_id)?;
+        let save_file_location = PathBuf::from(&location.location_path);
+        let filter = FileFilter::for_location(&location)?;
+
+        let manifest = self.snapshot_manifest(snapshot)?;
+        let mut entries = Vec::new();
+
+        // Files the location's patterns exclude are neither restored nor touched
+        for entry in manifest.files.iter().filter(|entry| filter.matches(Path::new(&entry.path))) {
+            let current_file = save_file_location.join(&entry.path);
+            let current = match fs::metadata(&current_file) {
+                Ok(metadata) if metadata.is_file() => {
+                    Some((metadata.len(), filesystem::modified_time(&metadata)))
+                }
+                _ => None,
+            };
+
+            let action = match current {
+                None => RestoreAction::Add,
+                Some((size, _))
+                    if size == entry.size && self.fs.hash_file(&current_file)? == entry.sha256 =>
+                {
+                    RestoreAction::Unchanged
+                }
+                Some(_) => RestoreAction::Overwrite,
+            };
+
+            entries.push(RestorePreviewEntry {
+                path: entry.path.clone(),
+                action,
+                backup: Some((entry.size, entry.mtime)),
+                current,
+            });
+        }
+
+        if save_file_location.is_dir() {
+            for relative_path in self.fs.list_matching_files(&save_file_location, &filter)? {
+                let path = filesystem::manifest_path(&relative_path);
+                if manifest.get(&path).is_some() {
+                    continue;
+                }
+
+                let metadata = fs::metadata(save_file_location.join(&relative_path))?;
+                entries.push(RestorePreviewEntry {
+                    path,
+                    action: RestoreAction::NotInBackup,
+                    backup: None,
+                    current: Some((metadata.len(), filesystem::modified_time(&metadata))),
+                });
+            }
+        }
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(RestorePreview {
+            save_id: save.id,
+            created_at: snapshot.created_at,
+            location_path: location.location_path,
+            entries,
+        })
+    }
+
+    /// Returns the manifest of a snapshot, building one from the stored files if it has none.
+    fn snapshot_manifest(&self, snapshot: &db::Snapshot) -> Result<Manifest, io::Error> {
+        match Self::load_manifest(snapshot) {
+            Ok(manifest) => Ok(manifest),
+            Err(e) => match snapshot.format {
+                BackupFormat::Directory => self.fs.build_manifest(
+                    &Self::directory_files_location(Path::new(&snapshot.backup_path)),
+                    &FileFilter::default(),
+                ),
+                BackupFormat::TarZst | BackupFormat::Zip | BackupFormat::Encrypted => self.fs.build_archive_manifest(
+                    &Self::archive_location(Path::new(&snapshot.backup_path), snapshot.format),
+                    snapshot.format,
+                ),
+                BackupFormat::Deduplicated => Err(e.into()),
+            },
+        }
+    }
+
+    /// Restores a snapshot to the save file location of its save, protected by a pre-restore snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - The snapshot to restore.
+    /// * `files` - The files of the snapshot to restore, or `None` to restore all of them.
+    /// * `exact` - Whether to remove files from the save file location that are not in the snapshot.
+    ///
+    /// # Returns
+    ///
+    /// Returns the ID of the pre-restore snapshot.
+    fn restore_safely(
+        &self,
+        snapshot: &db::Snapshot,
+        files: Option<&[PathBuf]>,
+        exact: bool,
+    ) -> Result<i32> {
+        let save = self.save(snapshot.save_id)?;
+        let location = self.location(save.location_id)?;
+        let save_file_location = PathBuf::from(&location.location_path);
+        let filter = FileFilter::for_location(&location)?;
+
+        // Files the location's patterns exclude are neither restored nor removed
+        let snapshot_files: Vec<PathBuf> = self
+            .snapshot_files(snapshot)?
+            .into_iter()
+            .filter(|file| filter.matches(file))
+            .collect();
+        let restored_files = files.map_or_else(|| snapshot_files.clone(), |files| files.to_vec());
+
+        fs::create_dir_all(&save_file_location)?;
+        let pre_restore = self.take_snapshot(&save, SnapshotKind::PreRestore)?;
+
+        // Files that couldn't be kept in the pre-restore snapshot would be lost for good
+        if !pre_restore.files.is_complete() {
+            for snapshot_id in &pre_restore.snapshot_ids {
+                let _ = self.delete_snapshot(*snapshot_id);
+            }
+            return Err(Error::Io(io::Error::other(format!(
+                "the current files could not be backed up before restoring, {}",
+                pre_restore.files
+            ))));
+        }
+
+        let pre_restore_id = match pre_restore.snapshot_ids.first() {
+            Some(snapshot_id) => *snapshot_id,
+            None => return Err(Error::Io(io::Error::new(io::ErrorKind::Interrupted, "the restore was cancelled"))),
+        };
+
+        let result = (|| -> Result<(), io::Error> {
+            match files {
+                None => self.restore_snapshot_to(snapshot, &save_file_location, &filter)?,
+                Some(files) => {
+                    for file in files {
+                        self.restore_snapshot_file(snapshot, file, &save_file_location)?;
+                    }
+                }
+            }
+
+            if exact {
+                for file in self.fs.list_matching_files(&save_file_location, &filter)? {
+                    if !snapshot_files.contains(&file) {
+                        fs::remove_file(save_file_location.join(&file))?;
+                    }
+                }
+            }
+
+            Ok(())
+        })();
+
+        if let Err(e) = result {
+            let pre_restore = self.snapshot(pre_restore_id)?;
+
+            return match self.roll_back(&pre_restore, &save_file_location, &restored_files) {
+                Ok(()) => Err(Error::Io(e)),
+                Err(rollback_error) => Err(Error::Io(io::Error::new(
+                    e.kind(),
+                    format!(
+                        "{}, rolling back failed as well ({}), the previous files are kept in snapshot {}",
+                        e, rollback_error, pre_restore_id
+                    ),
+                ))),
+            };
+        }
+
+        Ok(pre_restore_id)
+    }
+
+    /// Puts the files of a pre-restore snapshot back after a failed restore and removes the files the
+    /// restore added.
+    ///
+    /// The rollback runs to the end even if the job the restore runs in was cancelled.
+    fn roll_back(
+        &self,
+        pre_restore: &db::Snapshot,
+        save_file_location: &Path,
+        restored_files: &[PathBuf],
+    ) -> Result<(), io::Error> {
+        let fs = Filesystem::new();
+        GameSaves::new(self.db, &fs, self.settings)
+            .restore_snapshot_to(pre_restore, save_file_location, &FileFilter::default())?;
+
+        let previous_files = self.snapshot_files(pre_restore)?;
+        for file in restored_files {
+            let added_file = save_file_location.join(file);
+            if !previous_files.contains(file) && added_file.exists() {
+                fs::remove_file(added_file)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies the files of a snapshot that match a filter into a folder.
+    fn restore_snapshot_to(
+        &self,
+        snapshot: &db::Snapshot,
+        dest_dir: &Path,
+        filter: &FileFilter,
+    ) -> Result<(), io::Error> {
+        let backup_file_location = PathBuf::from(&snapshot.backup_path);
+
+        // Snapshots made before manifests existed don't announce their files, so their progress has no total
+        if let Ok(manifest) = Self::load_manifest(snapshot) {
+            let entries: Vec<&ManifestEntry> = manifest
+                .files
+                .iter()
+                .filter(|entry| filter.matches(Path::new(&entry.path)))
+                .collect();
+            self.fs.add_to_total(entries.len(), entries.iter().map(|entry| entry.size).sum());
+        }
+
+        match snapshot.format {
+            BackupFormat::Directory => match Self::load_manifest(snapshot) {
+                Ok(manifest) => {
+                    for entry in manifest.files.iter().filter(|entry| filter.matches(Path::new(&entry.path))) {
+                        self.fs.begin_file(&entry.path)?;
+                        let dest_file = dest_dir.join(&entry.path);
+                        if let Some(parent) = dest_file.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        fs::copy(self.directory_file_location(snapshot, entry)?, &dest_file)?;
+                        self.fs.finish_file(entry.size);
+                    }
+                }
+                Err(_) => {
+                    let report = self.fs.copy_files(
+                        &Self::directory_files_location(&backup_file_location),
+                        dest_dir,
+                        filter,
+                    )?;
+                    if !report.is_complete() {
+                        return Err(io::Error::other(report.to_string()));
+                    }
+                }
+            },
+            BackupFormat::Deduplicated => {
+                let manifest = Self::load_manifest(snapshot)?;
+                self.fs
+                    .restore_files(&manifest, &self.objects_location(), dest_dir, filter)?;
+            }
+            BackupFormat::TarZst | BackupFormat::Zip | BackupFormat::Encrypted => {
+                let archive = Self::archive_location(&backup_file_location, snapshot.format);
+                self.fs.extract_archive(&archive, snapshot.format, dest_dir, filter)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the paths of all files in a snapshot, relative to the save file location.
+    fn snapshot_files(&self, snapshot: &db::Snapshot) -> Result<Vec<PathBuf>, io::Error> {
+        let backup_file_location = PathBuf::from(&snapshot.backup_path);
+
+        match snapshot.format {
+            BackupFormat::Directory | BackupFormat::Deduplicated => match Self::load_manifest(snapshot) {
+                Ok(manifest) => Ok(manifest
+                    .files
+                    .into_iter()
+                    .map(|entry| PathBuf::from(entry.path))
+                    .collect()),
+                Err(_) => self.fs.list_files(&Self::directory_files_location(&backup_file_location)),
+            },
+            BackupFormat::TarZst | BackupFormat::Zip | BackupFormat::Encrypted => self.fs.list_archive_files(
+                &Self::archive_location(&backup_file_location, snapshot.format),
+                snapshot.format,
+            ),
+        }
+    }
+
+    /// Copies a single file of a snapshot into a folder.
+    fn restore_snapshot_file(
+        &self,
+        snapshot: &db::Snapshot,
+        file: &Path,
+        dest_dir: &Path,
+    ) -> Result<(), io::Error> {
+        let backup_file_location = PathBuf::from(&snapshot.backup_path);
+        let source_file = match snapshot.format {
+            BackupFormat::Directory | BackupFormat::Deduplicated => match Self::load_manifest(snapshot) {
+                Ok(manifest) => {
+                    let entry = manifest
+                        .files
+                        .iter()
+                        .find(|entry| Path::new(&entry.path) == file)
+                        .ok_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::NotFound,
+                                format!("{} is not part of the snapshot", file.display()),
+                            )
+                        })?;
+
+                    if snapshot.format == BackupFormat::Directory {
+                        self.directory_file_location(snapshot, entry)?
+                    } else {
+                        Filesystem::object_path(&self.objects_location(), &entry.sha256)
+                    }
+                }
+                Err(_) => Self::directory_files_location(&backup_file_location).join(file),
+            },
+            BackupFormat::TarZst | BackupFormat::Zip | BackupFormat::Encrypted => {
+                let archive = Self::archive_location(&backup_file_location, snapshot.format);
+                return self.fs.extract_archive_file(&archive, snapshot.format, file, dest_dir);
+            }
+        };
+
+        let dest_file = dest_dir.join(file);
+        if let Some(parent) = dest_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&source_file, &dest_file)?;
+        Ok(())
+    }
+
+    /// Deletes a snapshot from the backup folder and the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot_id` - The ID of the snapshot to delete.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshot cannot be found, its files cannot be deleted
+    /// or handed on to later snapshots, or it cannot be deleted from the database.
+    pub fn delete_snapshot(&self, snapshot_id: i32) -> Result<()> {
+        let snapshot = self.snapshot(snapshot_id)?;
+
+        self.remove_snapshot(&snapshot)?;
+
+        if snapshot.format == BackupFormat::Deduplicated {
+            self.collect_garbage()?;
+        }
+        Ok(())
+    }
+
+    /// Deletes a snapshot's folder and database row, leaving unreferenced objects in the object store.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshot's files cannot be handed on or deleted, or its
+    /// row cannot be deleted. Files handed on before stay where they were copied to.
+    fn remove_snapshot(&self, snapshot: &db::Snapshot) -> Result<()> {
+        let _backups = lock_backups();
+        if snapshot.format == BackupFormat::Directory {
+            self.release_snapshot_files(snapshot)?;
+        }
+
+        let backup_file_location = PathBuf::from(&snapshot.backup_path);
+        if backup_file_location.exists() {
+            fs::remove_dir_all(&backup_file_location)?;
+        }
+
+        self.db.delete_snapshot(snapshot.id)?;
+        Ok(())
+    }
+
+    /// Returns the retention policy that applies to a game: its own policy if it has one, the global
+    /// policy otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the policy cannot be read from the database.
+    pub fn retention_policy(&self, game_id: i32) -> Result<RetentionPolicy> {
+        Ok(self.db.get_retention_policy(game_id)?.unwrap_or(self.settings.retention))
+    }
+
+    /// Deletes the snapshots of a game that its retention policy no longer keeps.
+    ///
+    /// The policy applies to the backups of each save separately. Of the pre-restore snapshots only the
+    /// most recent one of each save is kept, so the last restore can still be undone.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to prune.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshots cannot be read from the database or an
+    /// expired snapshot cannot be deleted. The snapshots deleted before are not restored.
+    ///
+    /// # Returns
+    ///
+    /// Returns how many snapshots were deleted and how much space that freed.
+    pub fn prune_game(&self, game_id: i32) -> Result<PruneReport> {
+        let policy = self.retention_policy(game_id)?;
+        let mut report = PruneReport::default();
+        if policy.keeps_everything() {
+            return Ok(report);
+        }
+
+        let saves = self.db.get_saves_by_game_id(game_id)?;
+
+        let mut deleted_deduplicated = false;
+        for save in saves {
+            let snapshots = self.db.get_snapshots_by_save_id(save.id)?;
+            let usage = self.snapshot_usage(&snapshots);
+
+            let backups: Vec<SnapshotUsage> = snapshots
+                .iter()
+                .zip(&usage)
+                .filter(|(snapshot, _)| snapshot.kind == SnapshotKind::Backup)
+                .map(|(_, usage)| *usage)
+                .collect();
+            let mut expired = policy.expired(&backups);
+
+            // Snapshots are ordered oldest first, so every pre-restore snapshot but the last one expires
+            let mut pre_restore: Vec<i32> = snapshots
+                .iter()
+                .filter(|snapshot| snapshot.kind == SnapshotKind::PreRestore)
+                .map(|snapshot| snapshot.id)
+                .collect();
+            pre_restore.pop();
+            expired.extend(pre_restore);
+
+            // Delete oldest first, so plain copies hand their files on to snapshots that are kept
+            for (snapshot, usage) in snapshots.iter().zip(&usage) {
+                if !expired.contains(&snapshot.id) {
+                    continue;
+                }
+
+                self.remove_snapshot(snapshot)?;
+                deleted_deduplicated |= snapshot.format == BackupFormat::Deduplicated;
+                report.snapshots_deleted += 1;
+                report.bytes_freed += usage.size;
+            }
+        }
+
+        if deleted_deduplicated {
+            self.collect_garbage()?;
+        }
+
+        Ok(report)
+    }
+
+    /// Deletes the snapshots of every game that their retention policies no longer keep.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the games cannot be read from the database or a game
+    /// fails to prune, see [`GameSaves::prune_game`].
+    ///
+    /// # Returns
+    ///
+    /// Returns how many snapshots were deleted and how much space that freed in total.
+    pub fn prune_all_games(&self) -> Result<PruneReport> {
+        let games = self.db.get_all_games()?;
+
+        games.iter().try_fold(PruneReport::default(), |mut total, game| {
+            let report = self.prune_game(game.id)?;
+            total.snapshots_deleted += report.snapshots_deleted;
+            total.bytes_freed += report.bytes_freed;
+            Ok(total)
+        })
+    }
+
+    /// Measures the disk space each snapshot of a save takes up.
+    ///
+    /// Objects of deduplicated snapshots are counted for the most recent snapshot that lists them, as
+    /// they are only freed once no snapshot lists them anymore.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshots` - The snapshots of a save, oldest first.
+    ///
+    /// # Returns
+    ///
+    /// Returns the usage of each snapshot in the same order.
+    fn snapshot_usage(&self, snapshots: &[db::Snapshot]) -> Vec<SnapshotUsage> {
+        let mut counted_objects = HashSet::new();
+
+        let mut usage: Vec<SnapshotUsage> = snapshots
+            .iter()
+            .rev()
+            .map(|snapshot| {
+                let backup_file_location = PathBuf::from(&snapshot.backup_path);
+                let mut size = self.fs.disk_usage(&backup_file_location).unwrap_or(0);
+
+                if snapshot.format == BackupFormat::Deduplicated {
+                    if let Ok(manifest) = Self::load_manifest(snapshot) {
+                        for entry in manifest.files {
+                            if counted_objects.insert(entry.sha256) {
+                                size += entry.size;
+                            }
+                        }
+                    }
+                }
+
+                SnapshotUsage {
+                    snapshot_id: snapshot.id,
+                    created_at: snapshot.created_at,
+                    size,
+                }
+            })
+            .collect();
+
+        usage.reverse();
+        usage
+    }
+
+    /// Moves the files that later incremental snapshots still take from a plain copy snapshot into the
+    /// oldest of those snapshots, so the snapshot can be deleted without breaking them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a file cannot be copied or a manifest cannot be written.
+    fn release_snapshot_files(&self, snapshot: &db::Snapshot) -> Result<()> {
+        let files_location = Self::directory_files_location(&PathBuf::from(&snapshot.backup_path));
+        let snapshots = self.db.get_snapshots_by_save_id(snapshot.save_id)?;
+
+        // The snapshot each moved file ends up in, so later snapshots can point there instead
+        let mut new_locations: HashMap<String, i32> = HashMap::new();
+        for dependent in snapshots {
+            if dependent.id == snapshot.id || dependent.format != BackupFormat::Directory {
+                continue;
+            }
+            let mut manifest = match Self::load_manifest(&dependent) {
+                Ok(manifest) => manifest,
+                Err(_) => continue,
+            };
+
+            let mut changed = false;
+            for entry in manifest.files.iter_mut().filter(|entry| entry.stored_in == Some(snapshot.id)) {
+                match new_locations.get(&entry.path) {
+                    Some(snapshot_id) => entry.stored_in = Some(*snapshot_id),
+                    None => {
+                        let dependent_files = PathBuf::from(&dependent.backup_path).join(DIRECTORY_FILES_NAME);
+                        self.fs.copy_file(&files_location, Path::new(&entry.path), &dependent_files)?;
+                        new_locations.insert(entry.path.clone(), dependent.id);
+                        entry.stored_in = None;
+                    }
+                }
+                changed = true;
+            }
+
+            if changed {
+                manifest.save(&PathBuf::from(&dependent.backup_path).join(MANIFEST_FILE_NAME))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves all backups from one backup root to another and points the snapshots in the database to
+    /// their new folders.
+    ///
+    /// The backup root is renamed if possible. Otherwise, e.g. when the new root is on another drive,
+    /// the backups are copied and the old root is only deleted once every file was copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_root` - The folder the backups are stored in now.
+    /// * `new_root` - The folder to move the backups to. It must not exist or be empty.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the new root isn't empty or lies inside the old root, or
+    /// if any of the backups cannot be copied. The backups stay in the old root in that case.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of snapshots that were moved and the files of the old root that could not be
+    /// deleted after they were copied.
+    pub fn move_backups(&self, old_root: &Path, new_root: &Path) -> Result<MoveReport> {
+        let mut report = MoveReport::default();
+        if !old_root.exists() || old_root == new_root {
+            return Ok(report);
+        }
+        if new_root.starts_with(old_root) {
+            return Err(Error::Validation("the new backup folder can't be inside the current one".to_string()));
+        }
+        if new_root.exists() {
+            if fs::read_dir(new_root)?.next().is_some() {
+                return Err(Error::Validation(format!("{} is not empty", new_root.display())));
+            }
+            fs::remove_dir(new_root)?;
+        }
+        if let Some(parent) = new_root.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let _backups = lock_backups();
+        if fs::rename(old_root, new_root).is_err() {
+            let files = self.fs.list_files(old_root)?;
+            self.fs.add_to_total(files.len(), self.fs.disk_usage(old_root)?);
+
+            match self.fs.copy_files(old_root, new_root, &FileFilter::default()) {
+                Ok(copied) if copied.is_complete() => {}
+                Ok(copied) => {
+                    let _ = self.fs.delete_files(new_root);
+                    return Err(Error::Io(io::Error::other(format!(
+                        "the backups could not be copied, {}",
+                        copied
+                    ))));
+                }
+                Err(e) => {
+                    let _ = self.fs.delete_files(new_root);
+                    return Err(Error::Io(e));
+                }
+            }
+
+            // The backups are complete in the new root, files left behind only take up space
+            report.old_files = self.fs.delete_files(old_root)?;
+        }
+
+        // Snapshots of older versions are stored relative to the working directory, which strip_prefix
+        // handles just the same as long as the old root is given the same way
+        let snapshots = self.db.get_all_snapshots()?;
+        for snapshot in snapshots {
+            if let Ok(relative_path) = Path::new(&snapshot.backup_path).strip_prefix(old_root) {
+                self.db
+                    .update_snapshot_backup_path(snapshot.id, &new_root.join(relative_path).display().to_string())?;
+                report.snapshots_moved += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Exports a game with its saves and all of their snapshots to a single bundle file, which
+    /// [`GameSaves::import_game`] adds to another database.
+    ///
+    /// Encrypted snapshots stay encrypted. The bundle carries the key file of the backup folder, so they
+    /// can be restored with the same passphrase after importing.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` - The ID of the game to export.
+    /// * `bundle_path` - The path of the bundle file to write.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any of the backup files cannot be read, the bundle cannot
+    /// be written or the job it runs in is cancelled. No bundle is left behind in that case.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of exported snapshots.
+    pub fn export_game(&self, game_id: i32, bundle_path: &Path) -> Result<usize> {
+        let result = self.write_bundle(game_id, bundle_path);
+        if result.is_err() {
+            let _ = fs::remove_file(bundle_path);
+        }
+        result
+    }
+
+    fn write_bundle(&self, game_id: i32, bundle_path: &Path) -> Result<usize> {
+        let game = self.game(game_id)?;
+        let saves = self.db.get_saves_by_game_id(game_id)?;
+
+        let mut bundle = GameBundle {
+            version: BUNDLE_VERSION,
+            game,
+            saves: Vec::new(),
+            has_key_file: false,
+            total_files: 0,
+            total_bytes: 0,
+        };
+        // The files to add to the bundle together with their path inside it
+        let mut files: Vec<(PathBuf, String)> = Vec::new();
+        let mut objects = HashSet::new();
+        let mut snapshot_count = 0;
+
+        for save in saves {
+            let platform = self.platform(save.platform_id)?;
+            let location = self.location(save.location_id)?;
+
+            // Saves backed up before snapshots existed only have the files in their backup folder
+            let mut snapshots = self.db.get_snapshots_by_save_id(save.id)?;
+            let legacy = snapshots.is_empty() && self.save_backup_location(&save).is_dir();
+            if legacy {
+                snapshots.push(self.legacy_snapshot(&save));
+            }
+
+            let mut bundled_snapshots = Vec::new();
+            for mut snapshot in snapshots {
+                let backup_file_location = PathBuf::from(&snapshot.backup_path);
+                let folder = format!("{}/{}", BUNDLE_SNAPSHOTS_FOLDER, snapshot_count);
+                snapshot_count += 1;
+
+                for file in self.fs.list_files(&backup_file_location)? {
+                    let path = format!("{}/{}", folder, filesystem::manifest_path(&file));
+                    files.push((backup_file_location.join(&file), path));
+                }
+                if snapshot.format == BackupFormat::Deduplicated {
+                    objects.extend(Self::load_manifest(&snapshot)?.files.into_iter().map(|entry| entry.sha256));
+                }
+                bundle.has_key_file |= snapshot.format == BackupFormat::Encrypted;
+
+                let folder_name = match backup_file_location.file_name() {
+                    Some(name) if !legacy => name.to_string_lossy().into_owned(),
+                    _ => "legacy".to_string(),
+                };
+                snapshot.backup_path = folder;
+                bundled_snapshots.push(BundledSnapshot { snapshot, folder_name });
+            }
+
+            bundle.saves.push(BundledSave { save, platform, location, snapshots: bundled_snapshots });
+        }
+
+        let objects_location = self.objects_location();
+        for sha256 in objects {
+            let object = Filesystem::object_path(&objects_location, &sha256);
+            if let Ok(relative_path) = object.strip_prefix(&objects_location) {
+                let path = format!("{}/{}", BUNDLE_OBJECTS_FOLDER, filesystem::manifest_path(relative_path));
+                files.push((object.clone(), path));
+            }
+        }
+
+        let key_file = self.settings.backup_root().join(crypto::KEY_FILE_NAME);
+        bundle.has_key_file &= key_file.is_file();
+        if bundle.has_key_file {
+            files.push((key_file, crypto::KEY_FILE_NAME.to_string()));
+        }
+
+        bundle.total_files = files.len();
+        for (file, _) in &files {
+            bundle.total_bytes += fs::metadata(file)?.len();
+        }
+        self.fs.add_to_total(bundle.total_files, bundle.total_bytes);
+
+        self.fs.write_bundle(bundle_path, BUNDLE_FILE_NAME, &bundle.to_json()?, &files)?;
+        Ok(snapshot_count)
+    }
+
+    /// Imports a game exported with [`GameSaves::export_game`], adding it with its saves to the
+    /// database with new IDs and its snapshots to the backup folder.
+    ///
+    /// Encrypted snapshots are only imported if the backup folder uses the passphrase they were
+    /// encrypted with, or has no passphrase yet, in which case it takes over the one of the bundle.
+    ///
+    /// # Arguments
+    ///
+    /// * `bundle_path` - The path of the bundle file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the bundle cannot be read, was made by a newer version or
+    /// the job it runs in is cancelled, before anything is added. It also returns an error if the
+    /// snapshots cannot be moved into the backup folder.
+    pub fn import_game(&self, bundle_path: &Path) -> Result<ImportReport> {
+        let description = self.fs.read_bundle_description(bundle_path, BUNDLE_FILE_NAME)?;
+        let bundle = GameBundle::read(description.as_slice())?;
+        self.fs.add_to_total(bundle.total_files, bundle.total_bytes);
+
+        // Unpack next to the backups, so the snapshots only have to be renamed into place
+        let staging = self.settings
+            .backup_root()
+            .join(format!(".import-{}", Local::now().format("%Y-%m-%d_%H-%M-%S%.3f")));
+        let result = self.fs
+            .extract_bundle(bundle_path, BUNDLE_FILE_NAME, &staging)
+            .map_err(Error::Io)
+            .and_then(|()| self.add_bundle(&bundle, &staging));
+
+        let _ = fs::remove_dir_all(&staging);
+        result
+    }
+
+    /// Adds the game of an unpacked bundle to the database and moves its snapshots into the backup folder.
+    ///
+    /// Nothing is added if any of it fails, the objects, key file and snapshot folders moved into the
+    /// backup folder are removed again.
+    fn add_bundle(&self, bundle: &GameBundle, staging: &Path) -> Result<ImportReport> {
+        let _backups = lock_backups();
+        let mut added_files = Vec::new();
+        let mut added_folders = Vec::new();
+
+        let result = self.move_bundle_files(bundle, staging, &mut added_files, &mut added_folders);
+        if result.is_err() {
+            for folder in added_folders.iter().rev() {
+                let _ = fs::remove_dir_all(folder);
+            }
+            for file in &added_files {
+                let _ = fs::remove_file(file);
+            }
+        }
+        result
+    }
+
+    /// Does the work of [`GameSaves::add_bundle`], adding the rows in one transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `added_files` - Gets the objects and key file that were moved into the backup folder.
+    /// * `added_folders` - Gets the save and snapshot folders that were created in the backup folder.
+    fn move_bundle_files(
+        &self,
+        bundle: &GameBundle,
+        staging: &Path,
+        added_files: &mut Vec<PathBuf>,
+        added_folders: &mut Vec<PathBuf>,
+    ) -> Result<ImportReport> {
+        let bundled_objects = staging.join(BUNDLE_OBJECTS_FOLDER);
+        if bundled_objects.is_dir() {
+            let objects_location = self.objects_location();
+            for file in self.fs.list_files(&bundled_objects)? {
+                // Other games' snapshots share the objects, so a bundle must not be able to plant a
+                // file under a hash that doesn't match its contents
+                let sha256 = file.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+                if !manifest::is_sha256(sha256) || file != Filesystem::object_path(Path::new(""), sha256) {
+                    return Err(Error::Validation(format!(
+                        "the bundle contains the object {}, which is not named after a hash",
+                        file.display()
+                    )));
+                }
+                if self.fs.hash_file(&bundled_objects.join(&file))? != sha256 {
+                    return Err(Error::Validation(format!(
+                        "the contents of the bundled object {} don't match its hash",
+                        file.display()
+                    )));
+                }
+
+                let object = objects_location.join(&file);
+                if !object.exists() {
+                    if let Some(parent) = object.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::rename(bundled_objects.join(&file), &object)?;
+                    added_files.push(object);
+                }
+            }
+        }
+
+        // Encrypted snapshots can only be restored with the key file they were encrypted with
+        let key_file = self.settings.backup_root().join(crypto::KEY_FILE_NAME);
+        let bundled_key_file = staging.join(crypto::KEY_FILE_NAME);
+        let keep_encrypted = if !bundle.has_key_file || !bundled_key_file.is_file() {
+            false
+        } else if key_file.exists() {
+            fs::read(&key_file)? == fs::read(&bundled_key_file)?
         } else {
-            println!("Select a game to update:");
-            for game in games {
-                println!("{} - {}", game.id, game.title);
-            }
-    
-            // Get the user's choice
-            print!("> ");
-            io::stdout().flush().unwrap();
-            let mut choice = String::new();
-            io::stdin()
-                .read_line(&mut choice)
-                .expect("Failed to read line");
-    
-            // Convert the user's choice to an integer
-            let game_id = choice.trim().parse::<i32>().expect("Invalid input");
-    
-            // Check if the game exists in the database
-            let existing_game = self.db.get_game(game_id).expect("Failed to get game");
-    
-            // Display the game information to the user
-            println!("Game information:");
-            println!("Title: {}", existing_game.title);
-            println!("Publisher: {}", existing_game.publisher);
-            println!("Release date: {}", existing_game.release_date);
-    
-            // Get the new game information from the user
-            print!("Enter new title (leave empty to keep existing title): ");
-            io::stdout().flush().unwrap();
-            let mut new_title = String::new();
-            io::stdin()
-                .read_line(&mut new_title)
-                .expect("Failed to read line");
-    
-            print!("Enter new publisher (leave empty to keep existing publisher): ");
-            io::stdout().flush().unwrap();
-            let mut new_publisher = String::new();
-            io::stdin()
-                .read_line(&mut new_publisher)
-                .expect("Failed to read line");
-    
-            print!("Enter new release date (leave empty to keep existing release date): ");
-            io::stdout().flush().unwrap();
-            let mut new_release_date = String::new();
-            io::stdin()
-                .read_line(&mut new_release_date)
-                .expect("Failed to read line");
-    
-            // Update the game information in the database
-            let new_title = new_title.trim().to_string();
-            let new_publisher = new_publisher.trim().to_string();
-            let new_release_date = new_release_date.trim().to_string();
-    
-            if !new_title.is_empty() || !new_publisher.is_empty() || !new_release_date.is_empty() {
-                let title = if new_title.is_empty() {
-                    existing_game.title
-                } else {
-                    new_title
-                };
-                let publisher = if new_publisher.is_empty() {
-                    existing_game.publisher
-                } else {
-                    new_publisher
-                };
-                let release_date = if new_release_date.is_empty() {
-                    existing_game.release_date.to_string()
-                } else {
-                    new_release_date
+            fs::copy(&bundled_key_file, &key_file)?;
+            added_files.push(key_file);
+            true
+        };
+
+        self.db.in_transaction(|| -> Result<_> {
+            let game_id = self.db.insert_game(bundle.game.clone())?;
+            let mut report = ImportReport { game_id, title: bundle.game.title.clone(), ..Default::default() };
+            let mut snapshot_ids = HashMap::new();
+            let mut directory_snapshots = Vec::new();
+
+            for bundled_save in &bundle.saves {
+                let location = &bundled_save.location;
+                let platform_id = self.db.insert_platform(&bundled_save.platform.platform_name)?;
+                let location_id = self.db.insert_location(
+                    &location.location_path,
+                    &location.description,
+                    &location.include_patterns,
+                    &location.exclude_patterns,
+                )?;
+                let save_id = self.db.insert_save(
+                    game_id,
+                    location_id,
+                    bundled_save.save.metadata.as_deref().unwrap_or_default(),
+                    platform_id,
+                )?;
+                let save = self.save(save_id)?;
+                let save_backup_location = self.save_backup_location(&save);
+                if !save_backup_location.exists() {
+                    fs::create_dir_all(&save_backup_location)?;
+                    added_folders.push(save_backup_location.clone());
+                }
+
+                for bundled in &bundled_save.snapshots {
+                    let snapshot = &bundled.snapshot;
+                    if snapshot.format == BackupFormat::Encrypted && !keep_encrypted {
+                        report.snapshots_skipped += 1;
+                        continue;
+                    }
+
+                    let backup_file_location =
+                        save_backup_location.join(bundle_relative_path(&bundled.folder_name)?);
+                    let bundled_files = staging.join(bundle_relative_path(&snapshot.backup_path)?);
+                    if bundled_files.is_dir() {
+                        fs::rename(&bundled_files, &backup_file_location)?;
+                    } else {
+                        fs::create_dir_all(&backup_file_location)?;
+                    }
+                    added_folders.push(backup_file_location.clone());
+
+                    let snapshot_id = self.db.insert_snapshot(
+                        save_id,
+                        snapshot.created_at,
+                        &backup_file_location.display().to_string(),
+                        snapshot.format,
+                        snapshot.kind,
+                    )?;
+                    snapshot_ids.insert(snapshot.id, snapshot_id);
+                    if snapshot.format == BackupFormat::Directory {
+                        directory_snapshots.push(backup_file_location);
+                    }
+                    report.snapshots_imported += 1;
+                }
+            }
+
+            // Unchanged files of incremental snapshots point at the snapshot holding them by its ID
+            for backup_file_location in directory_snapshots {
+                let manifest_path = backup_file_location.join(MANIFEST_FILE_NAME);
+                if let Ok(mut manifest) = Manifest::load(&manifest_path) {
+                    if manifest.files.iter().any(|entry| entry.stored_in.is_some()) {
+                        for entry in &mut manifest.files {
+                            entry.stored_in = entry.stored_in.and_then(|id| snapshot_ids.get(&id).copied());
+                        }
+                        manifest.save(&manifest_path)?;
+                    }
+                }
+            }
+
+            Ok(report)
+        })
+    }
+
+    /// Exports every row of the database to a JSON document, which [`GameSaves::import_library`] reads
+    /// back. The backup files are not part of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `library_path` - The path of the JSON file to write.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be written.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of exported games.
+    pub fn export_library(&self, library_path: &Path) -> Result<usize> {
+        let library = self.db.export_library()?;
+        fs::write(library_path, library.to_json()?)?;
+
+        Ok(library.games.len())
+    }
+
+    /// Imports a library exported by [`GameSaves::export_library`].
+    ///
+    /// Merging adds the saves of the library to the games with the same title, or to new games. A save
+    /// of a game that already has a save on the same platform is a conflict and is left out with its
+    /// snapshots. The folders of the merged snapshots are copied to the backup folders of their new
+    /// saves, and the copies of incremental plain copies refer to the new snapshot IDs. The folders the
+    /// library points at are left as they are. Replacing deletes every game first and keeps the IDs of
+    /// the library, so new backups go to the same folders as before.
+    ///
+    /// # Arguments
+    ///
+    /// * `library_path` - The path of the JSON file to read.
+    /// * `mode` - Whether to merge the library into the database or replace the database with it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be read, its rows don't fit together or the
+    /// snapshot folders cannot be copied. The database and backup folder are left as they were in that
+    /// case.
+    pub fn import_library(&self, library_path: &Path, mode: ImportMode) -> Result<LibraryImportReport> {
+        let library = Library::read(fs::File::open(library_path)?)?;
+
+        match mode {
+            ImportMode::Replace => {
+                self.db.replace_library(&library)?;
+                Ok(LibraryImportReport {
+                    games_added: library.games.len(),
+                    saves_added: library.saves.len(),
+                    snapshots_added: library.snapshots.len(),
+                    conflicts: Vec::new(),
+                })
+            }
+            ImportMode::Merge => {
+                let _backups = lock_backups();
+                let mut copied_folders = Vec::new();
+                let merged = self.db.in_transaction(|| -> Result<_> {
+                    let (report, snapshot_ids, copies) = self.merge_library(&library)?;
+                    self.copy_merged_snapshots(&copies, &snapshot_ids, &mut copied_folders)?;
+                    Ok(report)
+                });
+
+                if merged.is_err() {
+                    for folder in &copied_folders {
+                        let _ = fs::remove_dir_all(folder);
+                    }
+                }
+                merged
+            }
+        }
+    }
+
+    /// Inserts the rows of a library that don't conflict with the database with new IDs.
+    ///
+    /// The added snapshots point at folders in the backup folders of their new saves, which
+    /// [`GameSaves::copy_merged_snapshots`] copies the snapshot folders of the library to.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a row cannot be inserted or refers to a row the library
+    /// doesn't contain.
+    ///
+    /// # Returns
+    ///
+    /// Returns what was added, the new IDs of the added snapshots by their IDs in the library and the
+    /// snapshot folders to copy.
+    fn merge_library(&self, library: &Library) -> Result<(LibraryImportReport, HashMap<i32, i32>, Vec<SnapshotCopy>)> {
+        let mut report = LibraryImportReport::default();
+
+        // Titles and platform names are compared the way users see them, without case
+        let platform_names: HashMap<i32, String> = self.db
+            .get_all_platforms()?
+            .into_iter()
+            .map(|platform| (platform.id, platform.platform_name.to_lowercase()))
+            .collect();
+        let existing_games = self.db.get_all_games()?;
+        let mut games_by_title: HashMap<String, i32> = existing_games
+            .iter()
+            .map(|game| (game.title.to_lowercase(), game.id))
+            .collect();
+        let mut saved_on: HashSet<(i32, String)> = self.db
+            .get_all_save_records()?
+            .into_iter()
+            .filter_map(|save| Some((save.game_id, platform_names.get(&save.platform_id)?.clone())))
+            .collect();
+
+        let mut game_ids = HashMap::new();
+        let mut added_games = HashSet::new();
+        for game in &library.games {
+            let game_id = match games_by_title.get(&game.title.to_lowercase()) {
+                Some(game_id) => *game_id,
+                None => {
+                    let game_id = self.db.insert_game(game.clone())?;
+                    games_by_title.insert(game.title.to_lowercase(), game_id);
+                    added_games.insert(game_id);
+                    report.games_added += 1;
+                    game_id
+                }
+            };
+            game_ids.insert(game.id, game_id);
+        }
+
+        let mut location_ids = HashMap::new();
+        let mut saves = HashMap::new();
+        for save in &library.saves {
+            let missing = |table: &str, id: i32| {
+                Error::Validation(format!("save {} refers to {} {}, which is not in the library", save.id, table, id))
+            };
+            let game = library
+                .games
+                .iter()
+                .find(|game| game.id == save.game_id)
+                .ok_or_else(|| missing("game", save.game_id))?;
+            let platform = library
+                .platforms
+                .iter()
+                .find(|platform| platform.id == save.platform_id)
+                .ok_or_else(|| missing("platform", save.platform_id))?;
+            let location = library
+                .locations
+                .iter()
+                .find(|location| location.id == save.location_id)
+                .ok_or_else(|| missing("location", save.location_id))?;
+
+            let game_id = *game_ids.get(&game.id).ok_or_else(|| missing("game", game.id))?;
+            if !saved_on.insert((game_id, platform.platform_name.to_lowercase())) {
+                report.conflicts.push(LibraryConflict {
+                    title: game.title.clone(),
+                    platform_name: platform.platform_name.clone(),
+                });
+                continue;
+            }
+
+            let platform_id = self.db.insert_platform(&platform.platform_name)?;
+            let location_id = match location_ids.get(&location.id) {
+                Some(location_id) => *location_id,
+                None => {
+                    let location_id = self.db.insert_location(
+                        &location.location_path,
+                        &location.description,
+                        &location.include_patterns,
+                        &location.exclude_patterns,
+                    )?;
+                    location_ids.insert(location.id, location_id);
+                    location_id
+                }
+            };
+            let save_id = self.db.insert_save(
+                game_id,
+                location_id,
+                save.metadata.as_deref().unwrap_or_default(),
+                platform_id,
+            )?;
+            saves.insert(save.id, self.save(save_id)?);
+            report.saves_added += 1;
+        }
+
+        let mut snapshot_ids = HashMap::new();
+        let mut copies = Vec::new();
+        for snapshot in &library.snapshots {
+            if let Some(save) = saves.get(&snapshot.save_id) {
+                let source = PathBuf::from(&snapshot.backup_path);
+                let folder_name = match source.file_name() {
+                    Some(name) => name.to_string_lossy().into_owned(),
+                    None => format!("snapshot-{}", snapshot.id),
                 };
-    
-                self.db.update_game(game_id, &title, &publisher, &release_date)
-                    .expect("Failed to update game");
-                println!("Game information updated");
-            } else {
-                println!("No changes made to game information");
+                let destination = self.save_backup_location(save).join(folder_name);
+
+                let snapshot_id = self.db.insert_snapshot(
+                    save.id,
+                    snapshot.created_at,
+                    &destination.display().to_string(),
+                    snapshot.format,
+                    snapshot.kind,
+                )?;
+                snapshot_ids.insert(snapshot.id, snapshot_id);
+                copies.push(SnapshotCopy { source, destination, format: snapshot.format });
+                report.snapshots_added += 1;
+            }
+        }
+
+        // Games that were already in the database keep their own policies and schedules
+        for retention_policy in &library.retention_policies {
+            match game_ids.get(&retention_policy.game_id) {
+                Some(game_id) if added_games.contains(game_id) => {
+                    self.db.set_retention_policy(*game_id, &retention_policy.policy)?;
+                }
+                _ => {}
+            }
+        }
+        let mut schedules = self.db.get_all_schedules()?;
+        for schedule in &library.schedules {
+            let mut schedule = schedule.clone();
+            schedule.game_ids = schedule.game_ids.iter().filter_map(|game_id| game_ids.get(game_id).copied()).collect();
+            schedule.game_ids.sort();
+            schedule.game_ids.dedup();
+
+            let exists = schedules.iter().any(|existing| {
+                existing.all_games == schedule.all_games
+                    && existing.timing == schedule.timing
+                    && (existing.all_games || existing.game_ids == schedule.game_ids)
+            });
+            if !exists {
+                schedule.id = self.db.insert_schedule(&schedule)?;
+                schedules.push(schedule);
             }
         }
+
+        Ok((report, snapshot_ids, copies))
     }
-    
-    /// Adds a new game save to the database and creates a backup of the save files in the backup folder.
+
+    /// Copies the snapshot folders of a merged library to the folders of the snapshots that were added
+    /// for them, so the new snapshots don't share their files with the ones the library came from.
+    ///
+    /// Snapshot folders that don't exist, e.g. of a library exported on another computer, are skipped.
     ///
     /// # Arguments
     ///
-    /// * `db` - A reference to a `db::Db` instance.
-    /// * `fs` - A reference to a `filesystem::Filesystem` instance.
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if any of the following operations fail:
-    ///
-    /// * Failed to read user input
-    /// * Failed to insert game information into the database
-    /// * Failed to insert platform information into the database
-    /// * Failed to insert location information into the database
-    /// * Failed to insert save information into the database
-    /// * Failed to copy save files to backup folder
-    pub fn add_game_save(&self, game:db::Game, path: String, platform: String) {
-
-        let game_id = self.db.insert_game(game)
-        .expect("Failed to insert game");
-    
-        // Copy the save files to the backup folder
-    let platform_id = self.db
-        .insert_platform(platform.trim())
-        .expect("Failed to insert platform");
-    let location_id = self.db
-        .insert_location(path.trim(), "")
-        .expect("Failed to insert location");
-    let save_id = self.db
-        .insert_save(game_id, location_id, "", platform_id)
-        .expect("Failed to insert save");
-        let backup_file_location =
-            PathBuf::from(&format!("backups/{}/{}/{}/", game_id, platform_id, save_id));
-    
-        let save_file_location = PathBuf::from(path);
-        self.fs.copy_files(&save_file_location, &backup_file_location)
-            .expect("Failed to copy files");
-    }
-    
-    /// Prompts the user to restore a game save.
-    ///
-    /// Restores the game save data from the database and displays it to the user. Asks the user if they want to restore the game save and if so, copies the save files from the backup folder to the original save file location. If the user wants to restore only select files, prompts the user to confirm each file copy operation.
+    /// * `copies` - The snapshot folders to copy.
+    /// * `snapshot_ids` - The new IDs of the added snapshots by their IDs in the library.
+    /// * `copied_folders` - Gets the folders that were created, so they can be removed if the import fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a file cannot be copied or a manifest cannot be updated.
+    fn copy_merged_snapshots(
+        &self,
+        copies: &[SnapshotCopy],
+        snapshot_ids: &HashMap<i32, i32>,
+        copied_folders: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        for copy in copies.iter().filter(|copy| copy.source.is_dir()) {
+            if copy.destination.exists() {
+                return Err(Error::Validation(format!("{} already exists", copy.destination.display())));
+            }
+            copied_folders.push(copy.destination.clone());
+
+            let copied = self.fs.copy_files(&copy.source, &copy.destination, &FileFilter::default())?;
+            if !copied.is_complete() {
+                return Err(Error::Io(io::Error::other(format!(
+                    "the snapshot files could not be copied, {}",
+                    copied
+                ))));
+            }
+
+            // Unchanged files of incremental snapshots point at the snapshot holding them by its ID
+            let manifest_path = copy.destination.join(MANIFEST_FILE_NAME);
+            if copy.format == BackupFormat::Directory && manifest_path.is_file() {
+                let mut manifest = Manifest::load(&manifest_path)?;
+                if manifest.files.iter().any(|entry| entry.stored_in.is_some()) {
+                    for entry in &mut manifest.files {
+                        entry.stored_in = entry.stored_in.and_then(|id| snapshot_ids.get(&id).copied());
+                    }
+                    manifest.save(&manifest_path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the games and save file locations listed in a CSV file, see [`read_csv_games`] for the
+    /// columns it needs.
+    ///
+    /// Rows of a game that is already in the database add a save to it. Rows that cannot be read, point
+    /// at a save path that is not a folder, or list a game already saved on the same platform are
+    /// skipped and reported. A save whose first backup fails stays added, the failure is listed in the
+    /// backup report.
     ///
     /// # Arguments
     ///
-    /// * `db` - A reference to a `Db` instance to restore data from the local_games database.
-    /// * `fs` - A reference to a `Filesystem` instance to handle file I/O operations.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let db = db::Db::new("local_games.db").unwrap();
-    /// let fs = filesystem::Filesystem::new();
-    /// restore_game_save(&db, &fs);
-    /// ```
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the backup file copy operation fails due to a file I/O error.
-    pub fn restore_game_save(&self) {
-        // Get the game title from the user
-        print!("Enter the game title: ");
-        io::stdout().flush().unwrap();
-        let mut title = String::new();
-        io::stdin()
-            .read_line(&mut title)
-            .expect("Failed to read line");
-    
-        // Restore games from the database and display them to the user
-        let games = self.db
-            .get_games_by_title(title.trim())
-            .expect("Failed to get games");
-        if games.is_empty() {
-            println!("No games found with that title");
-        } else {
-            println!("Select a game to restore:");
-            for game in games {
-                println!("{} - {}", game.id, game.title);
-            }
-    
-            // Get the user's choice
-            print!("> ");
-            io::stdout().flush().unwrap();
-            let mut choice = String::new();
-            io::stdin()
-                .read_line(&mut choice)
-                .expect("Failed to read line");
-    
-            // Convert the user's choice to an integer
-            let game_id = choice.trim().parse::<i32>().expect("Invalid input");
-    
-            // Check if the game exists in the database
-            let game = self.db.get_game(game_id).expect("Failed to get game");
-    
-            // Display the game information to the user
-            println!("Game information:");
-            println!("Title: {}", game.title);
-            println!("Publisher: {}", game.publisher);
-            println!("Release date: {}", game.release_date);
-    
-            let saves = self.db
-                .get_saves_by_game_id(game.id)
-                .expect("Failed to retrieve save from database");
-    
-            // Display the game save data to the user
-            for save in saves.iter() {
-                let location = self.db
-                    .get_location(save.location_id)
-                    .expect("Failed to retrieve location from database");
-                let platform = self.db
-                    .get_platform(save.platform_id)
-                    .expect("Failed to retrieve platform from database");
-    
-                // Display the game save data
-                println!("Game title: {}", game.title);
-                println!("Publisher: {}", game.publisher);
-                println!("Release date: {}", game.release_date);
-                println!("Platform: {}", platform.platform_name);
-                println!("Save file location: {}", location.location_path);
-    
-                // Ask the user if they want to restore the game save
-                print!("Do you want to restore this game save? (Y/n/a): ");
-                io::stdout().flush().unwrap();
-                let mut restore = String::new();
-                io::stdin()
-                    .read_line(&mut restore)
-                    .expect("Failed to read line");
-    
-                // If the user wants to restore the game save, copy the save file to the correct location
-                // default option y
-                if restore.trim() == "Y" || restore.trim() == "" {
-                    println!("Restoring game save...");
-    
-                    // Copy the save files from the backup folder to the save file location one by one and ask per file
-                    let backup_file_location = PathBuf::from(&format!(
-                        "backups/{}/{}/{}/",
-                        game.id, save.platform_id, save.id
-                    ));
-    
-                    for entry in fs::read_dir(&backup_file_location).expect("Failed to read directory")
-                    {
-                        let entry = entry.expect("Failed to read directory entry");
-                        let file_name = entry.file_name();
-                        let file_path = entry.path();
-    
-                        // Ask the user whether to copy the file or not
-                        print!("Copy file {:?}? (Y/n): ", file_name);
-                        io::stdout().flush().unwrap();
-                        let mut answer = String::new();
-                        io::stdin()
-                            .read_line(&mut answer)
-                            .expect("Failed to read answer");
-    
-                        if answer.trim().to_lowercase() == "y" || restore.trim() == "" {
-                            // Copy the file to the save file location
-                            let dest_file = Path::new(&location.location_path).join(file_name);
-                            fs::copy(&file_path, &dest_file).expect("Failed to copy file");
+    /// * `csv_path` - The path of the CSV file.
+    /// * `back_up` - Whether to take the first backup of every added save.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be read or is missing a required column.
+    pub fn import_csv(&self, csv_path: &Path, back_up: bool) -> Result<CsvImportReport> {
+        let rows = read_csv_games(fs::File::open(csv_path)?)?;
+        let mut report = CsvImportReport::default();
+
+        // Titles and platform names are compared the way users see them, without case
+        let platform_names: HashMap<i32, String> = self.db
+            .get_all_platforms()?
+            .into_iter()
+            .map(|platform| (platform.id, platform.platform_name.to_lowercase()))
+            .collect();
+        let mut games_by_title: HashMap<String, i32> = self.db
+            .get_all_games()?
+            .into_iter()
+            .map(|game| (game.title.to_lowercase(), game.id))
+            .collect();
+        let mut saved_on: HashSet<(i32, String)> = self.db
+            .get_all_save_records()?
+            .into_iter()
+            .filter_map(|save| Some((save.game_id, platform_names.get(&save.platform_id)?.clone())))
+            .collect();
+
+        let mut save_ids = Vec::new();
+        for row in rows {
+            let csv_game = match row {
+                Ok(csv_game) => csv_game,
+                Err(error) => {
+                    report.errors.push(error);
+                    continue;
+                }
+            };
+            let error = |message: String| CsvRowError {
+                line: csv_game.line,
+                title: csv_game.game.title.clone(),
+                message,
+            };
+
+            if !Path::new(&csv_game.save_path).is_dir() {
+                report.errors.push(error(format!("the save path {} is not a folder", csv_game.save_path)));
+                continue;
+            }
+            let existing_game_id = games_by_title.get(&csv_game.game.title.to_lowercase()).copied();
+            let platform_name = csv_game.platform.to_lowercase();
+            if existing_game_id.is_some_and(|game_id| saved_on.contains(&(game_id, platform_name.clone()))) {
+                report.errors.push(error(format!("it is already in the library on {}", csv_game.platform)));
+                continue;
+            }
+
+            let inserted = self.db.in_transaction(|| -> Result<_, rusqlite::Error> {
+                let game_id = match existing_game_id {
+                    Some(game_id) => game_id,
+                    None => self.db.insert_game(csv_game.game.clone())?,
+                };
+                let platform_id = self.db.insert_platform(&csv_game.platform)?;
+                let location_id = self.db.insert_location(&csv_game.save_path, "", &[], &[])?;
+                let save_id = self.db.insert_save(game_id, location_id, "", platform_id)?;
+                Ok((game_id, save_id))
+            });
+            match inserted {
+                Ok((game_id, save_id)) => {
+                    if existing_game_id.is_none() {
+                        games_by_title.insert(csv_game.game.title.to_lowercase(), game_id);
+                        report.games_added += 1;
+                    }
+                    saved_on.insert((game_id, platform_name));
+                    save_ids.push((save_id, csv_game.game.title.clone()));
+                    report.saves_added += 1;
+                }
+                Err(e) => report.errors.push(error(e.to_string())),
+            }
+        }
+
+        if back_up {
+            for (save_id, title) in save_ids {
+                if self.fs.is_cancelled() {
+                    break;
+                }
+                match self.save(save_id).and_then(|save| self.create_snapshot(&save)) {
+                    Ok(backup) => report.backup.merge(backup),
+                    Err(e) => report.backup.record_game_failure(&title, &e),
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Deletes every object in the object store that is no longer listed in any snapshot manifest.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshots cannot be read from the database or the
+    /// object store cannot be read.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of deleted objects.
+    pub fn collect_garbage(&self) -> Result<usize> {
+        Ok(self.delete_unreferenced_objects()?.succeeded)
+    }
+
+    /// Deletes the objects no snapshot manifest lists, like [`GameSaves::collect_garbage`].
+    ///
+    /// The manifests of the kept backups of removed games count as well, so their files stay available.
+    /// If any manifest cannot be read, no object is deleted and the manifest is listed in the report.
+    /// Backups taken at the same time wait until the objects were deleted, so their objects are never
+    /// taken for unreferenced ones.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshots cannot be read from the database or the
+    /// object store cannot be read.
+    ///
+    /// # Returns
+    ///
+    /// Returns a report of the deleted objects and their combined size.
+    fn delete_unreferenced_objects(&self) -> Result<FileReport> {
+        let _backups = lock_backups();
+        let snapshots = self.db.get_snapshots_by_format(BackupFormat::Deduplicated)?;
+        let mut manifest_paths: Vec<PathBuf> = snapshots
+            .iter()
+            .map(|snapshot| PathBuf::from(&snapshot.backup_path).join(MANIFEST_FILE_NAME))
+            .collect();
+
+        let removed_location = self.settings.backup_root().join(REMOVED_FOLDER_NAME);
+        if removed_location.is_dir() {
+            let files = self.fs.list_files(&removed_location)?;
+            manifest_paths.extend(
+                files
+                    .into_iter()
+                    .filter(|file| file.file_name().is_some_and(|name| name == MANIFEST_FILE_NAME))
+                    .map(|file| removed_location.join(file)),
+            );
+        }
+
+        let mut report = FileReport::default();
+        let mut referenced = HashSet::new();
+        for manifest_path in manifest_paths {
+            match Manifest::load(&manifest_path) {
+                Ok(manifest) => {
+                    referenced.extend(manifest.files.into_iter().map(|entry| entry.sha256));
+                }
+                // Kept encrypted snapshots store their files in their archive, not as objects
+                Err(_) if fs::read(&manifest_path).is_ok_and(|data| crypto::is_encrypted(&data)) => {}
+                Err(e) => {
+                    // Without the manifest we can't tell which objects are still needed, keep them all
+                    report.record_failure(manifest_path, &e.into());
+                    return Ok(report);
+                }
+            }
+        }
+
+        let objects_location = self.objects_location();
+        if !objects_location.exists() {
+            return Ok(report);
+        }
+
+        for object in self.fs.list_files(&objects_location)? {
+            let name = object.file_name().unwrap_or_default().to_string_lossy().to_string();
+            if !referenced.contains(&name) {
+                let path = objects_location.join(&object);
+                let size = fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or_default();
+                match fs::remove_file(&path) {
+                    Ok(()) => report.record_success(size),
+                    Err(e) => report.record_failure(path, &e),
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Deletes the objects no snapshot lists anymore after snapshots were removed. A failed cleanup is
+    /// recorded in the report of the removal, which already happened, instead of failing it.
+    fn clean_up_objects(&self, report: &mut FileReport) {
+        match self.delete_unreferenced_objects() {
+            Ok(objects) => report.merge(objects),
+            Err(e) => report.record_failure(self.objects_location(), &e.into()),
+        }
+    }
+
+    /// Verifies the snapshots of every game in the database.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the games or their snapshots cannot be read from the
+    /// database. Damaged backups are listed in the reports instead.
+    ///
+    /// # Returns
+    ///
+    /// Returns one report per game.
+    pub fn verify_all_games(&self) -> Result<Vec<VerifyReport>> {
+        let games = self.db.get_all_games()?;
+
+        // Objects are shared between games, so only hash each of them once
+        let mut verified_objects = HashMap::new();
+        games
+            .iter()
+            .map(|game| self.verify_game_with_cache(game, &mut verified_objects))
+            .collect()
+    }
+
+    /// Re-hashes the stored files of every snapshot of a game and compares them with the snapshot
+    /// manifests, skipping the objects in `verified_objects` that were already read for another game.
+    fn verify_game_with_cache(
+        &self,
+        game: &Game,
+        verified_objects: &mut HashMap<String, Option<(u64, String)>>,
+    ) -> Result<VerifyReport> {
+        Ok(VerifyReport {
+            title: game.title.clone(),
+            snapshots: self
+                .list_snapshots(game.id)?
+                .iter()
+                .map(|snapshot| self.verify_snapshot(snapshot, verified_objects))
+                .collect(),
+        })
+    }
+
+    /// Compares the stored files of a snapshot with its manifest.
+    ///
+    /// `verified_objects` remembers the size and actual hash of every object of the object store that
+    /// was already read, or `None` if it is missing.
+    fn verify_snapshot(
+        &self,
+        snapshot: &db::Snapshot,
+        verified_objects: &mut HashMap<String, Option<(u64, String)>>,
+    ) -> SnapshotCheck {
+        let mut check = SnapshotCheck {
+            snapshot_id: snapshot.id,
+            backup_path: snapshot.backup_path.clone(),
+            ..Default::default()
+        };
+
+        let backup_file_location = PathBuf::from(&snapshot.backup_path);
+        let expected = match Self::load_manifest(snapshot) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                check.error = Some(format!("Failed to read manifest: {}", e));
+                return check;
+            }
+        };
+
+        let stored = match snapshot.format {
+            BackupFormat::Directory => {
+                let mut stored = Manifest::default();
+                for entry in &expected.files {
+                    // A file whose snapshot is gone is missing like a deleted one
+                    let file = match self.directory_file_location(snapshot, entry) {
+                        Ok(file) => file,
+                        Err(_) => continue,
+                    };
+                    if let (Ok(metadata), Ok(sha256)) = (fs::metadata(&file), self.fs.hash_file(&file)) {
+                        stored.files.push(ManifestEntry {
+                            size: metadata.len(),
+                            sha256,
+                            ..entry.clone()
+                        });
+                    }
+                }
+
+                // Files copied into this snapshot that the manifest doesn't list
+                let files_location = Self::directory_files_location(&backup_file_location);
+                self.fs.list_files(&files_location).map(|files| {
+                    for file in files {
+                        let path = filesystem::manifest_path(&file);
+                        let listed = expected.get(&path).is_some_and(|entry| entry.stored_in.is_none());
+                        if !listed {
+                            check.extra.push(path);
                         }
                     }
+                    stored
+                })
+            }
+            BackupFormat::TarZst | BackupFormat::Zip | BackupFormat::Encrypted => self.fs.build_archive_manifest(
+                &Self::archive_location(&backup_file_location, snapshot.format),
+                snapshot.format,
+            ),
+            BackupFormat::Deduplicated => {
+                let objects_location = self.objects_location();
+                let mut stored = Manifest::default();
+                for entry in &expected.files {
+                    // Objects are named after their hash, so an intact object still hashes to its name
+                    let object = verified_objects.entry(entry.sha256.clone()).or_insert_with(|| {
+                        let object = Filesystem::object_path(&objects_location, &entry.sha256);
+                        let size = fs::metadata(&object).ok()?.len();
+                        let sha256 = self.fs.hash_file(&object).ok()?;
+                        Some((size, sha256))
+                    });
+
+                    if let Some((size, sha256)) = object {
+                        stored.files.push(ManifestEntry {
+                            size: *size,
+                            sha256: sha256.clone(),
+                            ..entry.clone()
+                        });
+                    }
                 }
-                // if the user wants to restore all game saves, copy the save file to the correct location
-                else if restore.trim() == "a" {
-                    println!("Restoring all game saves...");
-    
-                    // Copy the save files from the backup folder to the save file location
-                    let save_file_location = PathBuf::from(&location.location_path);
-                    let backup_file_location = PathBuf::from(&format!(
-                        "backups/{}/{}/{}/",
-                        game.id, save.platform_id, save.id
-                    ));
-    
-                    self.fs.copy_files(&backup_file_location, &save_file_location)
-                        .expect("Failed to copy files");
+                Ok(stored)
+            }
+        };
+
+        let stored = match stored {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                check.error = Some(format!("Failed to read backup: {}", e));
+                return check;
+            }
+        };
+
+        for entry in &expected.files {
+            match stored.get(&entry.path) {
+                None => check.missing.push(entry.path.clone()),
+                Some(stored_entry) => {
+                    if stored_entry.size != entry.size || stored_entry.sha256 != entry.sha256 {
+                        check.corrupted.push(entry.path.clone());
+                    }
                 }
             }
         }
+        for entry in &stored.files {
+            if expected.get(&entry.path).is_none() {
+                check.extra.push(entry.path.clone());
+            }
+        }
+
+        check
+    }
+
+    /// Returns the most recent backup snapshot of a save, skipping pre-restore snapshots, or `None` if
+    /// it has none.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshots cannot be read from the database.
+    fn latest_snapshot(&self, save: &db::Save) -> Result<Option<db::Snapshot>> {
+        let snapshots = self.db.get_snapshots_by_save_id(save.id)?;
+
+        Ok(snapshots
+            .into_iter()
+            .rev()
+            .find(|snapshot| snapshot.kind == SnapshotKind::Backup))
+    }
+
+    /// Returns the snapshot a save is restored from: its most recent backup snapshot, or the backup it
+    /// got before snapshots existed if it has none, see [`GameSaves::legacy_snapshot`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshots cannot be read from the database.
+    fn restore_source(&self, save: &db::Save) -> Result<db::Snapshot> {
+        Ok(self.latest_snapshot(save)?.unwrap_or_else(|| self.legacy_snapshot(save)))
+    }
+
+    /// Describes the backup of a save that was made before snapshots existed, which keeps its files
+    /// directly in the save's backup folder.
+    ///
+    /// The returned snapshot is not stored in the database, so its ID doesn't refer to any row and
+    /// must not be looked up.
+    fn legacy_snapshot(&self, save: &db::Save) -> db::Snapshot {
+        db::Snapshot {
+            id: 0,
+            save_id: save.id,
+            created_at: 0,
+            backup_path: self.save_backup_location(save).display().to_string(),
+            format: BackupFormat::Directory,
+            kind: SnapshotKind::Backup,
+        }
+    }
+}
+
+/// Checks a relative path read from a bundle, refusing paths that would point outside of the folder the
+/// bundle is unpacked to.
+fn bundle_relative_path(path: &str) -> Result<PathBuf, io::Error> {
+    let path = PathBuf::from(path);
+    let is_relative = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+
+    if path.as_os_str().is_empty() || !is_relative {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the bundle contains an invalid path '{}'", path.display()),
+        ));
     }
+    Ok(path)
+}
+
+/// Checks that the include and exclude patterns of a save file location are valid glob patterns.
+fn check_patterns(include_patterns: &[String], exclude_patterns: &[String]) -> Result<()> {
+    FileFilter::new(include_patterns, exclude_patterns)
+        .map(|_| ())
+        .map_err(|e| Error::Validation(format!("the file pattern is not valid: {}", e)))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::tests::{write_file, TestDir};
+
+    /// Returns a database with its tables and settings that keep the backups in a test folder.
+    fn test_library(dir: &Path, backup_format: BackupFormat, incremental: bool) -> (Db, Settings) {
+        let db = Db::new(":memory:").unwrap();
+        db.create_tables().unwrap();
+        let settings = Settings {
+            backup_format,
+            incremental,
+            backup_root: Some(dir.join("backups")),
+            ..Default::default()
+        };
+        (db, settings)
+    }
+
+    /// Adds a game with the save file location `saves` and returns its save, which has one snapshot.
+    fn add_game(game_save: &GameSaves, saves: &Path) -> db::Save {
+        let game = Game { title: "Game".to_string(), ..Default::default() };
+        game_save
+            .add_game_save(game, saves.display().to_string(), "PC".to_string(), Vec::new(), Vec::new())
+            .unwrap();
+        let game_id = game_save.db.get_all_games().unwrap()[0].id;
+        game_save.db.get_saves_by_game_id(game_id).unwrap().remove(0)
+    }
+
+    fn read(dir: &Path, relative_path: &str) -> String {
+        fs::read_to_string(dir.join(relative_path)).unwrap()
+    }
+
+    #[test]
+    fn test_incremental_snapshots_only_copy_changed_files() {
+        let dir = TestDir::new("incremental_snapshots");
+        let saves = dir.join("saves");
+        write_file(&saves, "unchanged.sav", "unchanged");
+        write_file(&saves, "modified.sav", "before");
+        write_file(&saves, "deleted.sav", "deleted");
+        let (db, settings) = test_library(&dir, BackupFormat::Directory, true);
+        let fs = Filesystem::new();
+        let game_save = GameSaves::new(&db, &fs, &settings);
+        let save = add_game(&game_save, &saves);
+        let first_id = db.get_snapshots_by_save_id(save.id).unwrap()[0].id;
+
+        write_file(&saves, "modified.sav", "after, with a different size");
+        fs::remove_file(saves.join("deleted.sav")).unwrap();
+        let second_id = game_save.create_snapshot(&save).unwrap().snapshot_ids[0];
+
+        let second = game_save.snapshot(second_id).unwrap();
+        let manifest = GameSaves::load_manifest(&second).unwrap();
+        assert_eq!(manifest.get("unchanged.sav").unwrap().stored_in, Some(first_id));
+        assert_eq!(manifest.get("modified.sav").unwrap().stored_in, None);
+        assert!(manifest.get("deleted.sav").is_none());
+        assert_eq!(manifest.deleted, ["deleted.sav"]);
+
+        let second_files = PathBuf::from(&second.backup_path).join(DIRECTORY_FILES_NAME);
+        assert_eq!(fs.list_files(&second_files).unwrap(), [PathBuf::from("modified.sav")]);
+    }
+
+    #[test]
+    fn test_restore_reads_unchanged_files_from_earlier_snapshot() {
+        let dir = TestDir::new("restore_incremental");
+        let saves = dir.join("saves");
+        write_file(&saves, "unchanged.sav", "unchanged");
+        write_file(&saves, "modified.sav", "before");
+        let (db, settings) = test_library(&dir, BackupFormat::Directory, true);
+        let fs = Filesystem::new();
+        let game_save = GameSaves::new(&db, &fs, &settings);
+        let save = add_game(&game_save, &saves);
+        write_file(&saves, "modified.sav", "after, with a different size");
+        game_save.create_snapshot(&save).unwrap();
+
+        write_file(&saves, "unchanged.sav", "changed after the last backup");
+        write_file(&saves, "modified.sav", "changed after the last backup");
+        game_save.restore_game(save.game_id).unwrap();
+
+        assert_eq!(read(&saves, "unchanged.sav"), "unchanged");
+        assert_eq!(read(&saves, "modified.sav"), "after, with a different size");
+    }
+
+    #[test]
+    fn test_failed_restore_leaves_the_save_files_as_they_were() {
+        let dir = TestDir::new("failed_restore");
+        let saves = dir.join("saves");
+        write_file(&saves, "a.sav", "backed up a");
+        write_file(&saves, "b.sav", "backed up b");
+        write_file(&saves, "c.sav", "backed up c");
+        let (db, settings) = test_library(&dir, BackupFormat::Directory, false);
+        let fs = Filesystem::new();
+        let game_save = GameSaves::new(&db, &fs, &settings);
+        let save = add_game(&game_save, &saves);
+
+        // The missing b.sav makes the restore fail after it has already written some of the files
+        let snapshot = &db.get_snapshots_by_save_id(save.id).unwrap()[0];
+        let backed_up_files = PathBuf::from(&snapshot.backup_path).join(DIRECTORY_FILES_NAME);
+        fs::remove_file(backed_up_files.join("b.sav")).unwrap();
+        write_file(&saves, "a.sav", "current a");
+        write_file(&saves, "b.sav", "current b");
+        fs::remove_file(saves.join("c.sav")).unwrap();
+        write_file(&saves, "new.sav", "current new");
+
+        assert!(game_save.restore_game(save.game_id).is_err());
+
+        assert_eq!(read(&saves, "a.sav"), "current a");
+        assert_eq!(read(&saves, "b.sav"), "current b");
+        assert!(!saves.join("c.sav").exists());
+        assert_eq!(read(&saves, "new.sav"), "current new");
+    }
+
+    #[test]
+    fn test_import_refuses_objects_that_dont_match_their_hash() {
+        let dir = TestDir::new("import_tampered_object");
+        let (db, settings) = test_library(&dir, BackupFormat::Directory, false);
+        let fs = Filesystem::new();
+        let game_save = GameSaves::new(&db, &fs, &settings);
+        let staging = dir.join("staging");
+        let objects = staging.join(BUNDLE_OBJECTS_FOLDER);
+        let bundle = GameBundle {
+            version: BUNDLE_VERSION,
+            game: Game { title: "Game".to_string(), ..Default::default() },
+            saves: Vec::new(),
+            has_key_file: false,
+            total_files: 1,
+            total_bytes: 0,
+        };
+        write_file(&dir, "original.sav", "original");
+        let sha256 = fs.hash_file(&dir.join("original.sav")).unwrap();
+        let object = Filesystem::object_path(Path::new(""), &sha256);
+
+        write_file(&objects, object.to_str().unwrap(), "tampered");
+        assert!(matches!(game_save.add_bundle(&bundle, &staging), Err(Error::Validation(_))));
+        // The right contents in the wrong folder are refused too
+        fs::remove_dir_all(&objects).unwrap();
+        write_file(&objects, &format!("00/{}", sha256), "original");
+        assert!(matches!(game_save.add_bundle(&bundle, &staging), Err(Error::Validation(_))));
+
+        assert!(!Filesystem::object_path(&game_save.objects_location(), &sha256).exists());
+        assert!(db.get_all_games().unwrap().is_empty());
+    }
+}
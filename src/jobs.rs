@@ -0,0 +1,378 @@
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use chrono::Local;
+
+use crate::{
+    bundle::ImportReport,
+    csv_import::CsvImportReport,
+    db::Db,
+    error::Error,
+    filesystem::Filesystem,
+    game_saves::{BackupReport, GameSaves, MoveReport, RemoveReport},
+    library::LibraryImportReport,
+    settings::Settings,
+};
+
+/// How many finished jobs are kept in the list of recent jobs.
+const RECENT_JOBS: usize = 20;
+
+/// How many failures of the background threads are kept until the GUI shows them.
+const RECENT_FAILURES: usize = 20;
+
+/// Collects what failed on the threads running in the background, like the scheduler and the watcher,
+/// so the GUI can show it.
+#[derive(Clone, Debug, Default)]
+pub struct FailureLog {
+    failures: Arc<Mutex<Vec<String>>>,
+}
+
+impl FailureLog {
+    /// Keeps a failure until [`FailureLog::take`] is called, dropping the oldest failure once too many
+    /// were kept.
+    pub fn record(&self, message: String) {
+        let mut failures = self.failures.lock().unwrap();
+        if failures.len() >= RECENT_FAILURES {
+            failures.remove(0);
+        }
+        failures.push(message);
+    }
+
+    /// Returns the failures recorded since the last call, oldest first.
+    pub fn take(&self) -> Vec<String> {
+        mem::take(&mut *self.failures.lock().unwrap())
+    }
+}
+
+/// How a finished job ended.
+#[derive(Clone, Debug)]
+pub enum JobOutcome {
+    /// A backup finished, possibly without some files that could not be copied.
+    Backup(BackupReport),
+    /// A game or a save file location was added together with its first backup.
+    SaveAdded(BackupReport),
+    /// The job finished with the given message.
+    Done(String),
+    /// The backups were moved to the given backup root.
+    BackupsMoved(PathBuf, MoveReport),
+    /// A game was imported from a bundle.
+    GameImported(ImportReport),
+    /// A game was removed, with or without its backups.
+    GameRemoved(RemoveReport),
+    /// Games were imported from a CSV file.
+    CsvImported(CsvImportReport),
+    /// A library export was merged into the database or replaced it.
+    LibraryImported(LibraryImportReport),
+    /// The job failed with the given error message.
+    Failed(String),
+    /// The job was cancelled before it started or stopped early because it was cancelled. A job that
+    /// finished anyway keeps its own outcome.
+    Cancelled,
+}
+
+impl JobOutcome {
+    /// Returns the outcome of a job that stopped with an error, which is [`JobOutcome::Cancelled`] if it
+    /// stopped because the job was cancelled.
+    pub fn failed(error: Error) -> Self {
+        if error.is_cancelled() {
+            JobOutcome::Cancelled
+        } else {
+            JobOutcome::Failed(error.to_string())
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            JobOutcome::Backup(report) if report.is_complete() => {
+                format!("created {} snapshots", report.snapshot_ids.len())
+            }
+            JobOutcome::Backup(report) if report.failed_games.is_empty() => format!(
+                "created {} snapshots, {} files failed",
+                report.snapshot_ids.len(),
+                report.files.failed.len()
+            ),
+            JobOutcome::Backup(report) => format!(
+                "created {} snapshots, {} games and {} files failed",
+                report.snapshot_ids.len(),
+                report.failed_games.len(),
+                report.files.failed.len()
+            ),
+            JobOutcome::SaveAdded(report) => {
+                format!("added the save files with {} snapshots", report.snapshot_ids.len())
+            }
+            JobOutcome::Done(message) => message.clone(),
+            JobOutcome::BackupsMoved(backup_root, report) if report.old_files.is_complete() => {
+                format!("moved the backups to {}", backup_root.display())
+            }
+            JobOutcome::BackupsMoved(backup_root, report) => format!(
+                "moved the backups to {}, {} files were left in the old folder",
+                backup_root.display(),
+                report.old_files.failed.len()
+            ),
+            JobOutcome::GameImported(report) => report.to_string(),
+            JobOutcome::GameRemoved(report) => report.to_string(),
+            JobOutcome::CsvImported(report) => report.to_string(),
+            JobOutcome::LibraryImported(report) => report.to_string(),
+            JobOutcome::Failed(error) => format!("failed: {}", error),
+            JobOutcome::Cancelled => "cancelled".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum JobState {
+    Queued,
+    Running,
+    Finished(JobOutcome),
+}
+
+/// How far a job got, shared between the worker thread running it and the GUI showing it.
+///
+/// The totals grow while a job runs, e.g. when a backup of several saves moves on to the next save.
+#[derive(Debug, Default)]
+pub struct JobProgress {
+    files_done: AtomicU64,
+    files_total: AtomicU64,
+    bytes_done: AtomicU64,
+    bytes_total: AtomicU64,
+    current_file: Mutex<String>,
+    cancelled: AtomicBool,
+}
+
+impl JobProgress {
+    /// Adds files that are about to be processed to the totals.
+    pub fn add_total(&self, files: u64, bytes: u64) {
+        self.files_total.fetch_add(files, Ordering::Relaxed);
+        self.bytes_total.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Records that a file is being processed.
+    pub fn start_file(&self, path: &str) {
+        *self.current_file.lock().unwrap() = path.to_string();
+    }
+
+    /// Records that a file of the given size was processed.
+    pub fn finish_file(&self, bytes: u64) {
+        self.files_done.fetch_add(1, Ordering::Relaxed);
+        self.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Asks the job to stop at the next file.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of files processed so far and the number of files known to be processed.
+    pub fn files(&self) -> (u64, u64) {
+        (self.files_done.load(Ordering::Relaxed), self.files_total.load(Ordering::Relaxed))
+    }
+
+    /// Returns the number of bytes processed so far and the number of bytes known to be processed.
+    pub fn bytes(&self) -> (u64, u64) {
+        (self.bytes_done.load(Ordering::Relaxed), self.bytes_total.load(Ordering::Relaxed))
+    }
+
+    pub fn current_file(&self) -> String {
+        self.current_file.lock().unwrap().clone()
+    }
+
+    /// Returns the share of the work done so far, between 0 and 1.
+    pub fn fraction(&self) -> f32 {
+        let (bytes_done, bytes_total) = self.bytes();
+        let (files_done, files_total) = self.files();
+
+        if bytes_total > 0 {
+            (bytes_done as f64 / bytes_total as f64).min(1.0) as f32
+        } else if files_total > 0 {
+            (files_done as f64 / files_total as f64).min(1.0) as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A job added to the job runner.
+pub struct Job {
+    pub id: usize,
+    pub title: String,
+    /// When the job was added as a unix timestamp.
+    pub queued_at: i64,
+    pub progress: Arc<JobProgress>,
+    state: Arc<Mutex<JobState>>,
+    /// Whether the outcome was already returned by [`JobRunner::finished_jobs`].
+    reported: bool,
+}
+
+impl Job {
+    pub fn state(&self) -> JobState {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), JobState::Finished(_))
+    }
+}
+
+type JobFn = Box<dyn FnOnce(&GameSaves) -> JobOutcome + Send>;
+
+struct QueuedJob {
+    progress: Arc<JobProgress>,
+    state: Arc<Mutex<JobState>>,
+    settings: Settings,
+    run: JobFn,
+}
+
+/// Runs slow `GameSaves` operations like backups and restores one after another on a worker thread,
+/// so the GUI stays responsive while they run.
+///
+/// The worker uses its own database connection. Dropping the runner cancels the queued jobs and waits
+/// for the running one to stop.
+pub struct JobRunner {
+    sender: Option<mpsc::Sender<QueuedJob>>,
+    thread: Option<JoinHandle<()>>,
+    jobs: Vec<Job>,
+    next_id: usize,
+}
+
+impl JobRunner {
+    /// Starts the worker thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_path` - The path of the database file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the database cannot be opened.
+    pub fn start(db_path: &Path) -> Result<Self, rusqlite::Error> {
+        let db = Db::new(&db_path.display().to_string())?;
+        let (sender, receiver) = mpsc::channel::<QueuedJob>();
+
+        let thread = thread::spawn(move || {
+            for job in receiver {
+                run_job(&db, job);
+            }
+        });
+
+        Ok(Self {
+            sender: Some(sender),
+            thread: Some(thread),
+            jobs: Vec::new(),
+            next_id: 1,
+        })
+    }
+
+    /// Adds a job to the end of the queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - Describes the job in the list of jobs.
+    /// * `settings` - The settings the job runs with.
+    /// * `run` - Does the work, using a `GameSaves` that reports its progress to the job.
+    pub fn spawn(
+        &mut self,
+        title: impl Into<String>,
+        settings: &Settings,
+        run: impl FnOnce(&GameSaves) -> JobOutcome + Send + 'static,
+    ) {
+        let progress = Arc::new(JobProgress::default());
+        let state = Arc::new(Mutex::new(JobState::Queued));
+
+        let queued = QueuedJob {
+            progress: Arc::clone(&progress),
+            state: Arc::clone(&state),
+            settings: settings.clone(),
+            run: Box::new(run),
+        };
+        if let Some(sender) = &self.sender {
+            if sender.send(queued).is_err() {
+                *state.lock().unwrap() = JobState::Finished(JobOutcome::Failed("the job runner stopped".to_string()));
+            }
+        }
+
+        self.jobs.push(Job {
+            id: self.next_id,
+            title: title.into(),
+            queued_at: Local::now().timestamp(),
+            progress,
+            state,
+            reported: false,
+        });
+        self.next_id += 1;
+    }
+
+    /// Returns the queued, running and recently finished jobs, oldest first.
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Returns whether any job is queued or running.
+    pub fn is_busy(&self) -> bool {
+        self.jobs.iter().any(|job| !job.is_finished())
+    }
+
+    /// Cancels a queued or running job.
+    pub fn cancel(&self, job_id: usize) {
+        if let Some(job) = self.jobs.iter().find(|job| job.id == job_id) {
+            job.progress.cancel();
+        }
+    }
+
+    /// Returns the title and outcome of every job that finished since the last call, and forgets the
+    /// oldest finished jobs beyond the number of recent jobs that are kept.
+    pub fn finished_jobs(&mut self) -> Vec<(String, JobOutcome)> {
+        let mut finished = Vec::new();
+        for job in self.jobs.iter_mut().filter(|job| !job.reported) {
+            if let JobState::Finished(outcome) = job.state() {
+                job.reported = true;
+                finished.push((job.title.clone(), outcome));
+            }
+        }
+
+        let mut finished_count = self.jobs.iter().filter(|job| job.reported).count();
+        self.jobs.retain(|job| {
+            if job.reported && finished_count > RECENT_JOBS {
+                finished_count -= 1;
+                false
+            } else {
+                true
+            }
+        });
+
+        finished
+    }
+}
+
+impl Drop for JobRunner {
+    fn drop(&mut self) {
+        for job in &self.jobs {
+            job.progress.cancel();
+        }
+
+        // Closing the channel ends the worker thread once the running job stopped
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run_job(db: &Db, job: QueuedJob) {
+    if job.progress.is_cancelled() {
+        *job.state.lock().unwrap() = JobState::Finished(JobOutcome::Cancelled);
+        return;
+    }
+    *job.state.lock().unwrap() = JobState::Running;
+
+    let fs = Filesystem::with_progress(Arc::clone(&job.progress));
+    let game_save = GameSaves::new(db, &fs, &job.settings);
+    let outcome = (job.run)(&game_save);
+    *job.state.lock().unwrap() = JobState::Finished(outcome);
+}
@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, ErrorKind, Read};
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::{Game, Location, Platform, Save, Schedule, Snapshot};
+use crate::error::{Error, Result};
+use crate::filesystem::FileFilter;
+use crate::retention::RetentionPolicy;
+
+/// The extension of library export files.
+pub const LIBRARY_EXTENSION: &str = "json";
+
+/// The version of the library layout written by this version of the app.
+pub const LIBRARY_VERSION: u32 = 1;
+
+/// Every row of every table in the database, exported to a JSON document.
+///
+/// Unlike a game bundle it contains no files, only the catalogue. The snapshot rows keep pointing at
+/// the snapshot folders in the backup folder.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Library {
+    pub version: u32,
+    /// When the library was exported as a unix timestamp.
+    pub exported_at: i64,
+    pub games: Vec<Game>,
+    pub platforms: Vec<Platform>,
+    pub locations: Vec<Location>,
+    pub saves: Vec<Save>,
+    pub snapshots: Vec<Snapshot>,
+    #[serde(default)]
+    pub retention_policies: Vec<GameRetentionPolicy>,
+    #[serde(default)]
+    pub schedules: Vec<Schedule>,
+}
+
+/// The retention policy of a single game.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameRetentionPolicy {
+    pub game_id: i32,
+    #[serde(flatten)]
+    pub policy: RetentionPolicy,
+}
+
+/// What importing a library does with the games already in the database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Adds the games and saves of the library that aren't in the database yet.
+    #[default]
+    Merge,
+    /// Deletes every row in the database and inserts the rows of the library with their IDs.
+    Replace,
+}
+
+impl ImportMode {
+    pub const ALL: [ImportMode; 2] = [ImportMode::Merge, ImportMode::Replace];
+
+    /// Returns a human readable name of the mode.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ImportMode::Merge => "Merge into this library",
+            ImportMode::Replace => "Replace this library",
+        }
+    }
+}
+
+/// A save of the imported library that was left out because the database already has a save of the
+/// same game on the same platform.
+#[derive(Clone, Debug)]
+pub struct LibraryConflict {
+    pub title: String,
+    pub platform_name: String,
+}
+
+impl fmt::Display for LibraryConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' on {} is already in the library", self.title, self.platform_name)
+    }
+}
+
+/// What importing a library added to the database.
+#[derive(Clone, Debug, Default)]
+pub struct LibraryImportReport {
+    pub games_added: usize,
+    pub saves_added: usize,
+    pub snapshots_added: usize,
+    pub conflicts: Vec<LibraryConflict>,
+}
+
+impl fmt::Display for LibraryImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "imported {} games with {} saves and {} snapshots",
+            self.games_added, self.saves_added, self.snapshots_added
+        )?;
+        if !self.conflicts.is_empty() {
+            write!(f, ", skipped {} saves that were already in the library", self.conflicts.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl Library {
+    /// Reads a library export and checks that its rows fit together.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it cannot be parsed, was written by a newer version of the
+    /// app, has rows referring to rows it doesn't contain or file patterns that are not valid.
+    pub fn read(reader: impl Read) -> Result<Self> {
+        let library: Library = serde_json::from_reader(reader).map_err(|e| {
+            if e.is_io() {
+                Error::Io(e.into())
+            } else {
+                Error::Validation(format!("the library is not valid: {}", e))
+            }
+        })?;
+
+        if library.version > LIBRARY_VERSION {
+            return Err(Error::Validation(format!(
+                "the library was exported by a newer version (layout {})",
+                library.version
+            )));
+        }
+        library.validate()?;
+        Ok(library)
+    }
+
+    /// Returns the library as JSON.
+    pub fn to_json(&self) -> Result<Vec<u8>, io::Error> {
+        serde_json::to_vec_pretty(self).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Checks that the IDs are unique, every row refers to rows in the library and the file patterns of
+    /// the save file locations are valid.
+    fn validate(&self) -> Result<()> {
+        let game_ids = unique_ids("game", self.games.iter().map(|game| game.id))?;
+        let platform_ids = unique_ids("platform", self.platforms.iter().map(|platform| platform.id))?;
+        let location_ids = unique_ids("location", self.locations.iter().map(|location| location.id))?;
+        let save_ids = unique_ids("save", self.saves.iter().map(|save| save.id))?;
+        unique_ids("snapshot", self.snapshots.iter().map(|snapshot| snapshot.id))?;
+        unique_ids("schedule", self.schedules.iter().map(|schedule| schedule.id))?;
+
+        for location in &self.locations {
+            FileFilter::for_location(location)?;
+        }
+        for save in &self.saves {
+            check_reference("save", save.id, "game", save.game_id, &game_ids)?;
+            check_reference("save", save.id, "platform", save.platform_id, &platform_ids)?;
+            check_reference("save", save.id, "location", save.location_id, &location_ids)?;
+        }
+        for snapshot in &self.snapshots {
+            check_reference("snapshot", snapshot.id, "save", snapshot.save_id, &save_ids)?;
+        }
+        for retention_policy in &self.retention_policies {
+            check_reference("retention policy", retention_policy.game_id, "game", retention_policy.game_id, &game_ids)?;
+        }
+        for schedule in &self.schedules {
+            for game_id in &schedule.game_ids {
+                check_reference("schedule", schedule.id, "game", *game_id, &game_ids)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn unique_ids(table: &str, ids: impl Iterator<Item = i32>) -> Result<HashSet<i32>> {
+    let mut unique = HashSet::new();
+    for id in ids {
+        if !unique.insert(id) {
+            return Err(Error::Validation(format!("the library has more than one {} with ID {}", table, id)));
+        }
+    }
+    Ok(unique)
+}
+
+fn check_reference(table: &str, id: i32, referenced: &str, referenced_id: i32, ids: &HashSet<i32>) -> Result<()> {
+    if ids.contains(&referenced_id) {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!(
+            "{} {} refers to {} {}, which is not in the library",
+            table, id, referenced, referenced_id
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a library with one game saved on one platform, with one snapshot and a schedule.
+    fn library() -> Library {
+        Library {
+            version: LIBRARY_VERSION,
+            games: vec![Game { id: 1, title: "Game".to_string(), ..Default::default() }],
+            platforms: vec![Platform { id: 2, platform_name: "PC".to_string() }],
+            locations: vec![Location {
+                id: 3,
+                location_path: "/saves".to_string(),
+                description: String::new(),
+                include_patterns: vec!["*.sav".to_string()],
+                exclude_patterns: Vec::new(),
+            }],
+            saves: vec![Save { id: 4, game_id: 1, location_id: 3, metadata: None, platform_id: 2 }],
+            snapshots: vec![Snapshot {
+                id: 5,
+                save_id: 4,
+                created_at: 0,
+                backup_path: "/backups/1/2/4/snapshot".to_string(),
+                format: Default::default(),
+                kind: Default::default(),
+            }],
+            retention_policies: vec![GameRetentionPolicy { game_id: 1, policy: RetentionPolicy::default() }],
+            schedules: vec![Schedule {
+                id: 6,
+                all_games: false,
+                game_ids: vec![1],
+                timing: crate::scheduler::ScheduleTiming::Interval { minutes: 60 },
+                last_run: 0,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn validation_error(library: &Library) -> String {
+        match library.validate() {
+            Err(Error::Validation(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_valid_library() {
+        assert!(library().validate().is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_ids() {
+        let mut library = library();
+        library.games.push(Game { id: 1, title: "Other game".to_string(), ..Default::default() });
+
+        assert_eq!(validation_error(&library), "the library has more than one game with ID 1");
+    }
+
+    #[test]
+    fn test_save_referring_to_missing_rows() {
+        let mut library = library();
+        library.saves[0].platform_id = 7;
+
+        assert_eq!(validation_error(&library), "save 4 refers to platform 7, which is not in the library");
+    }
+
+    #[test]
+    fn test_snapshot_referring_to_missing_save() {
+        let mut library = library();
+        library.snapshots[0].save_id = 7;
+
+        assert_eq!(validation_error(&library), "snapshot 5 refers to save 7, which is not in the library");
+    }
+
+    #[test]
+    fn test_schedule_referring_to_missing_game() {
+        let mut library = library();
+        library.schedules[0].game_ids.push(7);
+
+        assert_eq!(validation_error(&library), "schedule 6 refers to game 7, which is not in the library");
+    }
+
+    #[test]
+    fn test_invalid_file_pattern() {
+        let mut library = library();
+        library.locations[0].include_patterns = vec!["[".to_string()];
+
+        assert!(matches!(library.validate(), Err(Error::Validation(_))));
+    }
+
+    #[test]
+    fn test_read_rejects_newer_version() {
+        let library = Library { version: LIBRARY_VERSION + 1, ..library() };
+        let json = library.to_json().unwrap();
+
+        assert!(matches!(Library::read(json.as_slice()), Err(Error::Validation(_))));
+    }
+}
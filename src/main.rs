@@ -1,14 +1,117 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process, thread,
+    time::Duration,
+};
+
 use eframe::egui;
 
+mod bundle;
+mod crypto;
+mod csv_import;
 mod game_saves;
 mod shark_gui;
 mod db;
+mod error;
 mod filesystem;
+mod jobs;
+mod library;
+mod manifest;
+mod retention;
+mod scheduler;
+mod settings;
+mod watcher;
 mod widgets;
-const DB_NAME: &str = "local_games.db";
+
+use settings::{Settings, StorageOverrides, BACKUPS_FOLDER_NAME, DB_FILE_NAME, SETTINGS_FILE_NAME};
+
+/// How often the daemon prints what failed on the background threads.
+const DAEMON_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+const USAGE: &str = "Usage: sharks_save_haven [--daemon] [--config <file>] [--db <file>] [--backup-root <folder>] [--passphrase-file <file>]";
+
+/// The command line arguments.
+#[derive(Debug, Default)]
+struct Args {
+    daemon: bool,
+    /// The settings file to use instead of the one in the config folder.
+    config: Option<PathBuf>,
+    /// A file holding the passphrase of encrypted backups, so they can be taken without asking for it.
+    passphrase_file: Option<PathBuf>,
+    storage: StorageOverrides,
+}
+
+impl Args {
+    /// Parses the command line arguments, accepting both `--flag value` and `--flag=value`.
+    ///
+    /// Relative paths are resolved against the working directory right away, so the locations don't
+    /// change when the working directory does.
+    fn parse() -> Result<Self, String> {
+        let mut args = Args::default();
+        let mut arguments = env::args().skip(1);
+
+        while let Some(argument) = arguments.next() {
+            let (flag, inline_value) = match argument.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (argument, None),
+            };
+
+            if flag == "--daemon" {
+                args.daemon = true;
+                continue;
+            }
+
+            let slot = match flag.as_str() {
+                "--config" => &mut args.config,
+                "--db" => &mut args.storage.db_path,
+                "--backup-root" => &mut args.storage.backup_root,
+                "--passphrase-file" => &mut args.passphrase_file,
+                _ => return Err(format!("Unknown argument '{}'", flag)),
+            };
+            let value = inline_value
+                .or_else(|| arguments.next())
+                .ok_or_else(|| format!("Missing value for {}", flag))?;
+            *slot = Some(absolute_path(Path::new(&value)));
+        }
+
+        Ok(args)
+    }
+}
 
 fn main() -> Result<(), eframe::Error> {
-    let my_app = shark_gui::SharkGui::new();
+    let args = Args::parse().unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+    let settings_path = args.config.unwrap_or_else(settings::default_settings_path);
+    Settings::set_overrides(args.storage);
+    let legacy_migration_backup = migrate_legacy_data(&settings_path);
+
+    let migration_backup = match prepare_database(&settings_path) {
+        Ok(migration_backup) => migration_backup.or(legacy_migration_backup),
+        Err(e) => {
+            eprintln!("Failed to open the database: {}", e);
+            process::exit(1);
+        }
+    };
+
+    if let Some(passphrase_file) = &args.passphrase_file {
+        if let Err(e) = unlock_backups(&settings_path, passphrase_file) {
+            eprintln!("Failed to unlock the encrypted backups: {}", e);
+            process::exit(1);
+        }
+    }
+
+    if args.daemon {
+        if let Some(backup_path) = &migration_backup {
+            println!("{}", migration_notice(backup_path));
+        }
+        run_daemon(&settings_path);
+        return Ok(());
+    }
+
+    let my_app = shark_gui::SharkGui::new(settings_path, migration_backup);
 
     let options = eframe::NativeOptions {
         initial_window_size: Some(egui::vec2(800.0, 600.0)),
@@ -20,3 +123,139 @@ fn main() -> Result<(), eframe::Error> {
         Box::new(|_cc| Box::new(my_app)),
     )
 }
+
+/// Runs the backup scheduler and, if enabled in the settings, the save file watcher without the GUI
+/// until the process is killed.
+fn run_daemon(settings_path: &Path) {
+    let settings = Settings::load(settings_path);
+    let db_path = settings.db_path();
+    let db = open_database(&db_path).expect("Failed to create database connection");
+    db.create_tables().expect("Failed to create tables");
+
+    let scheduler = scheduler::Scheduler::start(&db_path, settings_path)
+        .expect("Failed to start the backup scheduler");
+    let watcher = if settings.watch {
+        Some(
+            watcher::Watcher::start(&db_path, settings_path)
+                .expect("Failed to start watching save files"),
+        )
+    } else {
+        None
+    };
+
+    println!("Running backups in the background, press Ctrl+C to stop");
+    loop {
+        thread::sleep(DAEMON_REPORT_INTERVAL);
+
+        // Without the GUI to show them, the failures of the background threads go to the terminal
+        let mut failures = scheduler.take_failures();
+        if let Some(watcher) = &watcher {
+            failures.extend(watcher.take_failures());
+        }
+        for failure in failures {
+            eprintln!("{}", failure);
+        }
+    }
+}
+
+/// Unlocks the encrypted backups with the passphrase read from a file, ignoring a trailing line break.
+fn unlock_backups(settings_path: &Path, passphrase_file: &Path) -> io::Result<()> {
+    let settings = Settings::load(settings_path);
+    let passphrase = fs::read_to_string(passphrase_file)?;
+    crypto::unlock(&settings.backup_root(), passphrase.trim_end_matches(['\r', '\n']))
+}
+
+/// Opens the database, creating the folder it's in first.
+fn open_database(db_path: &Path) -> rusqlite::Result<db::Db> {
+    if let Some(parent) = db_path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    db::Db::new(&db_path.display().to_string())
+}
+
+/// Opens the database and migrates it to the current schema, so the GUI and the background threads
+/// find the tables they expect.
+///
+/// Returns the path of the copy of the database made before migrating it, if it was migrated.
+fn prepare_database(settings_path: &Path) -> rusqlite::Result<Option<PathBuf>> {
+    let db = open_database(&Settings::load(settings_path).db_path())?;
+    db.create_tables()
+}
+
+/// Tells the user where the copy of the database made before migrating it to the current schema is kept.
+fn migration_notice(backup_path: &Path) -> String {
+    format!(
+        "The database was updated for this version of the app, the previous database is kept at {}",
+        backup_path.display()
+    )
+}
+
+/// Moves the settings file, database and backups that older versions kept in the working directory to
+/// their configured locations, unless something already exists there.
+///
+/// Returns the path of the copy of the database made before migrating it, if moving the backups
+/// needed the database and it was migrated.
+fn migrate_legacy_data(settings_path: &Path) -> Option<PathBuf> {
+    let legacy_settings = absolute_path(Path::new(SETTINGS_FILE_NAME));
+    if !settings_path.exists() && legacy_settings.is_file() {
+        match move_file(&legacy_settings, settings_path) {
+            Ok(()) => println!("Moved the settings to {}", settings_path.display()),
+            Err(e) => println!("Failed to move the settings to {}: {}", settings_path.display(), e),
+        }
+    }
+
+    let settings = Settings::load(settings_path);
+    let db_path = settings.db_path();
+    let legacy_db = absolute_path(Path::new(DB_FILE_NAME));
+    if !db_path.exists() && legacy_db.is_file() {
+        match move_file(&legacy_db, &db_path) {
+            Ok(()) => println!("Moved the database to {}", db_path.display()),
+            Err(e) => println!("Failed to move the database to {}: {}", db_path.display(), e),
+        }
+    }
+
+    // Older versions stored the snapshot paths relative to the working directory, so the old root is
+    // given the same way
+    let legacy_backups = Path::new(BACKUPS_FOLDER_NAME);
+    let backup_root = settings.backup_root();
+    let mut migration_backup = None;
+    if legacy_backups.is_dir() && !backup_root.exists() && absolute_path(legacy_backups) != backup_root {
+        let db = open_database(&db_path).expect("Failed to create database connection");
+        migration_backup = db.create_tables().expect("Failed to create tables");
+        let fs = filesystem::Filesystem::new();
+        let game_saves = game_saves::GameSaves::new(&db, &fs, &settings);
+
+        match game_saves.move_backups(legacy_backups, &backup_root) {
+            Ok(report) => {
+                println!("Moved {} snapshots to {}", report.snapshots_moved, backup_root.display());
+                if !report.old_files.is_complete() {
+                    print!("Some files could not be removed from the old backup folder: {}", report.old_files);
+                }
+            }
+            Err(e) => println!("Failed to move the backups to {}: {}", backup_root.display(), e),
+        }
+    }
+
+    migration_backup
+}
+
+/// Moves a file, copying it if it can't be renamed, e.g. because the destination is on another drive.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// Resolves a relative path against the working directory.
+fn absolute_path(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir().unwrap_or_default().join(path)
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::io::{self, ErrorKind, Read};
+use std::path::{Component, Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{self, BackupKey, DecryptReader, EncryptWriter};
+use crate::error::{Error, Result};
+
+/// The name of the file a snapshot's manifest is stored in, inside the snapshot folder.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Lists every file of a snapshot together with its size, modification time and the hash of its contents.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+    /// Files of the previous snapshot that no longer existed when this snapshot was taken.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deleted: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The path of the file relative to the save file location, using `/` as separator.
+    pub path: String,
+    pub size: u64,
+    /// The modification time of the original file as a unix timestamp.
+    #[serde(default)]
+    pub mtime: i64,
+    /// The SHA-256 hash of the file contents as a lowercase hex string.
+    pub sha256: String,
+    /// The ID of the earlier snapshot holding the file, if it was unchanged in an incremental backup
+    /// and therefore not copied again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_in: Option<i32>,
+}
+
+impl Manifest {
+    /// Reads a manifest from a JSON file, decrypting it first if it was written by
+    /// [`Manifest::save_encrypted`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the manifest file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be read or does not contain a valid manifest,
+    /// including a manifest listing a hash that is not 64 hex digits or a path that could lead out of
+    /// the save file location, or if it is encrypted and the backup passphrase has not been entered.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut contents = fs::read(path)?;
+        if crypto::is_encrypted(&contents) {
+            let mut plain = Vec::new();
+            DecryptReader::new(contents.as_slice())?.read_to_end(&mut plain)?;
+            contents = plain;
+        }
+        let manifest: Manifest =
+            serde_json::from_slice(&contents).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+        // Hashes name the objects in the object store, so they must not be able to point anywhere else
+        if let Some(entry) = manifest.files.iter().find(|entry| !is_sha256(&entry.sha256)) {
+            return Err(Error::Validation(format!(
+                "the manifest {} lists {} with an invalid hash",
+                path.display(),
+                entry.path
+            )));
+        }
+
+        // Restoring writes every file to its path inside the save file location, so a manifest from an
+        // imported bundle must not be able to write anywhere else
+        if let Some(entry) = manifest.files.iter().find(|entry| !is_relative_path(&entry.path)) {
+            return Err(Error::Validation(format!(
+                "the manifest {} lists the file {}, which is not inside the save file location",
+                path.display(),
+                entry.path
+            )));
+        }
+
+        Ok(manifest)
+    }
+
+    /// Writes the manifest to a JSON file, creating its parent folder if it doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the manifest file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), io::Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        fs::write(path, contents)
+    }
+
+    /// Writes the manifest to an encrypted JSON file, so the names and hashes of the files of an
+    /// encrypted snapshot don't give away what it contains.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the manifest file.
+    /// * `key` - The key to encrypt the manifest with.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be written.
+    pub fn save_encrypted(&self, path: &Path, key: &BackupKey) -> Result<(), io::Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = serde_json::to_vec(self).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        let mut writer = EncryptWriter::new(fs::File::create(path)?, key)?;
+        io::Write::write_all(&mut writer, &contents)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Returns the entry of the file with the given relative path.
+    pub fn get(&self, path: &str) -> Option<&ManifestEntry> {
+        self.files.iter().find(|entry| entry.path == path)
+    }
+}
+
+/// Returns whether a path is made only of file and folder names, so it stays inside the folder it is
+/// joined to.
+fn is_relative_path(path: &str) -> bool {
+    let path = Path::new(path);
+    path.components().next().is_some() && path.components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Returns whether a string is a SHA-256 hash as written in manifests, 64 lowercase hex digits.
+pub(crate) fn is_sha256(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::tests::TestDir;
+
+    fn manifest_with_path(path: &str) -> Manifest {
+        Manifest {
+            files: vec![ManifestEntry {
+                path: path.to_string(),
+                size: 0,
+                mtime: 0,
+                sha256: "0".repeat(64),
+                stored_in: None,
+            }],
+            deleted: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_load_accepts_relative_paths() {
+        let dir = TestDir::new("manifest_relative_path");
+        let path = dir.join(MANIFEST_FILE_NAME);
+        manifest_with_path("profiles/1/save.sav").save(&path).unwrap();
+
+        let manifest = Manifest::load(&path).unwrap();
+
+        assert!(manifest.get("profiles/1/save.sav").is_some());
+    }
+
+    #[test]
+    fn test_load_refuses_paths_leading_out_of_the_save_location() {
+        let dir = TestDir::new("manifest_traversal_path");
+        let path = dir.join(MANIFEST_FILE_NAME);
+
+        for file_path in ["../../outside.sav", "profiles/../../outside.sav", "/etc/outside.sav", "./save.sav", ""] {
+            manifest_with_path(file_path).save(&path).unwrap();
+
+            assert!(
+                matches!(Manifest::load(&path), Err(Error::Validation(_))),
+                "{} was not refused",
+                file_path
+            );
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+
+use chrono::{Datelike, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+/// Decides which snapshots of a save are kept when old backups are pruned.
+///
+/// A snapshot is kept if any of the rules keeps it. A policy without any rule keeps every snapshot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Keep the given number of most recent snapshots.
+    pub keep_last: Option<u32>,
+    /// Keep the most recent snapshot of each of the given number of most recent days with a snapshot.
+    pub keep_daily: Option<u32>,
+    /// Keep the most recent snapshot of each of the given number of most recent weeks with a snapshot.
+    pub keep_weekly: Option<u32>,
+    /// Keep the most recent snapshot of each of the given number of most recent months with a snapshot.
+    pub keep_monthly: Option<u32>,
+    /// Delete the oldest kept snapshots until the snapshots take up at most this many bytes. The most
+    /// recent snapshot is always kept.
+    pub max_total_size: Option<u64>,
+}
+
+/// The creation time and disk usage of a snapshot, as far as retention is concerned.
+#[derive(Clone, Copy, Debug)]
+pub struct SnapshotUsage {
+    pub snapshot_id: i32,
+    /// When the snapshot was taken as a unix timestamp.
+    pub created_at: i64,
+    /// The number of bytes the snapshot takes up on disk.
+    pub size: u64,
+}
+
+impl RetentionPolicy {
+    /// Returns whether the policy keeps every snapshot.
+    pub fn keeps_everything(&self) -> bool {
+        self.keep_last.is_none()
+            && self.keep_daily.is_none()
+            && self.keep_weekly.is_none()
+            && self.keep_monthly.is_none()
+            && self.max_total_size.is_none()
+    }
+
+    /// Selects the snapshots the policy doesn't keep.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshots` - The snapshots of a save, in any order.
+    ///
+    /// # Returns
+    ///
+    /// Returns the IDs of the expired snapshots, newest first.
+    pub fn expired(&self, snapshots: &[SnapshotUsage]) -> Vec<i32> {
+        if self.keeps_everything() || snapshots.is_empty() {
+            return Vec::new();
+        }
+
+        let mut newest_first = snapshots.to_vec();
+        newest_first.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.snapshot_id.cmp(&a.snapshot_id)));
+
+        let only_size_limit = self.keep_last.is_none()
+            && self.keep_daily.is_none()
+            && self.keep_weekly.is_none()
+            && self.keep_monthly.is_none();
+
+        let mut kept: HashSet<i32> = HashSet::new();
+        if only_size_limit {
+            kept.extend(newest_first.iter().map(|snapshot| snapshot.snapshot_id));
+        }
+        if let Some(count) = self.keep_last {
+            kept.extend(newest_first.iter().take(count as usize).map(|snapshot| snapshot.snapshot_id));
+        }
+        if let Some(count) = self.keep_daily {
+            kept.extend(keep_per_period(&newest_first, count, |date| {
+                (date.year(), date.ordinal())
+            }));
+        }
+        if let Some(count) = self.keep_weekly {
+            kept.extend(keep_per_period(&newest_first, count, |date| {
+                let week = date.iso_week();
+                (week.year(), week.week())
+            }));
+        }
+        if let Some(count) = self.keep_monthly {
+            kept.extend(keep_per_period(&newest_first, count, |date| {
+                (date.year(), date.month())
+            }));
+        }
+
+        // The most recent snapshot always survives, so a save never loses its last backup
+        let newest_id = newest_first[0].snapshot_id;
+        kept.insert(newest_id);
+
+        if let Some(max_total_size) = self.max_total_size {
+            let mut total_size = 0;
+            for snapshot in &newest_first {
+                if !kept.contains(&snapshot.snapshot_id) {
+                    continue;
+                }
+
+                total_size += snapshot.size;
+                if total_size > max_total_size && snapshot.snapshot_id != newest_id {
+                    kept.remove(&snapshot.snapshot_id);
+                }
+            }
+        }
+
+        newest_first
+            .iter()
+            .filter(|snapshot| !kept.contains(&snapshot.snapshot_id))
+            .map(|snapshot| snapshot.snapshot_id)
+            .collect()
+    }
+}
+
+/// Returns the IDs of the most recent snapshot in each of the `count` most recent periods.
+///
+/// `period` maps the local date of a snapshot to the period it falls into.
+fn keep_per_period<P: PartialEq>(
+    newest_first: &[SnapshotUsage],
+    count: u32,
+    period: impl Fn(chrono::NaiveDate) -> P,
+) -> Vec<i32> {
+    let mut kept = Vec::new();
+    let mut last_period = None;
+
+    for snapshot in newest_first {
+        if kept.len() == count as usize {
+            break;
+        }
+
+        let date = match Local.timestamp_opt(snapshot.created_at, 0).single() {
+            Some(time) => time.naive_local().date(),
+            None => continue,
+        };
+        let snapshot_period = period(date);
+        if last_period.as_ref() != Some(&snapshot_period) {
+            kept.push(snapshot.snapshot_id);
+            last_period = Some(snapshot_period);
+        }
+    }
+
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a snapshot of 10 bytes taken at the given local time.
+    fn snapshot(snapshot_id: i32, day: u32, hour: u32) -> SnapshotUsage {
+        let created_at = Local.with_ymd_and_hms(2023, 3, day, hour, 0, 0).unwrap().timestamp();
+        SnapshotUsage { snapshot_id, created_at, size: 10 }
+    }
+
+    #[test]
+    fn test_keeps_everything_without_rules() {
+        let snapshots = [snapshot(1, 1, 12), snapshot(2, 2, 12)];
+
+        assert!(RetentionPolicy::default().keeps_everything());
+        assert_eq!(RetentionPolicy::default().expired(&snapshots), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_keep_last() {
+        let snapshots = [snapshot(1, 1, 12), snapshot(3, 3, 12), snapshot(2, 2, 12)];
+        let policy = RetentionPolicy { keep_last: Some(1), ..Default::default() };
+
+        assert_eq!(policy.expired(&snapshots), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_keep_daily_keeps_the_newest_snapshot_of_each_day() {
+        let snapshots = [
+            snapshot(1, 1, 9),
+            snapshot(2, 1, 18),
+            snapshot(3, 2, 9),
+            snapshot(4, 2, 18),
+            snapshot(5, 3, 9),
+        ];
+        let policy = RetentionPolicy { keep_daily: Some(2), ..Default::default() };
+
+        assert_eq!(policy.expired(&snapshots), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_rules_keep_what_any_of_them_keeps() {
+        let snapshots = [snapshot(1, 1, 9), snapshot(2, 1, 18), snapshot(3, 2, 9), snapshot(4, 2, 18)];
+        let policy = RetentionPolicy { keep_last: Some(2), keep_daily: Some(2), ..Default::default() };
+
+        assert_eq!(policy.expired(&snapshots), vec![1]);
+    }
+
+    #[test]
+    fn test_max_total_size_deletes_the_oldest_snapshots() {
+        let snapshots = [snapshot(1, 1, 12), snapshot(2, 2, 12), snapshot(3, 3, 12)];
+        let policy = RetentionPolicy { max_total_size: Some(25), ..Default::default() };
+
+        assert_eq!(policy.expired(&snapshots), vec![1]);
+    }
+
+    #[test]
+    fn test_newest_snapshot_is_always_kept() {
+        let snapshots = [snapshot(1, 1, 12), snapshot(2, 2, 12)];
+        let policy = RetentionPolicy { keep_last: Some(0), max_total_size: Some(0), ..Default::default() };
+
+        assert_eq!(policy.expired(&snapshots), vec![1]);
+    }
+}
@@ -0,0 +1,207 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{Db, Schedule},
+    error::Result,
+    filesystem::Filesystem,
+    game_saves::{BackupReport, GameSaves},
+    jobs::FailureLog,
+    settings::Settings,
+};
+
+/// How long the scheduler thread sleeps between checks whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the scheduler reads the schedules from the database and runs the due ones.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// When a schedule backs up its games.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleTiming {
+    /// Every given number of minutes after the previous run.
+    Interval { minutes: u32 },
+    /// Once a day at the given local time, in minutes after midnight.
+    Daily { minute_of_day: u32 },
+}
+
+impl ScheduleTiming {
+    /// Returns whether a schedule with this timing that last ran at `last_run` should run at `now`.
+    ///
+    /// # Arguments
+    ///
+    /// * `last_run` - The unix timestamp of the previous run, 0 if it never ran.
+    /// * `now` - The current unix timestamp.
+    pub fn is_due(&self, last_run: i64, now: i64) -> bool {
+        match *self {
+            ScheduleTiming::Interval { minutes } => now - last_run >= i64::from(minutes) * 60,
+            ScheduleTiming::Daily { minute_of_day } => {
+                let today = match Local.timestamp_opt(now, 0).single() {
+                    Some(time) => time.naive_local().date(),
+                    None => return false,
+                };
+                let run_at = today.and_hms_opt(minute_of_day / 60 % 24, minute_of_day % 60, 0);
+
+                match run_at.and_then(|run_at| Local.from_local_datetime(&run_at).earliest()) {
+                    Some(run_at) => now >= run_at.timestamp() && last_run < run_at.timestamp(),
+                    None => false,
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for ScheduleTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleTiming::Interval { minutes } => write!(f, "every {} minutes", minutes),
+            ScheduleTiming::Daily { minute_of_day } => {
+                write!(f, "daily at {:02}:{:02}", minute_of_day / 60, minute_of_day % 60)
+            }
+        }
+    }
+}
+
+/// Runs the backup schedules stored in the database on a background thread.
+///
+/// The scheduler uses its own database connection. It stops when dropped.
+pub struct Scheduler {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    failures: FailureLog,
+}
+
+impl Scheduler {
+    /// Starts running the schedules in the database.
+    ///
+    /// Schedules are read again on every check, so added or removed schedules take effect without
+    /// restarting the scheduler. The settings are read again before every run.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_path` - The path of the database file.
+    /// * `settings_path` - The path of the settings file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the database cannot be opened.
+    pub fn start(db_path: &Path, settings_path: &Path) -> Result<Self, rusqlite::Error> {
+        let db = Db::new(&db_path.display().to_string())?;
+        let settings_path = settings_path.to_path_buf();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let failures = FailureLog::default();
+        let thread_stop = Arc::clone(&stop);
+        let thread_failures = failures.clone();
+        let thread = thread::spawn(move || run(&db, &settings_path, &thread_stop, &thread_failures));
+
+        Ok(Self { stop, thread: Some(thread), failures })
+    }
+
+    /// Returns the scheduled backups that failed since the last call.
+    pub fn take_failures(&self) -> Vec<String> {
+        self.failures.take()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(db: &Db, settings_path: &Path, stop: &AtomicBool, failures: &FailureLog) {
+    let fs = Filesystem::new();
+    let mut last_check: Option<Instant> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        if last_check.is_none_or(|time| time.elapsed() >= CHECK_INTERVAL) {
+            last_check = Some(Instant::now());
+
+            match db.get_all_schedules() {
+                Ok(schedules) => {
+                    let now = Local::now().timestamp();
+                    for schedule in schedules
+                        .iter()
+                        .filter(|schedule| schedule.timing.is_due(schedule.last_run, now))
+                    {
+                        run_schedule(db, &fs, settings_path, schedule, now, failures);
+                    }
+                }
+                Err(e) => failures.record(format!("Failed to get the backup schedules: {}", e)),
+            }
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Backs up the games of a schedule and records the run, even if it failed, so a failing schedule
+/// isn't retried on every check.
+fn run_schedule(
+    db: &Db,
+    fs: &Filesystem,
+    settings_path: &Path,
+    schedule: &Schedule,
+    now: i64,
+    failures: &FailureLog,
+) {
+    match back_up_games(db, fs, settings_path, schedule) {
+        Ok(report) => {
+            for failure in &report.failed_games {
+                failures.record(format!("Scheduled backup ({}) failed for {}", schedule.timing, failure));
+            }
+            if !report.files.is_complete() {
+                failures.record(format!(
+                    "Scheduled backup ({}) could not back up {} files",
+                    schedule.timing,
+                    report.files.failed.len()
+                ));
+            }
+        }
+        Err(e) => failures.record(format!("Scheduled backup ({}) failed: {}", schedule.timing, e)),
+    }
+
+    if let Err(e) = db.update_schedule_last_run(schedule.id, now) {
+        failures.record(format!("Failed to record scheduled backup: {}", e));
+    }
+}
+
+/// Backs up the games of a schedule with the current settings. A game that fails to back up doesn't
+/// stop the games after it, it's listed in the report instead.
+fn back_up_games(
+    db: &Db,
+    fs: &Filesystem,
+    settings_path: &Path,
+    schedule: &Schedule,
+) -> Result<BackupReport> {
+    let settings = Settings::load(settings_path);
+    let game_save = GameSaves::new(db, fs, &settings);
+
+    if schedule.all_games {
+        game_save.backup_all_games()
+    } else {
+        let mut report = BackupReport::default();
+        for game_id in &schedule.game_ids {
+            match game_save.backup_game(*game_id) {
+                Ok(game_report) => report.merge(game_report),
+                Err(e) => {
+                    let title = game_save.get_game_title_by_id(*game_id).unwrap_or_else(|_| game_id.to_string());
+                    report.record_game_failure(&title, &e);
+                }
+            }
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::db::BackupFormat;
+use crate::retention::RetentionPolicy;
+
+/// The name of the database file in the data folder.
+pub const DB_FILE_NAME: &str = "local_games.db";
+
+/// The name of the settings file in the config folder.
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+
+/// The name of the folder in the data folder that holds the backups.
+pub const BACKUPS_FOLDER_NAME: &str = "backups";
+
+/// Storage locations given on the command line. They take precedence over the settings file for as
+/// long as the process runs, but are never written to it.
+#[derive(Clone, Debug, Default)]
+pub struct StorageOverrides {
+    pub backup_root: Option<PathBuf>,
+    pub db_path: Option<PathBuf>,
+}
+
+static OVERRIDES: OnceLock<StorageOverrides> = OnceLock::new();
+
+/// User preferences that are kept in a JSON file next to the database.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// The format new snapshots are stored in.
+    pub backup_format: BackupFormat,
+    /// Whether new snapshots only copy the files that changed since the previous snapshot of the same
+    /// save. Archive formats always store every file.
+    pub incremental: bool,
+    /// Whether incremental backups hash every file instead of trusting unchanged sizes and
+    /// modification times.
+    pub incremental_compare_hash: bool,
+    /// The retention policy for games that don't have their own.
+    pub retention: RetentionPolicy,
+    /// Whether saves are backed up automatically when their files change.
+    pub watch: bool,
+    /// How many seconds a save file location has to stay unchanged before the watcher backs it up.
+    pub watch_quiet_period_secs: u64,
+    /// The folder the backups are stored in, or `None` for the backups folder in the data folder.
+    pub backup_root: Option<PathBuf>,
+    /// The database file, or `None` for the database file in the data folder.
+    pub db_path: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            backup_format: BackupFormat::default(),
+            incremental: false,
+            incremental_compare_hash: false,
+            retention: RetentionPolicy::default(),
+            watch: false,
+            watch_quiet_period_secs: 10,
+            backup_root: None,
+            db_path: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings from a JSON file.
+    ///
+    /// Returns the default settings if the file doesn't exist yet or cannot be parsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the settings file.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the settings to a JSON file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the settings file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), io::Error> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)
+    }
+
+    /// Sets the storage locations given on the command line. Only the first call has an effect.
+    pub fn set_overrides(overrides: StorageOverrides) {
+        let _ = OVERRIDES.set(overrides);
+    }
+
+    /// Returns the storage locations given on the command line.
+    pub fn overrides() -> &'static StorageOverrides {
+        OVERRIDES.get_or_init(StorageOverrides::default)
+    }
+
+    /// Returns the folder the backups are stored in, from the command line, the settings file or the
+    /// data folder, in that order.
+    pub fn backup_root(&self) -> PathBuf {
+        Self::overrides()
+            .backup_root
+            .clone()
+            .or_else(|| self.backup_root.clone())
+            .unwrap_or_else(|| data_dir().join(BACKUPS_FOLDER_NAME))
+    }
+
+    /// Returns the database file, from the command line, the settings file or the data folder, in that
+    /// order.
+    pub fn db_path(&self) -> PathBuf {
+        Self::overrides()
+            .db_path
+            .clone()
+            .or_else(|| self.db_path.clone())
+            .unwrap_or_else(|| data_dir().join(DB_FILE_NAME))
+    }
+}
+
+/// Returns the folder the app keeps its data in by the conventions of the platform, e.g.
+/// `~/.local/share/sharks_save_haven` following the XDG base directories on Linux. Falls back to the
+/// working directory if the user has no home directory.
+pub fn data_dir() -> PathBuf {
+    ProjectDirs::from("", "", "sharks_save_haven")
+        .map(|dirs| dirs.data_dir().to_path_buf())
+        .unwrap_or_default()
+}
+
+/// Returns the settings file in the folder the app keeps its configuration in by the conventions of
+/// the platform, e.g. `~/.config/sharks_save_haven/settings.json` on Linux.
+pub fn default_settings_path() -> PathBuf {
+    ProjectDirs::from("", "", "sharks_save_haven")
+        .map(|dirs| dirs.config_dir().to_path_buf())
+        .unwrap_or_default()
+        .join(SETTINGS_FILE_NAME)
+}
@@ -1,14 +1,23 @@
-use std::{io::empty, process};
+use std::{io::empty, path::PathBuf, process, time::Duration};
 
-use chrono::{Datelike, Local, NaiveDate};
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, TimeZone};
 use egui::{Context, Id, Pos2, Vec2};
 
 use crate::{
-    db::{self, Game},
-    filesystem,
-    game_saves::GameSaves,
+    bundle::BUNDLE_EXTENSION,
+    crypto,
+    csv_import::{CsvImportReport, CSV_EXTENSION},
+    db::{self, BackupFormat, Game, Schedule},
+    error::{Error, Result},
+    filesystem::{self, FileFilter},
+    game_saves::{BackupReport, GameSaves, RestoreAction, RestorePreview, SaveSummary, VerifyReport},
+    jobs::{JobOutcome, JobRunner, JobState},
+    library::{ImportMode, LIBRARY_EXTENSION},
+    retention::RetentionPolicy,
+    scheduler::{ScheduleTiming, Scheduler},
+    settings::{Settings, DB_FILE_NAME},
+    watcher::Watcher,
     widgets::{Column, TableBuilder},
-    DB_NAME,
 };
 
 #[derive(Clone, Debug, Default)]
@@ -17,6 +26,8 @@ struct NewGameState {
     release_date_input: NaiveDate,
     platform_input: String,
     location_input: String,
+    include_input: String,
+    exclude_input: String,
 }
 
 impl NewGameState {
@@ -37,6 +48,67 @@ impl NewGameState {
             release_date_input: NaiveDate::from_ymd(today.year(), today.month(), today.day()),
             platform_input: String::new(),
             location_input: String::new(),
+            include_input: String::new(),
+            exclude_input: String::new(),
+        }
+    }
+}
+
+/// The input of the form for editing a game and the platform and save file location of its first save.
+#[derive(Clone, Debug)]
+struct EditGameState {
+    form: NewGameState,
+    /// The save whose platform and save file location are edited, `None` if the game has no save.
+    save_id: Option<i32>,
+}
+
+/// The input of the form for adding a save file location to a game or editing one.
+#[derive(Clone, Debug, Default)]
+struct SaveFormState {
+    game_id: i32,
+    /// The save being edited, `None` when adding a save.
+    save_id: Option<i32>,
+    platform_input: String,
+    location_input: String,
+    include_input: String,
+    exclude_input: String,
+}
+
+/// The input of the form for adding a backup schedule.
+#[derive(Clone, Debug)]
+struct NewScheduleState {
+    daily: bool,
+    interval_minutes: u32,
+    hour: u32,
+    minute: u32,
+    all_games: bool,
+    game_ids: Vec<i32>,
+}
+
+impl Default for NewScheduleState {
+    fn default() -> Self {
+        Self {
+            daily: false,
+            interval_minutes: 30,
+            hour: 12,
+            minute: 0,
+            all_games: true,
+            game_ids: Vec::new(),
+        }
+    }
+}
+
+/// A restore that waits for the backup passphrase to be entered.
+#[derive(Clone, Debug)]
+enum PendingRestore {
+    Restore(Game),
+    Undo(Game),
+}
+
+impl PendingRestore {
+    fn game(&self) -> &Game {
+        match self {
+            PendingRestore::Restore(game) | PendingRestore::Undo(game) => game,
         }
     }
 }
@@ -46,28 +118,151 @@ pub struct SharkGui {
     selected_item: Option<usize>,
     db: Box<db::Db>,
     fs: Box<filesystem::Filesystem>,
+    settings: Settings,
+    settings_path: PathBuf,
     add_game_window_open: bool,
     edit_game_window_open: bool,
+    edit_game: Option<EditGameState>,
     remove_game_window_open: bool,
+    remove_keep_backups: bool,
+    /// The game the details pane shows the saves of, `None` to load them again.
+    details_game_id: Option<i32>,
+    save_summaries: Vec<SaveSummary>,
+    save_window_open: bool,
+    save_form: SaveFormState,
+    /// The save waiting for the user to confirm its removal.
+    remove_save: Option<SaveSummary>,
+    verify_window_open: bool,
+    verify_reports: Vec<VerifyReport>,
+    restore_window_open: bool,
+    restore_game: Option<Game>,
+    restore_previews: Vec<RestorePreview>,
+    retention_window_open: bool,
+    retention_game: Option<Game>,
+    retention_input: RetentionPolicy,
+    retention_use_global: bool,
+    watcher: Option<Watcher>,
+    scheduler: Option<Scheduler>,
+    schedules_window_open: bool,
+    schedules: Vec<Schedule>,
+    new_schedule: NewScheduleState,
+    /// The last backup that failed to copy some files, shown until the user closes it.
+    backup_report: Option<BackupReport>,
+    /// The last operation that failed, shown until the user closes it.
+    error_message: Option<String>,
+    jobs: JobRunner,
+    storage_window_open: bool,
+    backup_root_input: String,
+    db_path_input: String,
+    passphrase_window_open: bool,
+    passphrase_input: String,
+    passphrase_confirm_input: String,
+    passphrase_error: Option<String>,
+    pending_restore: Option<PendingRestore>,
+    library_import_window_open: bool,
+    library_import_path: Option<PathBuf>,
+    library_import_mode: ImportMode,
+    csv_import_window_open: bool,
+    csv_import_path: Option<PathBuf>,
+    csv_import_back_up: bool,
+    /// The last CSV import that skipped some rows, shown until the user closes it.
+    csv_import_report: Option<CsvImportReport>,
 }
 
 impl SharkGui {
-    pub fn new() -> Self {
-        let db = db::Db::new(DB_NAME).expect("Failed to create database connection");
+    /// Opens the main window.
+    ///
+    /// # Arguments
+    ///
+    /// * `migration_backup` - The copy of the database made when it was migrated at startup, which the
+    ///   window tells the user about.
+    pub fn new(settings_path: PathBuf, migration_backup: Option<PathBuf>) -> Self {
+        let settings = Settings::load(&settings_path);
+        let db = crate::open_database(&settings.db_path()).expect("Failed to create database connection");
         let fs = filesystem::Filesystem::new();
 
-        db.create_tables().expect("Failed to create tables");
+        let migration_backup = db.create_tables().expect("Failed to create tables").or(migration_backup);
 
         let games = db.get_all_games().expect("Failed to get games");
+        let jobs = JobRunner::start(&settings.db_path()).expect("Failed to start the job runner");
 
-        Self {
+        let mut gui = Self {
             items: games,
             selected_item: None,
             db: Box::new(db),
             fs: Box::new(fs),
+            settings,
+            settings_path,
             add_game_window_open: false,
             edit_game_window_open: false,
+            edit_game: None,
+            remove_keep_backups: false,
+            details_game_id: None,
+            save_summaries: Vec::new(),
+            save_window_open: false,
+            save_form: SaveFormState::default(),
+            remove_save: None,
             remove_game_window_open: false,
+            verify_window_open: false,
+            verify_reports: Vec::new(),
+            restore_window_open: false,
+            restore_game: None,
+            restore_previews: Vec::new(),
+            retention_window_open: false,
+            retention_game: None,
+            retention_input: RetentionPolicy::default(),
+            retention_use_global: false,
+            watcher: None,
+            scheduler: None,
+            schedules_window_open: false,
+            schedules: Vec::new(),
+            new_schedule: NewScheduleState::default(),
+            backup_report: None,
+            error_message: migration_backup.map(|backup_path| crate::migration_notice(&backup_path)),
+            jobs,
+            storage_window_open: false,
+            backup_root_input: String::new(),
+            db_path_input: String::new(),
+            passphrase_window_open: false,
+            passphrase_input: String::new(),
+            passphrase_confirm_input: String::new(),
+            passphrase_error: None,
+            pending_restore: None,
+            library_import_window_open: false,
+            library_import_path: None,
+            library_import_mode: ImportMode::Merge,
+            csv_import_window_open: false,
+            csv_import_path: None,
+            csv_import_back_up: true,
+            csv_import_report: None,
+        };
+
+        // The scheduler and watcher can't take encrypted backups until the passphrase was entered
+        if gui.settings.backup_format == BackupFormat::Encrypted && !crypto::is_unlocked(&gui.settings.backup_root()) {
+            gui.open_passphrase_window();
+        }
+
+        gui.update_watcher();
+        gui.start_scheduler();
+        gui
+    }
+
+    fn start_scheduler(&mut self) {
+        match Scheduler::start(&self.settings.db_path(), &self.settings_path) {
+            Ok(scheduler) => self.scheduler = Some(scheduler),
+            Err(e) => self.error_message = Some(format!("Failed to start the backup scheduler: {}", e)),
+        }
+    }
+
+    /// Starts or stops the watcher that backs up saves when their files change, following the settings.
+    fn update_watcher(&mut self) {
+        if !self.settings.watch {
+            self.watcher = None;
+        } else if self.watcher.is_none() {
+            match Watcher::start(&self.settings.db_path(), &self.settings_path) {
+                Ok(watcher) => self.watcher = Some(watcher),
+                Err(e) => self.error_message = Some(format!("Failed to start watching save files: {}", e)),
+            }
         }
     }
 
@@ -75,11 +270,22 @@ impl SharkGui {
         self.load_add_game_window(ui);
         self.load_edit_game_window(ui);
         self.load_remove_game_window(ui);
+        self.load_save_window(ui);
+        self.load_remove_save_window(ui);
+        self.load_verify_window(ui);
+        self.load_restore_window(ui);
+        self.load_retention_window(ui);
+        self.load_schedules_window(ui);
+        self.load_backup_report_window(ui);
+        self.load_error_window(ui);
+        self.load_storage_window(ui);
+        self.load_library_import_window(ui);
+        self.load_csv_import_window(ui);
+        self.load_csv_import_report_window(ui);
+        self.load_passphrase_window(ui);
     }
 
     fn load_add_game_window(&mut self, ui: &mut egui::Ui) {
-        let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref());
-
         let default_pos = ui.available_rect_before_wrap().center();
         let mut add_game_window_open = self.add_game_window_open;
 
@@ -120,18 +326,27 @@ impl SharkGui {
 
                     ui.text_edit_singleline(&mut new_game_state.location_input);
 
-                    if ui.button("Finish").clicked() {
+                    let patterns =
+                        pattern_inputs(ui, &mut new_game_state.include_input, &mut new_game_state.exclude_input);
+
+                    if ui.add_enabled(patterns.is_some(), egui::Button::new("Finish")).clicked() {
+                        let (include_patterns, exclude_patterns) = patterns.unwrap_or_default();
                         new_game_state.new_game.release_date = new_game_state
                             .release_date_input
                             .and_hms_opt(0, 0, 0)
                             .unwrap_or_default()
                             .timestamp();
 
-                        game_save.add_game_save(
-                            new_game_state.new_game.clone(),
-                            new_game_state.location_input.clone(),
-                            new_game_state.platform_input.clone(),
-                        );
+                        // The first backup copies every save file, so it runs as a job
+                        let game = new_game_state.new_game.clone();
+                        let location = new_game_state.location_input.clone();
+                        let platform = new_game_state.platform_input.clone();
+                        self.jobs.spawn(format!("Add '{}'", game.title), &self.settings, move |game_save| {
+                            match game_save.add_game_save(game, location, platform, include_patterns, exclude_patterns) {
+                                Ok(report) => JobOutcome::SaveAdded(report),
+                                Err(e) => JobOutcome::failed(e),
+                            }
+                        });
                         ui.close_menu();
                     }
                     if ui.button("Cancel").clicked() {
@@ -144,12 +359,58 @@ impl SharkGui {
         self.add_game_window_open &= add_game_window_open;
     }
 
+    /// Opens the edit window filled in with a game and the platform and save file location of its first
+    /// save.
+    fn open_edit_game_window(&mut self, game: Game) {
+        match self.edit_game_state(game) {
+            Ok(state) => {
+                self.edit_game = Some(state);
+                self.edit_game_window_open = true;
+            }
+            Err(e) => self.error_message = Some(format!("The game could not be edited: {}", e)),
+        }
+    }
+
+    fn edit_game_state(&self, game: Game) -> Result<EditGameState> {
+        let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref(), &self.settings);
+        let game = game_save.game(game.id)?;
+        let saves = self.db.get_saves_by_game_id(game.id)?;
+
+        let mut form = NewGameState::new();
+        if let Some(release_date) = NaiveDateTime::from_timestamp_opt(game.release_date, 0) {
+            form.release_date_input = release_date.date();
+        }
+        form.new_game = game;
+
+        let save_id = match saves.first() {
+            Some(save) => {
+                let location = game_save.location(save.location_id)?;
+                form.platform_input = game_save.platform(save.platform_id)?.platform_name;
+                form.location_input = location.location_path;
+                form.include_input = location.include_patterns.join("\n");
+                form.exclude_input = location.exclude_patterns.join("\n");
+                Some(save.id)
+            }
+            None => None,
+        };
+
+        Ok(EditGameState { form, save_id })
+    }
+
     fn load_edit_game_window(&mut self, ui: &mut egui::Ui) {
-        let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref());
+        let mut state = match self.edit_game.take() {
+            Some(state) => state,
+            None => {
+                self.edit_game_window_open = false;
+                return;
+            }
+        };
+        let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref(), &self.settings);
 
         let default_pos = ui.available_rect_before_wrap().center();
 
         let mut edit_game_window_open = self.edit_game_window_open;
+        let mut saved = false;
 
         egui::Window::new("Edit game")
             .default_size(Vec2::new(400.0, 400.0))
@@ -157,75 +418,110 @@ impl SharkGui {
             .open(&mut edit_game_window_open)
             .show(ui.ctx(), |ui| {
                 ui.set_min_width(200.0);
-                ui.label("Fill in to add game");
                 ui.vertical(|ui| {
-                    let mut new_game_state = NewGameState::load(ui.ctx()).unwrap_or_default();
-                    new_game_state.new_game.id = -1;
+                    let form = &mut state.form;
 
                     ui.label("Title");
-                    ui.text_edit_singleline(&mut new_game_state.new_game.title);
+                    ui.text_edit_singleline(&mut form.new_game.title);
 
                     ui.label("Publisher");
-                    ui.text_edit_singleline(&mut new_game_state.new_game.publisher);
-                    ui.label("Platform");
-                    ui.text_edit_singleline(&mut new_game_state.platform_input);
+                    ui.text_edit_singleline(&mut form.new_game.publisher);
 
                     ui.label("Release Date (YYYYMMDD)");
-                    ui.add(egui_extras::DatePickerButton::new(
-                        &mut new_game_state.release_date_input,
-                    ));
-
-                    ui.vertical(|ui| {
-                        ui.label("Location");
-                        if ui.button("Open file…").clicked() {
-                            if let Some(path) = rfd::FileDialog::new().pick_folder() {
-                                new_game_state.location_input = path.display().to_string();
-                            }
-                        }
+                    ui.add(egui_extras::DatePickerButton::new(&mut form.release_date_input));
 
-                        ui.text_edit_singleline(&mut new_game_state.location_input);
-                    });
+                    // Games without a save have no platform or save file location to edit
+                    let mut patterns = Some((Vec::new(), Vec::new()));
+                    if state.save_id.is_some() {
+                        ui.label("Platform");
+                        ui.text_edit_singleline(&mut form.platform_input);
 
-                    ui.text_edit_singleline(&mut new_game_state.location_input);
+                        ui.vertical(|ui| {
+                            ui.label("Location");
+                            if ui.button("Open file…").clicked() {
+                                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                                    form.location_input = path.display().to_string();
+                                }
+                            }
 
-                    if ui.button("Finish").clicked() {
-                        new_game_state.new_game.release_date = new_game_state
-                            .release_date_input
-                            .and_hms_opt(0, 0, 0)
-                            .unwrap_or_default()
-                            .timestamp();
+                            ui.text_edit_singleline(&mut form.location_input);
+                        });
 
-                        game_save.add_game_save(
-                            new_game_state.new_game.clone(),
-                            new_game_state.location_input.clone(),
-                            new_game_state.platform_input.clone(),
-                        );
-                        ui.close_menu();
-                    }
-                    if ui.button("Cancel").clicked() {
-                        self.edit_game_window_open = false;
+                        patterns = pattern_inputs(ui, &mut form.include_input, &mut form.exclude_input);
                     }
 
-                    new_game_state.store(ui.ctx());
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(patterns.is_some(), egui::Button::new("Save")).clicked() {
+                            let (include_patterns, exclude_patterns) = patterns.clone().unwrap_or_default();
+                            form.new_game.release_date = form
+                                .release_date_input
+                                .and_hms_opt(0, 0, 0)
+                                .unwrap_or_default()
+                                .timestamp();
+
+                            // The game and its save are changed together or not at all
+                            let updated = self.db.in_transaction(|| -> Result<(), Error> {
+                                game_save.update_game(&form.new_game)?;
+                                if let Some(save_id) = state.save_id {
+                                    game_save.update_save(
+                                        save_id,
+                                        &form.location_input,
+                                        &form.platform_input,
+                                        &include_patterns,
+                                        &exclude_patterns,
+                                    )?;
+                                }
+                                Ok(())
+                            });
+                            match updated {
+                                Ok(()) => saved = true,
+                                Err(e) => {
+                                    self.error_message = Some(format!("The game could not be saved: {}", e));
+                                }
+                            }
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.edit_game_window_open = false;
+                        }
+                    });
                 })
             });
-        self.edit_game_window_open &= edit_game_window_open;
+        self.edit_game_window_open &= edit_game_window_open && !saved;
+
+        if saved {
+            let game_id = state.form.new_game.id;
+            self.items = self.db.get_all_games().expect("Failed to get games");
+            self.selected_item = self.items.iter().position(|game| game.id == game_id);
+            self.details_game_id = None;
+        }
+        if self.edit_game_window_open {
+            self.edit_game = Some(state);
+        }
     }
 
     fn load_remove_game_window(&mut self, ui: &mut egui::Ui) {
         let selected_item = self.selected_item; // Store the selected item in a local variable
     
-        if selected_item.is_none() {
+        if selected_item.is_none() || !self.remove_game_window_open {
             return; // Return early if no item is selected
         }
     
-        let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref());
+        let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref(), &self.settings);
+        let game_id = self.items[selected_item.unwrap()].id;
         
-        let title = game_save.get_game_title_by_id(self.items[selected_item.unwrap()].clone().id);
+        let title = match game_save.get_game_title_by_id(game_id) {
+            Ok(title) => title,
+            Err(e) => {
+                self.error_message = Some(format!("The game could not be removed: {}", e));
+                self.remove_game_window_open = false;
+                return;
+            }
+        };
     
         let default_pos = ui.available_rect_before_wrap().center();
     
         let mut remove_game_window_open = self.remove_game_window_open;
+        let mut remove_request = false;
     
         egui::Window::new("Remove game")
             .default_size(Vec2::new(400.0, 400.0))
@@ -233,10 +529,11 @@ impl SharkGui {
             .open(&mut remove_game_window_open)
             .show(ui.ctx(), |ui| {
                 ui.label(format!("Are you sure you want to remove \"{}\"", title));
+                ui.checkbox(&mut self.remove_keep_backups, "Keep the backup files")
+                    .on_hover_text("Only removes the game from the list and moves its backups to the \"removed\" folder");
                 ui.horizontal(|ui| {
                     if ui.button("Yes").clicked() {
-                        // Add your code here to handle the removal of the selected game
-                        ui.close_menu();
+                        remove_request = true;
                     }
     
                     if ui.button("No").clicked() {
@@ -245,93 +542,1688 @@ impl SharkGui {
                 });
             });
     
-        self.remove_game_window_open &= remove_game_window_open;
+        self.remove_game_window_open &= remove_game_window_open && !remove_request;
+
+        if remove_request {
+            let keep_backups = self.remove_keep_backups;
+            self.selected_item = None;
+            self.jobs.spawn(format!("Remove '{}'", title), &self.settings, move |game_save| {
+                match game_save.remove_game_save(game_id, keep_backups) {
+                    Ok(report) => JobOutcome::GameRemoved(report),
+                    Err(e) => JobOutcome::failed(e),
+                }
+            });
+        }
     }
     
 
-    fn file_top_menu(ui: &mut egui::Ui) {
-        if ui.button("Exit").clicked() {
-            process::exit(0);
+    /// Shows the details of the selected game with all of its save file locations next to the list of
+    /// games.
+    fn game_details_panel(&mut self, ctx: &egui::Context) {
+        let game = match self.selected_item.and_then(|index| self.items.get(index)).cloned() {
+            Some(game) => game,
+            None => return,
+        };
+
+        // Measuring the backups walks their files, so it's only done when something changed
+        if self.details_game_id != Some(game.id) {
+            let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref(), &self.settings);
+            self.save_summaries = match game_save.list_saves(game.id) {
+                Ok(summaries) => summaries,
+                Err(e) => {
+                    self.error_message = Some(format!("The saves of '{}' could not be listed: {}", game.title, e));
+                    Vec::new()
+                }
+            };
+            self.details_game_id = Some(game.id);
         }
-    }
 
-    fn game_top_menu(&mut self, ui: &mut egui::Ui) {
-        let add_button_response = ui.add(egui::Button::new("Add Game"));
-        if add_button_response.clicked() {
-            self.add_game_window_open = true;
-            ui.close_menu();
+        let mut add_request = false;
+        let mut edit_request = None;
+        let mut remove_request = None;
+
+        egui::SidePanel::right("game_details")
+            .resizable(true)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.heading(&game.title);
+                if !game.publisher.is_empty() {
+                    ui.label(&game.publisher);
+                }
+                ui.separator();
+
+                ui.strong("Save file locations");
+                if self.save_summaries.is_empty() {
+                    ui.label("The game has no save file locations yet");
+                }
+
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for summary in &self.save_summaries {
+                        egui::Grid::new(("save_summary_grid", summary.save.id))
+                            .num_columns(2)
+                            .show(ui, |ui| {
+                                ui.label("Platform");
+                                ui.label(&summary.platform_name);
+                                ui.end_row();
+
+                                ui.label("Path");
+                                ui.label(&summary.location.location_path);
+                                ui.end_row();
+
+                                ui.label("Last backup");
+                                ui.label(summary.last_backup.map_or_else(|| "never".to_string(), format_timestamp));
+                                ui.end_row();
+
+                                ui.label("Size");
+                                ui.label(format!("{} bytes", summary.backup_size));
+                                ui.end_row();
+                            });
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Edit…").clicked() {
+                                edit_request = Some(summary.clone());
+                            }
+                            if ui.button("Remove…").clicked() {
+                                remove_request = Some(summary.clone());
+                            }
+                        });
+                        ui.separator();
+                    }
+                });
+
+                if ui.button("Add save file location…").clicked() {
+                    add_request = true;
+                }
+            });
+
+        if add_request {
+            self.save_form = SaveFormState { game_id: game.id, ..Default::default() };
+            self.save_window_open = true;
         }
-        let edit_button_response = ui.add(egui::Button::new("Edit Game"));
-        if edit_button_response.clicked() {
-            if self.selected_item.is_some() {
-                self.edit_game_window_open = true;
-                ui.close_menu();
-            }
+        if let Some(summary) = edit_request {
+            self.save_form = SaveFormState {
+                game_id: game.id,
+                save_id: Some(summary.save.id),
+                platform_input: summary.platform_name,
+                location_input: summary.location.location_path,
+                include_input: summary.location.include_patterns.join("\n"),
+                exclude_input: summary.location.exclude_patterns.join("\n"),
+            };
+            self.save_window_open = true;
         }
-        let remove_button_response = ui.add(egui::Button::new("Remove Game"));
-        if remove_button_response.clicked() && self.selected_item.is_some() {
-            self.remove_game_window_open = true;
-            ui.close_menu();
+        if remove_request.is_some() {
+            self.remove_save = remove_request;
         }
     }
-    
 
-    fn table_ui(&mut self, ui: &mut egui::Ui) {
-        let table = TableBuilder::new(ui)
-            .striped(true)
-            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .column(Column::initial(100.0).range(40.0..=300.0).resizable(true))
-            .column(
-                Column::initial(100.0)
-                    .at_least(40.0)
-                    .resizable(true)
-                    .clip(true),
-            )
-            .column(Column::remainder())
-            .min_scrolled_height(0.0)
-            .selected_row(&mut self.selected_item);
+    /// Shows the form for adding a save file location to a game or editing one.
+    fn load_save_window(&mut self, ui: &mut egui::Ui) {
+        let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref(), &self.settings);
 
-        table
-            .header(20.0, |mut header| {
-                header.col(|ui| {
-                    ui.strong("Game ID");
+        let default_pos = ui.available_rect_before_wrap().center();
+        let mut save_window_open = self.save_window_open;
+        let mut saved = false;
+        let title = if self.save_form.save_id.is_some() {
+            "Edit save file location"
+        } else {
+            "Add save file location"
+        };
+
+        egui::Window::new(title)
+            .default_size(Vec2::new(400.0, 300.0))
+            .default_pos(Pos2::new(default_pos.x - 200.0, default_pos.y - 150.0))
+            .open(&mut save_window_open)
+            .show(ui.ctx(), |ui| {
+                let form = &mut self.save_form;
+
+                ui.label("Platform");
+                ui.text_edit_singleline(&mut form.platform_input);
+
+                ui.label("Location");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut form.location_input);
+                    if ui.button("Open file…").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                            form.location_input = path.display().to_string();
+                        }
+                    }
                 });
-                header.col(|ui| {
-                    ui.strong("Publisher");
+
+                let patterns = pattern_inputs(ui, &mut form.include_input, &mut form.exclude_input);
+
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(patterns.is_some(), egui::Button::new("Save")).clicked() {
+                        let (include_patterns, exclude_patterns) = patterns.clone().unwrap_or_default();
+                        match form.save_id {
+                            Some(save_id) => match game_save.update_save(
+                                save_id,
+                                &form.location_input,
+                                &form.platform_input,
+                                &include_patterns,
+                                &exclude_patterns,
+                            ) {
+                                Ok(()) => saved = true,
+                                Err(e) => {
+                                    self.error_message =
+                                        Some(format!("The save file location could not be saved: {}", e));
+                                }
+                            },
+                            None => {
+                                // The first backup copies every save file, so it runs as a job
+                                let game_id = form.game_id;
+                                let location = form.location_input.clone();
+                                let platform = form.platform_input.clone();
+                                self.jobs.spawn(
+                                    format!("Add the save file location {}", location),
+                                    &self.settings,
+                                    move |game_save| {
+                                        match game_save.add_save(
+                                            game_id,
+                                            &location,
+                                            &platform,
+                                            &include_patterns,
+                                            &exclude_patterns,
+                                        ) {
+                                            Ok(report) => JobOutcome::SaveAdded(report),
+                                            Err(e) => JobOutcome::failed(e),
+                                        }
+                                    },
+                                );
+                                saved = true;
+                            }
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.save_window_open = false;
+                    }
                 });
-                header.col(|ui| {
-                    ui.strong("Title");
+            });
+
+        self.save_window_open &= save_window_open && !saved;
+        if saved {
+            self.details_game_id = None;
+        }
+    }
+
+    /// Asks whether to remove a save file location of a game together with its backups.
+    fn load_remove_save_window(&mut self, ui: &mut egui::Ui) {
+        let summary = match &self.remove_save {
+            Some(summary) => summary.clone(),
+            None => return,
+        };
+
+        let default_pos = ui.available_rect_before_wrap().center();
+        let mut remove_save_window_open = true;
+        let mut remove_request = false;
+
+        egui::Window::new("Remove save file location")
+            .default_size(Vec2::new(400.0, 100.0))
+            .default_pos(Pos2::new(default_pos.x - 200.0, default_pos.y - 50.0))
+            .open(&mut remove_save_window_open)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "Are you sure you want to remove the save in \"{}\" on {}? Its backups ({} bytes) are deleted.",
+                    summary.location.location_path, summary.platform_name, summary.backup_size
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Yes").clicked() {
+                        remove_request = true;
+                    }
+                    if ui.button("No").clicked() {
+                        self.remove_save = None;
+                    }
                 });
-            })
-            .body(|mut body| {
-                for (_row_index, game) in self.items.iter().enumerate() {
-                    let row_height = 18.00;
-                    body.row(row_height, |mut row| {
-                        row.col(|ui| {
-                            ui.label(game.id.to_string().clone());
-                        });
+            });
 
-                        row.col(|ui| {
-                            ui.label(game.publisher.to_string().clone());
-                        });
+        if !remove_save_window_open || remove_request {
+            self.remove_save = None;
+        }
 
-                        row.col(|ui| {
-                            ui.label(game.title.to_string().clone());
-                        });
+        if remove_request {
+            let save_id = summary.save.id;
+            let title = format!("Remove the save in {}", summary.location.location_path);
+            self.jobs.spawn(title, &self.settings, move |game_save| match game_save.remove_save(save_id) {
+                Ok(report) if report.is_complete() => {
+                    JobOutcome::Done(format!("freed {} files ({} bytes)", report.succeeded, report.bytes))
+                }
+                Ok(report) => JobOutcome::Done(format!(
+                    "freed {} files ({} bytes), {} files could not be deleted",
+                    report.succeeded,
+                    report.bytes,
+                    report.failed.len()
+                )),
+                Err(e) => JobOutcome::failed(e),
+            });
+        }
+    }
+
+    fn load_verify_window(&mut self, ui: &mut egui::Ui) {
+        let default_pos = ui.available_rect_before_wrap().center();
+
+        let mut verify_window_open = self.verify_window_open;
+
+        egui::Window::new("Verify backups")
+            .default_size(Vec2::new(400.0, 400.0))
+            .default_pos(Pos2::new(default_pos.x - 200.0, default_pos.y - 200.0))
+            .open(&mut verify_window_open)
+            .show(ui.ctx(), |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    if self.verify_reports.is_empty() {
+                        ui.label("No games to verify");
+                    }
+
+                    for report in &self.verify_reports {
+                        if report.is_intact() {
+                            ui.label(format!(
+                                "{}: {} snapshots intact",
+                                report.title,
+                                report.snapshots.len()
+                            ));
+                        } else {
+                            ui.colored_label(ui.visuals().error_fg_color, report.to_string());
+                        }
+                    }
+                });
+
+                if ui.button("Close").clicked() {
+                    self.verify_window_open = false;
+                }
+            });
+
+        self.verify_window_open &= verify_window_open;
+    }
+
+    /// Shows the files the last backup failed to copy, if there were any.
+    fn load_backup_report_window(&mut self, ui: &mut egui::Ui) {
+        let report = match &self.backup_report {
+            Some(report) if !report.is_complete() => report,
+            _ => {
+                self.backup_report = None;
+                return;
+            }
+        };
+
+        let default_pos = ui.available_rect_before_wrap().center();
+        let mut backup_report_window_open = true;
+
+        egui::Window::new("Backup problems")
+            .default_size(Vec2::new(500.0, 300.0))
+            .default_pos(Pos2::new(default_pos.x - 250.0, default_pos.y - 150.0))
+            .open(&mut backup_report_window_open)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "Created {} snapshots, but {} files or folders could not be backed up:",
+                    report.snapshot_ids.len(),
+                    report.files.failed.len()
+                ));
+                for failure in &report.failed_games {
+                    ui.colored_label(ui.visuals().error_fg_color, format!("Failed to back up {}", failure));
+                }
+
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("backup_report_grid").striped(true).show(ui, |ui| {
+                        ui.strong("File");
+                        ui.strong("Problem");
+                        ui.end_row();
+
+                        for error in &report.files.failed {
+                            ui.label(error.path.display().to_string());
+                            ui.colored_label(ui.visuals().error_fg_color, error.kind.label())
+                                .on_hover_text(&error.message);
+                            ui.end_row();
+                        }
                     });
+                });
+            });
+
+        if !backup_report_window_open {
+            self.backup_report = None;
+        }
+    }
+
+    /// Shows the error of the last operation that failed, if there is one.
+    fn load_error_window(&mut self, ui: &mut egui::Ui) {
+        let message = match &self.error_message {
+            Some(message) => message,
+            None => return,
+        };
+
+        let default_pos = ui.available_rect_before_wrap().center();
+        let mut error_window_open = true;
+        let mut dismissed = false;
+
+        egui::Window::new("Error")
+            .default_size(Vec2::new(400.0, 100.0))
+            .default_pos(Pos2::new(default_pos.x - 200.0, default_pos.y - 50.0))
+            .open(&mut error_window_open)
+            .show(ui.ctx(), |ui| {
+                ui.colored_label(ui.visuals().error_fg_color, message);
+                if ui.button("OK").clicked() {
+                    dismissed = true;
                 }
             });
+
+        if !error_window_open || dismissed {
+            self.error_message = None;
+        }
     }
-}
 
-impl eframe::App for SharkGui {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            self.load_windows(ui);
+    fn load_storage_window(&mut self, ui: &mut egui::Ui) {
+        let default_pos = ui.available_rect_before_wrap().center();
+        let overrides = Settings::overrides();
+        let mut storage_window_open = self.storage_window_open;
+        let mut apply = false;
 
-            ui.horizontal(|ui| {
-                ui.visuals_mut().button_frame = false;
-                ui.menu_button("File", Self::file_top_menu);
+        egui::Window::new("Storage locations")
+            .default_size(Vec2::new(500.0, 200.0))
+            .default_pos(Pos2::new(default_pos.x - 250.0, default_pos.y - 100.0))
+            .open(&mut storage_window_open)
+            .show(ui.ctx(), |ui| {
+                egui::Grid::new("storage_grid").num_columns(3).show(ui, |ui| {
+                    let backup_root_editable = overrides.backup_root.is_none();
+                    ui.label("Backup folder");
+                    ui.add_enabled(
+                        backup_root_editable,
+                        egui::TextEdit::singleline(&mut self.backup_root_input).desired_width(300.0),
+                    );
+                    if ui.add_enabled(backup_root_editable, egui::Button::new("Browse…")).clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                            self.backup_root_input = path.display().to_string();
+                        }
+                    }
+                    ui.end_row();
+
+                    let db_path_editable = overrides.db_path.is_none();
+                    ui.label("Database file");
+                    ui.add_enabled(
+                        db_path_editable,
+                        egui::TextEdit::singleline(&mut self.db_path_input).desired_width(300.0),
+                    );
+                    if ui.add_enabled(db_path_editable, egui::Button::new("Browse…")).clicked() {
+                        if let Some(path) = rfd::FileDialog::new().set_file_name(DB_FILE_NAME).save_file() {
+                            self.db_path_input = path.display().to_string();
+                        }
+                    }
+                    ui.end_row();
+                });
+
+                if overrides.backup_root.is_some() || overrides.db_path.is_some() {
+                    ui.label("Locations given on the command line can't be changed here.");
+                }
+                ui.label(
+                    "Changing the backup folder moves all existing backups there. If the new database \
+                     file already exists it is opened, otherwise the current database is copied there.",
+                );
+
+                let busy = self.jobs.is_busy();
+                if busy {
+                    ui.colored_label(ui.visuals().warn_fg_color, "Wait for the running jobs to finish first.");
+                }
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!busy, egui::Button::new("Apply")).clicked() {
+                        apply = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.storage_window_open = false;
+                    }
+                });
+            });
+
+        if apply {
+            self.apply_storage_locations();
+            self.storage_window_open = false;
+        }
+        self.storage_window_open &= storage_window_open;
+    }
+
+    fn load_library_import_window(&mut self, ui: &mut egui::Ui) {
+        let library_path = match self.library_import_path.clone() {
+            Some(library_path) => library_path,
+            None => return,
+        };
+
+        let default_pos = ui.available_rect_before_wrap().center();
+        let mut library_import_window_open = self.library_import_window_open;
+        let mut import = false;
+
+        egui::Window::new("Import library")
+            .default_size(Vec2::new(400.0, 200.0))
+            .default_pos(Pos2::new(default_pos.x - 200.0, default_pos.y - 100.0))
+            .open(&mut library_import_window_open)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!("Import the games in {}", library_path.display()));
+                for mode in ImportMode::ALL {
+                    ui.radio_value(&mut self.library_import_mode, mode, mode.label());
+                }
+
+                match self.library_import_mode {
+                    ImportMode::Merge => ui.label(
+                        "Saves of games that already have a save on the same platform are skipped.",
+                    ),
+                    ImportMode::Replace => ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "Every game in this library is removed first. Their backup files are kept.",
+                    ),
+                };
+
+                let busy = self.jobs.is_busy();
+                if busy {
+                    ui.colored_label(ui.visuals().warn_fg_color, "Wait for the running jobs to finish first.");
+                }
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!busy, egui::Button::new("Import")).clicked() {
+                        import = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.library_import_window_open = false;
+                    }
+                });
+            });
+
+        if import {
+            self.import_library(library_path, self.library_import_mode);
+            self.library_import_window_open = false;
+        }
+        self.library_import_window_open &= library_import_window_open;
+        if !self.library_import_window_open {
+            self.library_import_path = None;
+        }
+    }
+
+    fn load_csv_import_window(&mut self, ui: &mut egui::Ui) {
+        let csv_path = match self.csv_import_path.clone() {
+            Some(csv_path) => csv_path,
+            None => return,
+        };
+
+        let default_pos = ui.available_rect_before_wrap().center();
+        let mut csv_import_window_open = self.csv_import_window_open;
+        let mut import = false;
+
+        egui::Window::new("Import games from CSV")
+            .default_size(Vec2::new(400.0, 200.0))
+            .default_pos(Pos2::new(default_pos.x - 200.0, default_pos.y - 100.0))
+            .open(&mut csv_import_window_open)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!("Import the games in {}", csv_path.display()));
+                ui.label(
+                    "The first row names the columns: title, publisher, release date, platform and save \
+                     path. Publisher and release date may be left out.",
+                );
+                ui.checkbox(&mut self.csv_import_back_up, "Back up the imported saves now");
+
+                ui.horizontal(|ui| {
+                    if ui.button("Import").clicked() {
+                        import = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.csv_import_window_open = false;
+                    }
+                });
+            });
+
+        if import {
+            let back_up = self.csv_import_back_up;
+            let title = format!("Import games from {}", csv_path.display());
+            self.jobs.spawn(title, &self.settings, move |game_save| {
+                match game_save.import_csv(&csv_path, back_up) {
+                    Ok(report) => JobOutcome::CsvImported(report),
+                    Err(e) => JobOutcome::failed(e),
+                }
+            });
+            self.csv_import_window_open = false;
+        }
+        self.csv_import_window_open &= csv_import_window_open;
+        if !self.csv_import_window_open {
+            self.csv_import_path = None;
+        }
+    }
+
+    /// Shows the rows the last CSV import skipped, if there were any.
+    fn load_csv_import_report_window(&mut self, ui: &mut egui::Ui) {
+        let report = match &self.csv_import_report {
+            Some(report) if !report.errors.is_empty() => report,
+            _ => {
+                self.csv_import_report = None;
+                return;
+            }
+        };
+
+        let default_pos = ui.available_rect_before_wrap().center();
+        let mut csv_import_report_window_open = true;
+
+        egui::Window::new("CSV import problems")
+            .default_size(Vec2::new(500.0, 300.0))
+            .default_pos(Pos2::new(default_pos.x - 250.0, default_pos.y - 150.0))
+            .open(&mut csv_import_report_window_open)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "Imported {} games, but {} rows were skipped:",
+                    report.games_added,
+                    report.errors.len()
+                ));
+
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("csv_import_report_grid").striped(true).show(ui, |ui| {
+                        ui.strong("Line");
+                        ui.strong("Title");
+                        ui.strong("Problem");
+                        ui.end_row();
+
+                        for error in &report.errors {
+                            ui.label(error.line.to_string());
+                            ui.label(&error.title);
+                            ui.colored_label(ui.visuals().error_fg_color, &error.message);
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        if !csv_import_report_window_open {
+            self.csv_import_report = None;
+        }
+    }
+
+    /// Asks where to save the library export and writes it there.
+    fn export_library(&mut self) {
+        let library_path = rfd::FileDialog::new()
+            .set_file_name(&format!("library.{}", LIBRARY_EXTENSION))
+            .add_filter("Library export", &[LIBRARY_EXTENSION])
+            .save_file();
+
+        if let Some(library_path) = library_path {
+            self.jobs.spawn("Export library", &self.settings, move |game_save| {
+                match game_save.export_library(&library_path) {
+                    Ok(games) => JobOutcome::Done(format!("exported {} games to {}", games, library_path.display())),
+                    Err(e) => JobOutcome::failed(e),
+                }
+            });
+        }
+    }
+
+    fn import_library(&mut self, library_path: PathBuf, mode: ImportMode) {
+        let title = format!("Import library {}", library_path.display());
+        self.jobs.spawn(title, &self.settings, move |game_save| {
+            match game_save.import_library(&library_path, mode) {
+                Ok(report) => JobOutcome::LibraryImported(report),
+                Err(e) => JobOutcome::failed(e),
+            }
+        });
+    }
+
+    /// Switches to the database file and moves the backups to the folder entered in the storage window.
+    fn apply_storage_locations(&mut self) {
+        let overrides = Settings::overrides();
+
+        let db_path = PathBuf::from(self.db_path_input.trim());
+        if overrides.db_path.is_none() && !db_path.as_os_str().is_empty() && db_path != self.settings.db_path() {
+            match self.switch_database(db_path.clone()) {
+                Ok(migration_backup) => {
+                    self.error_message = migration_backup.map(|backup_path| crate::migration_notice(&backup_path));
+                }
+                Err(e) => {
+                    self.error_message =
+                        Some(format!("Failed to switch to the database {}: {}", db_path.display(), e));
+                }
+            }
+        }
+
+        // The backup root in the settings only changes once the backups arrived there
+        let backup_root = PathBuf::from(self.backup_root_input.trim());
+        let old_root = self.settings.backup_root();
+        if overrides.backup_root.is_none() && !backup_root.as_os_str().is_empty() && backup_root != old_root {
+            self.jobs.spawn(
+                format!("Move backups to {}", backup_root.display()),
+                &self.settings,
+                move |game_save| match game_save.move_backups(&old_root, &backup_root) {
+                    Ok(report) => JobOutcome::BackupsMoved(backup_root, report),
+                    Err(e) => JobOutcome::failed(e),
+                },
+            );
+        }
+    }
+
+    /// Copies the database to a new file, unless that file already exists, and connects the window and
+    /// the background threads to it.
+    ///
+    /// Returns the path of the copy of the database made before migrating it, if an existing database
+    /// made by an older version was switched to.
+    fn switch_database(&mut self, db_path: PathBuf) -> Result<Option<PathBuf>, rusqlite::Error> {
+        if !db_path.exists() {
+            if let Some(parent) = db_path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            self.db.copy_to(&db_path.display().to_string())?;
+        }
+
+        let db = crate::open_database(&db_path)?;
+        let migration_backup = db.create_tables()?;
+        let jobs = JobRunner::start(&db_path)?;
+
+        self.items = db.get_all_games()?;
+        self.selected_item = None;
+        *self.db = db;
+        self.jobs = jobs;
+        self.settings.db_path = Some(db_path);
+        self.settings
+            .save(&self.settings_path)
+            .expect("Failed to save settings");
+
+        self.scheduler = None;
+        self.start_scheduler();
+        self.watcher = None;
+        self.update_watcher();
+
+        Ok(migration_backup)
+    }
+
+    fn load_restore_window(&mut self, ui: &mut egui::Ui) {
+        let game = match &self.restore_game {
+            Some(game) => game.clone(),
+            None => return,
+        };
+
+        let default_pos = ui.available_rect_before_wrap().center();
+
+        let mut restore_window_open = self.restore_window_open;
+
+        egui::Window::new("Restore backup")
+            .default_size(Vec2::new(500.0, 400.0))
+            .default_pos(Pos2::new(default_pos.x - 250.0, default_pos.y - 200.0))
+            .open(&mut restore_window_open)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "Restoring the latest backup of \"{}\" would make these changes:",
+                    game.title
+                ));
+
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    if self.restore_previews.is_empty() {
+                        ui.label("No saves to restore");
+                    }
+
+                    for preview in &self.restore_previews {
+                        ui.separator();
+                        ui.strong(&preview.location_path);
+                        ui.label(format!(
+                            "Backup from {}: {} to add, {} to overwrite, {} unchanged, {} not in backup",
+                            format_timestamp(preview.created_at),
+                            preview.count(RestoreAction::Add),
+                            preview.count(RestoreAction::Overwrite),
+                            preview.count(RestoreAction::Unchanged),
+                            preview.count(RestoreAction::NotInBackup)
+                        ));
+
+                        egui::Grid::new(("restore_preview", preview.save_id))
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("File");
+                                ui.strong("Action");
+                                ui.strong("Backup");
+                                ui.strong("Current");
+                                ui.strong("");
+                                ui.end_row();
+
+                                for entry in &preview.entries {
+                                    ui.label(&entry.path);
+                                    match entry.action {
+                                        RestoreAction::Overwrite => {
+                                            ui.colored_label(ui.visuals().warn_fg_color, entry.action.label())
+                                        }
+                                        _ => ui.label(entry.action.label()),
+                                    };
+                                    ui.label(entry.backup.map(format_file_info).unwrap_or_default());
+                                    ui.label(entry.current.map(format_file_info).unwrap_or_default());
+                                    ui.label(match entry.backup_is_newer() {
+                                        Some(true) => "backup is newer",
+                                        Some(false) => "backup is older",
+                                        None => "",
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let has_changes = self.restore_previews.iter().any(RestorePreview::has_changes);
+                    if ui.add_enabled(has_changes, egui::Button::new("Restore")).clicked() {
+                        self.request_restore(PendingRestore::Restore(game.clone()));
+                        self.restore_window_open = false;
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        self.restore_window_open = false;
+                    }
+                });
+            });
+
+        self.restore_window_open &= restore_window_open;
+    }
+
+    /// Runs a restore or undoes one, asking for the backup passphrase first if it's needed.
+    fn request_restore(&mut self, restore: PendingRestore) {
+        let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref(), &self.settings);
+        match game_save.needs_passphrase(restore.game().id) {
+            Ok(true) => {
+                self.open_passphrase_window();
+                self.pending_restore = Some(restore);
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                self.error_message = Some(format!("The game could not be restored: {}", e));
+                return;
+            }
+        }
+
+        match restore {
+            PendingRestore::Restore(game) => self.jobs.spawn(
+                format!("Restore '{}'", game.title),
+                &self.settings,
+                move |game_save| match game_save.restore_game(game.id) {
+                    Ok(pre_restore) => JobOutcome::Done(format!(
+                        "restored, previous files kept in {} pre-restore snapshots",
+                        pre_restore.len()
+                    )),
+                    Err(e) => JobOutcome::failed(e),
+                },
+            ),
+            PendingRestore::Undo(game) => self.jobs.spawn(
+                format!("Undo the last restore of '{}'", game.title),
+                &self.settings,
+                move |game_save| match game_save.undo_game_restore(game.id) {
+                    Ok(0) => JobOutcome::Done("not restored yet".to_string()),
+                    Ok(undone) => JobOutcome::Done(format!("undid the last restore of {} saves", undone)),
+                    Err(e) => JobOutcome::failed(e),
+                },
+            ),
+        }
+    }
+
+    fn open_passphrase_window(&mut self) {
+        self.passphrase_input.clear();
+        self.passphrase_confirm_input.clear();
+        self.passphrase_error = None;
+        self.passphrase_window_open = true;
+    }
+
+    fn load_passphrase_window(&mut self, ui: &mut egui::Ui) {
+        let default_pos = ui.available_rect_before_wrap().center();
+        let new_passphrase = !crypto::has_key_file(&self.settings.backup_root());
+        let mut passphrase_window_open = self.passphrase_window_open;
+        let mut submitted = false;
+
+        egui::Window::new("Backup passphrase")
+            .default_size(Vec2::new(400.0, 150.0))
+            .default_pos(Pos2::new(default_pos.x - 200.0, default_pos.y - 75.0))
+            .open(&mut passphrase_window_open)
+            .show(ui.ctx(), |ui| {
+                if new_passphrase {
+                    ui.label(
+                        "Choose the passphrase that encrypted backups are protected with. It can't be \
+                         recovered, encrypted backups can't be restored without it.",
+                    );
+                } else {
+                    ui.label("Enter the passphrase the backups are encrypted with.");
+                }
+
+                egui::Grid::new("passphrase_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Passphrase");
+                    let response = ui.add(egui::TextEdit::singleline(&mut self.passphrase_input).password(true));
+                    submitted |= !new_passphrase
+                        && response.lost_focus()
+                        && ui.input(|input| input.key_pressed(egui::Key::Enter));
+                    ui.end_row();
+
+                    if new_passphrase {
+                        ui.label("Repeat passphrase");
+                        ui.add(egui::TextEdit::singleline(&mut self.passphrase_confirm_input).password(true));
+                        ui.end_row();
+                    }
+                });
+
+                if let Some(error) = &self.passphrase_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                ui.horizontal(|ui| {
+                    let label = if new_passphrase { "Set passphrase" } else { "Unlock" };
+                    if ui
+                        .add_enabled(!self.passphrase_input.is_empty(), egui::Button::new(label))
+                        .clicked()
+                    {
+                        submitted = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.passphrase_window_open = false;
+                    }
+                });
+            });
+
+        if submitted && !self.passphrase_input.is_empty() {
+            self.submit_passphrase(new_passphrase);
+        }
+
+        self.passphrase_window_open &= passphrase_window_open;
+        if !self.passphrase_window_open {
+            self.passphrase_input.clear();
+            self.passphrase_confirm_input.clear();
+            self.pending_restore = None;
+        }
+    }
+
+    /// Sets or checks the backup passphrase and runs the restore that waited for it.
+    fn submit_passphrase(&mut self, new_passphrase: bool) {
+        let backup_root = self.settings.backup_root();
+        let result = if new_passphrase {
+            if self.passphrase_input != self.passphrase_confirm_input {
+                self.passphrase_error = Some("The passphrases don't match".to_string());
+                return;
+            }
+            crypto::create_key_file(&backup_root, &self.passphrase_input)
+        } else {
+            crypto::unlock(&backup_root, &self.passphrase_input)
+        };
+
+        match result {
+            Ok(()) => {
+                self.passphrase_window_open = false;
+                if let Some(restore) = self.pending_restore.take() {
+                    self.request_restore(restore);
+                }
+            }
+            Err(e) => self.passphrase_error = Some(format!("Failed to unlock the backups: {}", e)),
+        }
+    }
+
+    fn load_retention_window(&mut self, ui: &mut egui::Ui) {
+        let default_pos = ui.available_rect_before_wrap().center();
+
+        let mut retention_window_open = self.retention_window_open;
+
+        egui::Window::new("Retention policy")
+            .default_size(Vec2::new(400.0, 300.0))
+            .default_pos(Pos2::new(default_pos.x - 200.0, default_pos.y - 150.0))
+            .open(&mut retention_window_open)
+            .show(ui.ctx(), |ui| {
+                match &self.retention_game {
+                    Some(game) => {
+                        ui.label(format!("Snapshots of \"{}\" to keep when pruning", game.title));
+                        ui.checkbox(&mut self.retention_use_global, "Use the global policy");
+                    }
+                    None => {
+                        ui.label("Snapshots to keep for games without their own policy");
+                    }
+                }
+
+                ui.add_enabled_ui(!self.retention_use_global, |ui| {
+                    egui::Grid::new("retention_policy").show(ui, |ui| {
+                        optional_count(ui, "Keep last", "snapshots", &mut self.retention_input.keep_last);
+                        optional_count(ui, "Keep daily", "days", &mut self.retention_input.keep_daily);
+                        optional_count(ui, "Keep weekly", "weeks", &mut self.retention_input.keep_weekly);
+                        optional_count(ui, "Keep monthly", "months", &mut self.retention_input.keep_monthly);
+                        optional_size(ui, "Maximum size", &mut self.retention_input.max_total_size);
+                    });
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        match &self.retention_game {
+                            Some(game) if self.retention_use_global => self.db
+                                .delete_retention_policy(game.id)
+                                .expect("Failed to delete retention policy"),
+                            Some(game) => self.db
+                                .set_retention_policy(game.id, &self.retention_input)
+                                .expect("Failed to save retention policy"),
+                            None => {
+                                self.settings.retention = self.retention_input;
+                                self.settings
+                                    .save(&self.settings_path)
+                                    .expect("Failed to save settings");
+                            }
+                        }
+                        self.retention_window_open = false;
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        self.retention_window_open = false;
+                    }
+                });
+            });
+
+        self.retention_window_open &= retention_window_open;
+    }
+
+    fn open_retention_window(&mut self, game: Option<Game>) {
+        match &game {
+            Some(game) => {
+                let policy = self.db
+                    .get_retention_policy(game.id)
+                    .expect("Failed to retrieve retention policy from database");
+                self.retention_use_global = policy.is_none();
+                self.retention_input = policy.unwrap_or(self.settings.retention);
+            }
+            None => {
+                self.retention_use_global = false;
+                self.retention_input = self.settings.retention;
+            }
+        }
+
+        self.retention_game = game;
+        self.retention_window_open = true;
+    }
+
+    fn load_schedules_window(&mut self, ui: &mut egui::Ui) {
+        let default_pos = ui.available_rect_before_wrap().center();
+
+        let mut schedules_window_open = self.schedules_window_open;
+
+        egui::Window::new("Backup schedules")
+            .default_size(Vec2::new(400.0, 400.0))
+            .default_pos(Pos2::new(default_pos.x - 200.0, default_pos.y - 200.0))
+            .open(&mut schedules_window_open)
+            .show(ui.ctx(), |ui| {
+                if self.schedules.is_empty() {
+                    ui.label("No backup schedules");
+                }
+
+                let mut removed_schedule = None;
+                for schedule in &self.schedules {
+                    ui.horizontal(|ui| {
+                        let games = if schedule.all_games {
+                            "all games".to_string()
+                        } else {
+                            self.items
+                                .iter()
+                                .filter(|game| schedule.game_ids.contains(&game.id))
+                                .map(|game| game.title.as_str())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        };
+                        ui.label(format!("Back up {} {}", games, schedule.timing));
+
+                        if ui.button("Remove").clicked() {
+                            removed_schedule = Some(schedule.id);
+                        }
+                    });
+                }
+
+                if let Some(schedule_id) = removed_schedule {
+                    self.db.delete_schedule(schedule_id).expect("Failed to delete schedule");
+                    self.schedules = self.db.get_all_schedules().expect("Failed to get schedules");
+                }
+
+                ui.separator();
+                ui.strong("New schedule");
+
+                let new_schedule = &mut self.new_schedule;
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut new_schedule.daily, false, "Every");
+                    ui.add_enabled(
+                        !new_schedule.daily,
+                        egui::DragValue::new(&mut new_schedule.interval_minutes)
+                            .clamp_range(1..=10080)
+                            .suffix(" min"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut new_schedule.daily, true, "Daily at");
+                    ui.add_enabled(
+                        new_schedule.daily,
+                        egui::DragValue::new(&mut new_schedule.hour).clamp_range(0..=23),
+                    );
+                    ui.label(":");
+                    ui.add_enabled(
+                        new_schedule.daily,
+                        egui::DragValue::new(&mut new_schedule.minute).clamp_range(0..=59),
+                    );
+                });
+
+                ui.checkbox(&mut new_schedule.all_games, "All games");
+                ui.add_enabled_ui(!new_schedule.all_games, |ui| {
+                    egui::ScrollArea::vertical().max_height(150.0).show(ui, |ui| {
+                        for game in &self.items {
+                            let mut selected = new_schedule.game_ids.contains(&game.id);
+                            if ui.checkbox(&mut selected, &game.title).changed() {
+                                if selected {
+                                    new_schedule.game_ids.push(game.id);
+                                } else {
+                                    new_schedule.game_ids.retain(|game_id| *game_id != game.id);
+                                }
+                            }
+                        }
+                    });
+                });
+
+                ui.horizontal(|ui| {
+                    let can_add = new_schedule.all_games || !new_schedule.game_ids.is_empty();
+                    if ui.add_enabled(can_add, egui::Button::new("Add schedule")).clicked() {
+                        let timing = if new_schedule.daily {
+                            ScheduleTiming::Daily { minute_of_day: new_schedule.hour * 60 + new_schedule.minute }
+                        } else {
+                            ScheduleTiming::Interval { minutes: new_schedule.interval_minutes }
+                        };
+
+                        self.db
+                            .insert_schedule(&Schedule {
+                                id: -1,
+                                all_games: new_schedule.all_games,
+                                game_ids: new_schedule.game_ids.clone(),
+                                timing,
+                                // Interval schedules count from now, daily ones run at their next time
+                                last_run: Local::now().timestamp(),
+                            })
+                            .expect("Failed to save schedule");
+                        *new_schedule = NewScheduleState::default();
+                        self.schedules = self.db.get_all_schedules().expect("Failed to get schedules");
+                    }
+
+                    if ui.button("Close").clicked() {
+                        self.schedules_window_open = false;
+                    }
+                });
+            });
+
+        self.schedules_window_open &= schedules_window_open;
+    }
+
+    fn file_top_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button("Backup format", |ui| {
+            for format in BackupFormat::ALL {
+                let response =
+                    ui.radio_value(&mut self.settings.backup_format, format, format.label());
+                if response.clicked() {
+                    self.settings
+                        .save(&self.settings_path)
+                        .expect("Failed to save settings");
+                    if format == BackupFormat::Encrypted && !crypto::is_unlocked(&self.settings.backup_root()) {
+                        self.open_passphrase_window();
+                    }
+                    ui.close_menu();
+                }
+            }
+        });
+
+        let incremental_response = ui.checkbox(&mut self.settings.incremental, "Incremental backups");
+        let compare_hash_response = ui.add_enabled(
+            self.settings.incremental,
+            egui::Checkbox::new(
+                &mut self.settings.incremental_compare_hash,
+                "Compare file hashes",
+            ),
+        );
+        if incremental_response.changed() || compare_hash_response.changed() {
+            self.settings
+                .save(&self.settings_path)
+                .expect("Failed to save settings");
+        }
+
+        if ui.checkbox(&mut self.settings.watch, "Back up when saves change").changed() {
+            self.settings
+                .save(&self.settings_path)
+                .expect("Failed to save settings");
+            self.update_watcher();
+        }
+        ui.add_enabled_ui(self.settings.watch, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Quiet period");
+                let quiet_period_response = ui.add(
+                    egui::DragValue::new(&mut self.settings.watch_quiet_period_secs)
+                        .clamp_range(1..=3600)
+                        .suffix(" s"),
+                );
+                if quiet_period_response.changed() {
+                    self.settings
+                        .save(&self.settings_path)
+                        .expect("Failed to save settings");
+                }
+            });
+        });
+
+        if ui.button("Retention policy…").clicked() {
+            self.open_retention_window(None);
+            ui.close_menu();
+        }
+
+        if ui.button("Storage locations…").clicked() {
+            self.backup_root_input = self.settings.backup_root().display().to_string();
+            self.db_path_input = self.settings.db_path().display().to_string();
+            self.storage_window_open = true;
+            ui.close_menu();
+        }
+
+        ui.separator();
+
+        if ui.button("Export library…").clicked() {
+            self.export_library();
+            ui.close_menu();
+        }
+
+        if ui.button("Import library…").clicked() {
+            if let Some(library_path) = rfd::FileDialog::new()
+                .add_filter("Library export", &[LIBRARY_EXTENSION])
+                .pick_file()
+            {
+                self.library_import_path = Some(library_path);
+                self.library_import_mode = ImportMode::Merge;
+                self.library_import_window_open = true;
+            }
+            ui.close_menu();
+        }
+
+        ui.separator();
+
+        if ui.button("Exit").clicked() {
+            process::exit(0);
+        }
+    }
+
+    fn game_top_menu(&mut self, ui: &mut egui::Ui) {
+        let add_button_response = ui.add(egui::Button::new("Add Game"));
+        if add_button_response.clicked() {
+            self.add_game_window_open = true;
+            ui.close_menu();
+        }
+        let edit_button_response = ui.add(egui::Button::new("Edit Game"));
+        if edit_button_response.clicked() {
+            if let Some(game) = self.selected_item.and_then(|index| self.items.get(index)).cloned() {
+                self.open_edit_game_window(game);
+                ui.close_menu();
+            }
+        }
+        let remove_button_response = ui.add(egui::Button::new("Remove Game"));
+        if remove_button_response.clicked() && self.selected_item.is_some() {
+            self.remove_game_window_open = true;
+            ui.close_menu();
+        }
+
+        ui.separator();
+
+        let export_button_response = ui.add_enabled(self.selected_item.is_some(), egui::Button::new("Export game…"));
+        if export_button_response.clicked() {
+            if let Some(game) = self.selected_item.and_then(|index| self.items.get(index)).cloned() {
+                self.export_game(game);
+            }
+            ui.close_menu();
+        }
+        let import_button_response = ui.add(egui::Button::new("Import game…"));
+        if import_button_response.clicked() {
+            self.import_game();
+            ui.close_menu();
+        }
+        let import_csv_button_response = ui.add(egui::Button::new("Import games from CSV…"));
+        if import_csv_button_response.clicked() {
+            if let Some(csv_path) = rfd::FileDialog::new()
+                .add_filter("CSV file", &[CSV_EXTENSION])
+                .pick_file()
+            {
+                self.csv_import_path = Some(csv_path);
+                self.csv_import_window_open = true;
+            }
+            ui.close_menu();
+        }
+
+        ui.separator();
+
+        let backup_all_button_response = ui.add(egui::Button::new("Backup all games"));
+        if backup_all_button_response.clicked() {
+            self.jobs.spawn("Back up all games", &self.settings, |game_save| {
+                match game_save.backup_all_games() {
+                    Ok(report) => JobOutcome::Backup(report),
+                    Err(e) => JobOutcome::failed(e),
+                }
+            });
+            ui.close_menu();
+        }
+
+        let verify_button_response = ui.add(egui::Button::new("Verify backups"));
+        if verify_button_response.clicked() {
+            let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref(), &self.settings);
+            match game_save.verify_all_games() {
+                Ok(reports) => {
+                    self.verify_reports = reports;
+                    for report in &self.verify_reports {
+                        print!("{}", report);
+                    }
+                    self.verify_window_open = true;
+                }
+                Err(e) => self.error_message = Some(format!("The backups could not be verified: {}", e)),
+            }
+            ui.close_menu();
+        }
+
+        let schedules_button_response = ui.add(egui::Button::new("Backup schedules…"));
+        if schedules_button_response.clicked() {
+            self.schedules = self.db.get_all_schedules().expect("Failed to get schedules");
+            self.schedules_window_open = true;
+            ui.close_menu();
+        }
+
+        let prune_button_response = ui.add(egui::Button::new("Prune old backups"));
+        if prune_button_response.clicked() {
+            // Pruning waits for the backups that are running, so it runs as a job
+            self.jobs.spawn("Prune old backups", &self.settings, |game_save| {
+                match game_save.prune_all_games() {
+                    Ok(report) => JobOutcome::Done(format!(
+                        "deleted {} snapshots, freed {} bytes",
+                        report.snapshots_deleted, report.bytes_freed
+                    )),
+                    Err(e) => JobOutcome::failed(e),
+                }
+            });
+            ui.close_menu();
+        }
+    }
+    
+
+    fn table_ui(&mut self, ui: &mut egui::Ui) {
+        let game_save = GameSaves::new(self.db.as_ref(), self.fs.as_ref(), &self.settings);
+        let mut restore_request = None;
+        let mut retention_request = None;
+        let mut backup_request = None;
+        let mut undo_request = None;
+        let mut export_request = None;
+
+        let table = TableBuilder::new(ui)
+            .striped(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::initial(100.0).range(40.0..=300.0).resizable(true))
+            .column(
+                Column::initial(100.0)
+                    .at_least(40.0)
+                    .resizable(true)
+                    .clip(true),
+            )
+            .column(Column::remainder())
+            .min_scrolled_height(0.0)
+            .selected_row(&mut self.selected_item);
+
+        table
+            .header(20.0, |mut header| {
+                header.col(|ui| {
+                    ui.strong("Game ID");
+                });
+                header.col(|ui| {
+                    ui.strong("Publisher");
+                });
+                header.col(|ui| {
+                    ui.strong("Title");
+                });
+            })
+            .body(|mut body| {
+                for (_row_index, game) in self.items.iter().enumerate() {
+                    let row_height = 18.00;
+                    let row_response = body.row(row_height, |mut row| {
+                        row.col(|ui| {
+                            ui.label(game.id.to_string().clone());
+                        });
+
+                        row.col(|ui| {
+                            ui.label(game.publisher.to_string().clone());
+                        });
+
+                        row.col(|ui| {
+                            ui.label(game.title.to_string().clone());
+                        });
+                    });
+
+                    row_response.context_menu(|ui| {
+                        if ui.button("Back up now").clicked() {
+                            backup_request = Some(game.clone());
+                            ui.close_menu();
+                        }
+                        if ui.button("Restore latest backup").clicked() {
+                            restore_request = Some(game.clone());
+                            ui.close_menu();
+                        }
+                        if ui.button("Retention policy…").clicked() {
+                            retention_request = Some(game.clone());
+                            ui.close_menu();
+                        }
+                        if ui.button("Undo last restore").clicked() {
+                            undo_request = Some(game.clone());
+                            ui.close_menu();
+                        }
+                        if ui.button("Export…").clicked() {
+                            export_request = Some(game.clone());
+                            ui.close_menu();
+                        }
+                    });
+                }
+            });
+
+        if let Some(game) = restore_request {
+            match game_save.preview_game_restore(game.id) {
+                Ok(previews) => {
+                    self.restore_previews = previews;
+                    self.restore_game = Some(game);
+                    self.restore_window_open = true;
+                }
+                Err(e) => {
+                    self.error_message = Some(format!("Failed to preview the restore of '{}': {}", game.title, e));
+                }
+            }
+        }
+
+        if let Some(game) = retention_request {
+            self.open_retention_window(Some(game));
+        }
+
+        if let Some(game) = backup_request {
+            self.jobs.spawn(format!("Back up '{}'", game.title), &self.settings, move |game_save| {
+                match game_save.backup_game(game.id) {
+                    Ok(report) => JobOutcome::Backup(report),
+                    Err(e) => JobOutcome::failed(e),
+                }
+            });
+        }
+
+        if let Some(game) = undo_request {
+            self.request_restore(PendingRestore::Undo(game));
+        }
+
+        if let Some(game) = export_request {
+            self.export_game(game);
+        }
+    }
+
+    /// Asks where to save a game's bundle and exports it there.
+    fn export_game(&mut self, game: Game) {
+        let bundle_path = rfd::FileDialog::new()
+            .set_file_name(&format!("{}.{}", game.title, BUNDLE_EXTENSION))
+            .add_filter("Game bundle", &[BUNDLE_EXTENSION])
+            .save_file();
+
+        if let Some(bundle_path) = bundle_path {
+            self.jobs.spawn(format!("Export '{}'", game.title), &self.settings, move |game_save| {
+                match game_save.export_game(game.id, &bundle_path) {
+                    Ok(snapshots) => {
+                        JobOutcome::Done(format!("exported {} snapshots to {}", snapshots, bundle_path.display()))
+                    }
+                    Err(e) => JobOutcome::failed(e),
+                }
+            });
+        }
+    }
+
+    /// Asks for a bundle and imports the game in it.
+    fn import_game(&mut self) {
+        let bundle_path = rfd::FileDialog::new()
+            .add_filter("Game bundle", &[BUNDLE_EXTENSION])
+            .pick_file();
+
+        if let Some(bundle_path) = bundle_path {
+            let title = format!("Import {}", bundle_path.display());
+            self.jobs.spawn(title, &self.settings, move |game_save| {
+                match game_save.import_game(&bundle_path) {
+                    Ok(report) => JobOutcome::GameImported(report),
+                    Err(e) => JobOutcome::failed(e),
+                }
+            });
+        }
+    }
+}
+
+/// Adds a grid row with a checkbox that enables a retention rule and the number of periods it keeps.
+fn optional_count(ui: &mut egui::Ui, label: &str, unit: &str, value: &mut Option<u32>) {
+    let mut enabled = value.is_some();
+    if ui.checkbox(&mut enabled, label).changed() {
+        *value = if enabled { Some(1) } else { None };
+    }
+
+    let mut count = value.unwrap_or(1);
+    ui.add_enabled(enabled, egui::DragValue::new(&mut count).clamp_range(1..=1000));
+    ui.label(unit);
+    ui.end_row();
+
+    if enabled {
+        *value = Some(count);
+    }
+}
+
+/// Adds a grid row with a checkbox that enables the size limit and the limit in MiB.
+fn optional_size(ui: &mut egui::Ui, label: &str, value: &mut Option<u64>) {
+    const MIB: u64 = 1024 * 1024;
+
+    let mut enabled = value.is_some();
+    if ui.checkbox(&mut enabled, label).changed() {
+        *value = if enabled { Some(1024 * MIB) } else { None };
+    }
+
+    let mut size_mib = value.map_or(1024, |size| size / MIB);
+    ui.add_enabled(enabled, egui::DragValue::new(&mut size_mib).clamp_range(1..=u32::MAX));
+    ui.label("MiB");
+    ui.end_row();
+
+    if enabled {
+        *value = Some(size_mib * MIB);
+    }
+}
+
+/// Splits the glob patterns typed into a text field, one per line or separated by commas.
+fn parse_patterns(input: &str) -> Vec<String> {
+    input
+        .split(['\n', ','])
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Adds the inputs for the include and exclude patterns of a save file location, and shows why the
+/// patterns are not valid if they aren't.
+///
+/// # Returns
+///
+/// Returns the include and exclude patterns, or `None` if any of them is not a valid glob pattern.
+fn pattern_inputs(
+    ui: &mut egui::Ui,
+    include_input: &mut String,
+    exclude_input: &mut String,
+) -> Option<(Vec<String>, Vec<String>)> {
+    ui.label("Include files (one pattern per line, empty for all)");
+    ui.text_edit_multiline(include_input);
+    ui.label("Exclude files (e.g. *.log, shadercache/)");
+    ui.text_edit_multiline(exclude_input);
+
+    let include_patterns = parse_patterns(include_input);
+    let exclude_patterns = parse_patterns(exclude_input);
+    match FileFilter::new(&include_patterns, &exclude_patterns) {
+        Ok(_) => Some((include_patterns, exclude_patterns)),
+        Err(e) => {
+            ui.colored_label(ui.visuals().error_fg_color, format!("Invalid pattern: {}", e));
+            None
+        }
+    }
+}
+
+/// Formats the size and modification time of a file for the restore preview.
+fn format_file_info((size, mtime): (u64, i64)) -> String {
+    format!("{} bytes, {}", size, format_timestamp(mtime))
+}
+
+/// Formats a unix timestamp in local time, or returns "unknown" for 0.
+fn format_timestamp(timestamp: i64) -> String {
+    match Local.timestamp_opt(timestamp, 0).single() {
+        Some(time) if timestamp != 0 => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        _ => "unknown".to_string(),
+    }
+}
+
+impl SharkGui {
+    /// Reports the jobs that finished since the last frame.
+    fn handle_finished_jobs(&mut self) {
+        for (title, outcome) in self.jobs.finished_jobs() {
+            println!("{}: {}", title, outcome.label());
+            // Any job may have added or deleted backups of the game shown in the details pane
+            self.details_game_id = None;
+
+            match outcome {
+                JobOutcome::Backup(report) => {
+                    if !report.is_complete() {
+                        print!("{}", report);
+                    }
+                    self.backup_report = Some(report);
+                }
+                JobOutcome::SaveAdded(report) => {
+                    self.items = self.db.get_all_games().expect("Failed to get games");
+                    self.backup_report = Some(report);
+                }
+                JobOutcome::GameImported(report) => {
+                    self.items = self.db.get_all_games().expect("Failed to get games");
+                    self.selected_item = self.items.iter().position(|game| game.id == report.game_id);
+                }
+                JobOutcome::GameRemoved(report) => {
+                    self.items = self.db.get_all_games().expect("Failed to get games");
+                    self.selected_item = None;
+                    if !report.files.is_complete() {
+                        self.error_message = Some(format!("{}:\n{}", report, report.files));
+                    }
+                }
+                JobOutcome::CsvImported(report) => {
+                    self.items = self.db.get_all_games().expect("Failed to get games");
+                    if !report.backup.is_complete() {
+                        self.backup_report = Some(report.backup.clone());
+                    }
+                    self.csv_import_report = Some(report);
+                }
+                JobOutcome::LibraryImported(report) => {
+                    if !report.conflicts.is_empty() {
+                        let conflicts: Vec<String> =
+                            report.conflicts.iter().map(|conflict| conflict.to_string()).collect();
+                        self.error_message = Some(format!("{}:\n{}", report, conflicts.join("\n")));
+                    }
+                    self.items = self.db.get_all_games().expect("Failed to get games");
+                    self.selected_item = None;
+                }
+                JobOutcome::BackupsMoved(backup_root, report) => {
+                    if !report.old_files.is_complete() {
+                        self.error_message = Some(format!(
+                            "Some files could not be removed from the old backup folder:\n{}",
+                            report.old_files
+                        ));
+                    }
+                    self.settings.backup_root = Some(backup_root);
+                    self.settings
+                        .save(&self.settings_path)
+                        .expect("Failed to save settings");
+                }
+                JobOutcome::Failed(error) => {
+                    self.error_message = Some(format!("{} failed: {}", title, error));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Shows the scheduled and automatic backups that failed since the last frame.
+    fn handle_background_failures(&mut self) {
+        let mut failures = Vec::new();
+        if let Some(scheduler) = &self.scheduler {
+            failures.extend(scheduler.take_failures());
+        }
+        if let Some(watcher) = &self.watcher {
+            failures.extend(watcher.take_failures());
+        }
+
+        if !failures.is_empty() {
+            if let Some(error_message) = self.error_message.take() {
+                failures.insert(0, error_message);
+            }
+            self.error_message = Some(failures.join("\n"));
+        }
+    }
+
+    /// Shows the progress of the queued and running jobs and the outcome of the recent ones.
+    fn jobs_panel(&mut self, ctx: &egui::Context) {
+        if self.jobs.jobs().is_empty() {
+            return;
+        }
+
+        egui::TopBottomPanel::bottom("jobs_panel").show(ctx, |ui| {
+            for job in self.jobs.jobs().iter().filter(|job| !job.is_finished()) {
+                ui.horizontal(|ui| {
+                    ui.label(&job.title);
+
+                    match job.state() {
+                        JobState::Running => {
+                            let (files_done, files_total) = job.progress.files();
+                            let (bytes_done, bytes_total) = job.progress.bytes();
+                            ui.add(
+                                egui::ProgressBar::new(job.progress.fraction())
+                                    .desired_width(200.0)
+                                    .text(format!(
+                                        "{}/{} files, {}/{} bytes",
+                                        files_done, files_total, bytes_done, bytes_total
+                                    )),
+                            );
+                        }
+                        _ => {
+                            ui.label("queued");
+                        }
+                    }
+
+                    let cancelling = job.progress.is_cancelled();
+                    if ui
+                        .add_enabled(!cancelling, egui::Button::new(if cancelling { "Cancelling…" } else { "Cancel" }))
+                        .clicked()
+                    {
+                        self.jobs.cancel(job.id);
+                    }
+                });
+
+                let current_file = job.progress.current_file();
+                if !current_file.is_empty() {
+                    ui.small(current_file);
+                }
+            }
+
+            egui::CollapsingHeader::new("Recent jobs").show(ui, |ui| {
+                for job in self.jobs.jobs().iter().rev() {
+                    if let JobState::Finished(outcome) = job.state() {
+                        let text = format!("{}  {}: {}", format_timestamp(job.queued_at), job.title, outcome.label());
+                        match outcome {
+                            JobOutcome::Failed(_) => ui.colored_label(ui.visuals().error_fg_color, text),
+                            JobOutcome::Backup(report) if !report.is_complete() => {
+                                ui.colored_label(ui.visuals().warn_fg_color, text)
+                            }
+                            _ => ui.label(text),
+                        };
+                    }
+                }
+            });
+        });
+    }
+}
+
+impl eframe::App for SharkGui {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.handle_finished_jobs();
+        self.handle_background_failures();
+        self.jobs_panel(ctx);
+
+        // Keep the progress moving while jobs run in the background
+        if self.jobs.is_busy() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        } else if self.scheduler.is_some() || self.watcher.is_some() {
+            // Check for failed scheduled and automatic backups now and then
+            ctx.request_repaint_after(Duration::from_secs(5));
+        }
+
+        self.game_details_panel(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.load_windows(ui);
+
+            ui.horizontal(|ui| {
+                ui.visuals_mut().button_frame = false;
+                ui.menu_button("File", |ui| {
+                    self.file_top_menu(ui);
+                });
                 ui.menu_button("Game", |ui| {
                     self.game_top_menu(ui);
                 });
@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::{
+    db::Db,
+    filesystem::Filesystem,
+    game_saves::GameSaves,
+    jobs::FailureLog,
+    settings::Settings,
+};
+
+/// How long the watcher thread waits for file changes before checking for quiet saves and stopping.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the watcher looks for locations that were added or removed since it started.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Backs up saves automatically once their save file locations stop changing.
+///
+/// The watcher runs on its own thread with its own database connection. It stops when dropped.
+pub struct Watcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    failures: FailureLog,
+}
+
+impl Watcher {
+    /// Starts watching the save file location of every save in the database.
+    ///
+    /// The settings are read again when the settings file changed, so changes to the backup format or
+    /// the quiet period take effect without restarting the watcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_path` - The path of the database file.
+    /// * `settings_path` - The path of the settings file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the database cannot be opened or the platform's file
+    /// watching API cannot be used.
+    pub fn start(db_path: &Path, settings_path: &Path) -> Result<Self, notify::Error> {
+        let db = Db::new(&db_path.display().to_string())
+            .map_err(|e| notify::Error::generic(&e.to_string()))?;
+        let (sender, events) = mpsc::channel();
+        let file_watcher = RecommendedWatcher::new(sender, Config::default())?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let failures = FailureLog::default();
+        let mut state = WatchState {
+            db,
+            fs: Filesystem::new(),
+            settings_path: settings_path.to_path_buf(),
+            settings: Settings::load(settings_path),
+            settings_modified: settings_modified(settings_path),
+            file_watcher,
+            locations: HashMap::new(),
+            watched: HashSet::new(),
+            unwatchable: HashSet::new(),
+            pending: HashMap::new(),
+            failures: failures.clone(),
+        };
+
+        let thread_stop = Arc::clone(&stop);
+        let thread = thread::spawn(move || state.run(&events, &thread_stop));
+
+        Ok(Self { stop, thread: Some(thread), failures })
+    }
+
+    /// Returns the backups of changed saves that failed since the last call.
+    pub fn take_failures(&self) -> Vec<String> {
+        self.failures.take()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The state owned by the watcher thread.
+struct WatchState {
+    db: Db,
+    fs: Filesystem,
+    settings_path: PathBuf,
+    settings: Settings,
+    /// When the settings file was last changed as the settings were read, `None` if it doesn't exist.
+    settings_modified: Option<SystemTime>,
+    file_watcher: RecommendedWatcher,
+    /// The IDs of the saves stored in each save file location.
+    locations: HashMap<PathBuf, Vec<i32>>,
+    /// The save file locations that are being watched.
+    watched: HashSet<PathBuf>,
+    /// The save file locations that could not be watched, which are tried again on every refresh.
+    unwatchable: HashSet<PathBuf>,
+    /// The time of the last change of each save that changed since its last backup.
+    pending: HashMap<i32, Instant>,
+    failures: FailureLog,
+}
+
+impl WatchState {
+    fn run(&mut self, events: &mpsc::Receiver<notify::Result<Event>>, stop: &AtomicBool) {
+        let mut last_refresh = None;
+
+        while !stop.load(Ordering::Relaxed) {
+            if last_refresh.is_none_or(|time: Instant| time.elapsed() >= REFRESH_INTERVAL) {
+                self.refresh_locations();
+                last_refresh = Some(Instant::now());
+            }
+
+            match events.recv_timeout(POLL_INTERVAL) {
+                Ok(Ok(event)) => self.record_event(&event),
+                Ok(Err(e)) => self.failures.record(format!("Failed to watch save files: {}", e)),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+
+            self.back_up_quiet_saves();
+        }
+    }
+
+    /// Watches the save file locations added since the last refresh and the ones that could not be
+    /// watched before, e.g. because they didn't exist yet, and stops watching removed ones.
+    fn refresh_locations(&mut self) {
+        let mut locations: HashMap<PathBuf, Vec<i32>> = HashMap::new();
+        let games = match self.db.get_all_games() {
+            Ok(games) => games,
+            Err(e) => {
+                self.failures.record(format!("Failed to get the games to watch: {}", e));
+                return;
+            }
+        };
+
+        for game in games {
+            for save in self.db.get_saves_by_game_id(game.id).unwrap_or_default() {
+                if let Ok(Some(location)) = self.db.get_location(save.location_id) {
+                    locations
+                        .entry(PathBuf::from(location.location_path))
+                        .or_default()
+                        .push(save.id);
+                }
+            }
+        }
+
+        self.watched.retain(|path| {
+            let keep = locations.contains_key(path);
+            if !keep {
+                let _ = self.file_watcher.unwatch(path);
+            }
+            keep
+        });
+        self.unwatchable.retain(|path| locations.contains_key(path));
+
+        for path in locations.keys() {
+            if self.watched.contains(path) {
+                continue;
+            }
+            match self.file_watcher.watch(path, RecursiveMode::Recursive) {
+                Ok(()) => {
+                    self.watched.insert(path.clone());
+                    self.unwatchable.remove(path);
+                }
+                // Only report a location once, not on every refresh it still fails
+                Err(e) => {
+                    if self.unwatchable.insert(path.clone()) {
+                        self.failures.record(format!("Failed to watch {}: {}", path.display(), e));
+                    }
+                }
+            }
+        }
+
+        self.locations = locations;
+    }
+
+    /// Marks the saves whose save file location contains a changed path as pending.
+    fn record_event(&mut self, event: &Event) {
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+
+        for changed_path in &event.paths {
+            for (location, save_ids) in &self.locations {
+                if changed_path.starts_with(location) {
+                    for save_id in save_ids {
+                        self.pending.insert(*save_id, Instant::now());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Backs up the pending saves whose files haven't changed for the quiet period.
+    fn back_up_quiet_saves(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let modified = settings_modified(&self.settings_path);
+        if modified != self.settings_modified {
+            self.settings = Settings::load(&self.settings_path);
+            self.settings_modified = modified;
+        }
+
+        let quiet_period = Duration::from_secs(self.settings.watch_quiet_period_secs);
+        let quiet_saves: Vec<i32> = self
+            .pending
+            .iter()
+            .filter(|(_, last_change)| last_change.elapsed() >= quiet_period)
+            .map(|(save_id, _)| *save_id)
+            .collect();
+
+        let game_save = GameSaves::new(&self.db, &self.fs, &self.settings);
+        for save_id in quiet_saves {
+            self.pending.remove(&save_id);
+            self.back_up_save(&game_save, save_id);
+        }
+    }
+
+    /// Backs up a save whose files changed, unless they are the same as in its latest snapshot, and
+    /// prunes the backups of its game.
+    fn back_up_save(&self, game_save: &GameSaves, save_id: i32) {
+        let save = match self.db.get_save(save_id) {
+            Ok(Some(save)) => save,
+            _ => return,
+        };
+        let title = game_save.get_game_title_by_id(save.game_id).unwrap_or_default();
+        match game_save.backup_save_if_changed(&save) {
+            Ok(Some(_)) => {
+                if let Err(e) = game_save.prune_game(save.game_id) {
+                    self.failures.record(format!("Failed to prune the backups of '{}': {}", title, e));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                self.failures
+                    .record(format!("Failed to back up '{}' after its save files changed: {}", title, e));
+            }
+        }
+    }
+}
+
+/// Returns when the settings file was last changed, or `None` if it doesn't exist.
+fn settings_modified(settings_path: &Path) -> Option<SystemTime> {
+    fs::metadata(settings_path).and_then(|metadata| metadata.modified()).ok()
+}
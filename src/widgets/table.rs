@@ -782,7 +782,9 @@ impl<'selection, 'a> TableBody<'selection, 'a> {
     /// Add a single row with the given height.
     ///
     /// If you have many thousands of row it can be more performant to instead use [`Self::rows`] or [`Self::heterogeneous_rows`].
-    pub fn row(&mut self, height: f32, add_row_content: impl FnOnce(TableRow<'a, '_, '_>)) {
+    ///
+    /// Returns the [`Response`] of the whole row, e.g. to attach a context menu to it.
+    pub fn row(&mut self, height: f32, add_row_content: impl FnOnce(TableRow<'a, '_, '_>)) -> Response {
         let top_y = self.layout.cursor.y;
         
         let id =Id::new("table_row").with(self.row_nr);
@@ -815,6 +817,8 @@ impl<'selection, 'a> TableBody<'selection, 'a> {
         }
 
         self.row_nr += 1;
+
+        clicked
     }
 
     /// Add many rows with same height.